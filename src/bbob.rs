@@ -0,0 +1,520 @@
+use nalgebra::{DMatrix, DVector};
+use std::f64::consts::PI;
+
+// The 24 noiseless functions of the BBOB suite. The instances are generated from the
+// seed of the function and the instance as in the COCO reference implementation, so
+// that the optima, shifts and rotations match the ones of the standard suite.
+pub struct Bbob {
+  function: usize,
+  dim: usize,
+  fopt: f64,
+  xopt: DVector<f64>,
+  // Rotations drawn from the seed plus one million and from the seed.
+  r1: DMatrix<f64>,
+  r2: DMatrix<f64>,
+  // Linear transformation of the function, if it has one beyond r1.
+  m: DMatrix<f64>,
+  // Sign flips of the Schwefel function.
+  signs: DVector<f64>,
+  gallagher: Option<Gallagher>,
+}
+
+// Peaks of the Gallagher functions.
+struct Gallagher {
+  values: Vec<f64>,
+  // Scales of every peak along every dimension.
+  scales: Vec<DVector<f64>>,
+  centers: Vec<DVector<f64>>,
+}
+
+impl Bbob {
+  pub fn new(function: usize, instance: usize, dim: usize) -> Self {
+    assert!(
+      (1..=24).contains(&function),
+      "BBOB contains 24 noiseless functions from F1 to F24."
+    );
+    assert!(dim >= 2, "The dimension of BBOB functions must be at least 2.");
+    // F4 and F18 share the seed of F3 and F17.
+    let base = match function {
+      4 => 3,
+      18 => 17,
+      f => f,
+    };
+    let seed = (base + 10000 * instance) as i64;
+    let fopt = compute_fopt(base, instance);
+    let r1 = rotation(seed + 1000000, dim);
+    let r2 = rotation(seed, dim);
+    let scaling = 1f64.max((dim as f64).sqrt() / 8.);
+
+    let mut xopt = compute_xopt(seed, dim);
+    let mut signs = DVector::from_element(dim, 1.);
+    match function {
+      4 => {
+        for d in (0..dim).step_by(2) {
+          xopt[d] = xopt[d].abs();
+        }
+      }
+      5 => xopt.apply(|x| *x = if *x < 0. { -5. } else { 5. }),
+      8 => xopt *= 0.75,
+      20 => {
+        let u = unif(dim, seed);
+        signs = DVector::from_fn(dim, |d, _| if u[d] < 0.5 { -1. } else { 1. });
+        xopt = &signs * 0.5 * 4.2096874637;
+      }
+      24 => {
+        let g = gauss(dim, seed);
+        xopt = DVector::from_fn(dim, |d, _| if g[d] < 0. { -1.25 } else { 1.25 });
+      }
+      _ => {}
+    }
+
+    let conditioned = |alpha: f64| &r1 * DMatrix::from_diagonal(&lambda(alpha, dim)) * &r2;
+    let m = match function {
+      6 | 13 | 15 => conditioned(10.),
+      7 | 17 => DMatrix::from_diagonal(&lambda(10., dim)) * &r2,
+      9 | 19 => &r2 * scaling,
+      16 => conditioned(0.01),
+      18 => DMatrix::from_diagonal(&lambda(1000., dim)) * &r2,
+      23 | 24 => conditioned(100.),
+      _ => DMatrix::identity(dim, dim),
+    };
+    let gallagher = match function {
+      21 => Some(Gallagher::new(101, seed, dim, &r2)),
+      22 => Some(Gallagher::new(21, seed, dim, &r2)),
+      _ => None,
+    };
+    Bbob {
+      function,
+      dim,
+      fopt,
+      xopt,
+      r1,
+      r2,
+      m,
+      signs,
+      gallagher,
+    }
+  }
+
+  pub fn fopt(&self) -> f64 {
+    self.fopt
+  }
+
+  pub fn evaluate(&self, x: &DVector<f64>) -> f64 {
+    let d = self.dim as f64;
+    let shifted = x - &self.xopt;
+    let value = match self.function {
+      1 => shifted.norm_squared(),
+      2 => ellipsoid(&t_osz(&shifted)),
+      3 => rastrigin(&lambda(10., self.dim).component_mul(&t_asy(&t_osz(&shifted), 0.2))),
+      4 => rastrigin(&bueche_rastrigin_scaling(&t_osz(&shifted))) + 100. * penalty(x),
+      5 => linear_slope(x, &self.xopt),
+      6 => {
+        let z = &self.m * &shifted;
+        let sum: f64 = z.iter().zip(self.xopt.iter()).map(|(z, o)| if z * o > 0. { 1e4 * z * z } else { z * z }).sum();
+        osz(sum).powf(0.9)
+      }
+      7 => self.step_ellipsoid(x, &shifted),
+      8 => rosenbrock(&(shifted * 1f64.max(d.sqrt() / 8.)).add_scalar(1.)),
+      9 => rosenbrock(&(&self.m * x).add_scalar(0.5)),
+      10 => ellipsoid(&t_osz(&(&self.r1 * &shifted))),
+      11 => {
+        let z = t_osz(&(&self.r1 * &shifted));
+        1e6 * z[0] * z[0] + z.rows(1, self.dim - 1).norm_squared()
+      }
+      12 => {
+        let z = &self.r1 * t_asy(&(&self.r1 * &shifted), 0.5);
+        z[0] * z[0] + 1e6 * z.rows(1, self.dim - 1).norm_squared()
+      }
+      13 => {
+        let z = &self.m * &shifted;
+        z[0] * z[0] + 100. * z.rows(1, self.dim - 1).norm()
+      }
+      14 => {
+        let z = &self.r1 * &shifted;
+        z.iter().enumerate().map(|(k, z)| z.abs().powf(2. + 4. * k as f64 / (d - 1.))).sum::<f64>().sqrt()
+      }
+      15 => rastrigin(&(&self.m * t_asy(&t_osz(&(&self.r1 * &shifted)), 0.2))),
+      16 => weierstrass(&(&self.m * t_osz(&(&self.r1 * &shifted)))) + 10. / d * penalty(x),
+      17 | 18 => schaffers(&(&self.m * t_asy(&(&self.r1 * &shifted), 0.5))) + 10. * penalty(x),
+      19 => griewank_rosenbrock(&(&self.m * x).add_scalar(0.5)),
+      20 => self.schwefel(x),
+      21 | 22 => self.gallagher.as_ref().map_or(f64::NAN, |g| g.evaluate(&(&self.r2 * x)) + penalty(x)),
+      23 => katsuura(&(&self.m * &shifted)) + penalty(x),
+      24 => self.lunacek(x),
+      _ => unreachable!(),
+    };
+    value + self.fopt
+  }
+
+  fn step_ellipsoid(&self, x: &DVector<f64>, shifted: &DVector<f64>) -> f64 {
+    let z_hat = &self.m * shifted;
+    let rounded = z_hat.map(|z| if z.abs() > 0.5 { round(z) } else { round(10. * z) / 10. });
+    let z = &self.r1 * rounded;
+    0.1 * (z_hat[0].abs() * 1e-4).max(ellipsoid_with(&z, 100.)) + penalty(x)
+  }
+
+  fn schwefel(&self, x: &DVector<f64>) -> f64 {
+    let x_hat = 2. * self.signs.component_mul(x);
+    let twice = self.xopt.abs() * 2.;
+    let mut z_hat = x_hat.clone();
+    for k in 1..self.dim {
+      z_hat[k] += 0.25 * (x_hat[k - 1] - twice[k - 1]);
+    }
+    let z = 100. * (lambda(10., self.dim).component_mul(&(z_hat - &twice)) + &twice);
+    let penalty: f64 = z.iter().map(|z| (z.abs() - 500.).max(0.).powi(2)).sum();
+    let sum: f64 = z.iter().map(|z| z * z.abs().sqrt().sin()).sum();
+    0.01 * (penalty + 418.9828872724339 - sum / self.dim as f64)
+  }
+
+  fn lunacek(&self, x: &DVector<f64>) -> f64 {
+    let d = self.dim as f64;
+    let (mu0, depth) = (2.5, 1.);
+    let s = 1. - 0.5 / ((d + 20.).sqrt() - 4.1);
+    let mu1 = -((mu0 * mu0 - depth) / s).sqrt();
+    let x_hat = DVector::from_fn(self.dim, |k, _| 2. * x[k] * self.xopt[k].signum());
+    let z = &self.m * x_hat.add_scalar(-mu0);
+    let sphere0 = x_hat.add_scalar(-mu0).norm_squared();
+    let sphere1 = x_hat.add_scalar(-mu1).norm_squared();
+    let cosines: f64 = z.iter().map(|z| (2. * PI * z).cos()).sum();
+    sphere0.min(depth * d + s * sphere1) + 10. * (d - cosines) + 1e4 * penalty(x)
+  }
+}
+
+impl Gallagher {
+  fn new(peaks: usize, seed: i64, dim: usize, rotation: &DMatrix<f64>) -> Self {
+    let (max_condition, b, c) = match peaks {
+      101 => (1000f64.sqrt(), 10., 5.),
+      _ => (1000., 9.8, 4.9),
+    };
+    // Conditions of the peaks in a random order, the first being the global one.
+    let order = permutation(&unif(peaks - 1, seed));
+    let mut conditions = vec![max_condition];
+    let mut values = vec![10.];
+    for p in 1..peaks {
+      conditions.push(1000f64.powf(order[p - 1] as f64 / (peaks - 2) as f64));
+      values.push((p - 1) as f64 / (peaks - 2) as f64 * 8. + 1.1);
+    }
+    let scales = (0..peaks)
+      .map(|p| {
+        let order = permutation(&unif(dim, seed + 1000 * p as i64));
+        DVector::from_fn(dim, |d, _| conditions[p].powf(order[d] as f64 / (dim - 1) as f64 - 0.5))
+      })
+      .collect();
+    let u = unif(dim * peaks, seed);
+    let centers = (0..peaks)
+      .map(|p| {
+        let center = rotation * DVector::from_fn(dim, |d, _| b * u[p * dim + d] - c);
+        if p == 0 {
+          center * 0.8
+        } else {
+          center
+        }
+      })
+      .collect();
+    Gallagher {
+      values,
+      scales,
+      centers,
+    }
+  }
+
+  // `z` is the rotated point.
+  fn evaluate(&self, z: &DVector<f64>) -> f64 {
+    let factor = -0.5 / z.len() as f64;
+    let mut f = 0f64;
+    for ((value, scales), center) in self.values.iter().zip(self.scales.iter()).zip(self.centers.iter()) {
+      let distance = (z - center).map(|v| v * v).dot(scales);
+      f = f.max(value * (factor * distance).exp());
+    }
+    osz(10. - f).powi(2)
+  }
+}
+
+// Indices of the values sorted in ascending order.
+fn permutation(values: &[f64]) -> Vec<usize> {
+  let mut indices: Vec<usize> = (0..values.len()).collect();
+  indices.sort_by(|&a, &b| values[a].total_cmp(&values[b]));
+  indices
+}
+
+// Uniform numbers of the COCO reference implementation, from a Park-Miller generator
+// with a shuffle table.
+fn unif(n: usize, seed: i64) -> Vec<f64> {
+  let mut seed = seed.abs().max(1);
+  let mut table = [0i64; 32];
+  let next = |seed: i64| {
+    let tmp = seed / 127773;
+    let seed = 16807 * (seed - tmp * 127773) - 2836 * tmp;
+    if seed < 0 {
+      seed + 2147483647
+    } else {
+      seed
+    }
+  };
+  for k in (0..40).rev() {
+    seed = next(seed);
+    if k < 32 {
+      table[k] = seed;
+    }
+  }
+  let mut current = table[0];
+  let mut r = Vec::with_capacity(n);
+  for _ in 0..n {
+    seed = next(seed);
+    let slot = (current / 67108865) as usize;
+    current = table[slot];
+    table[slot] = seed;
+    let u = current as f64 / 2.147483647e9;
+    r.push(if u == 0. { 1e-99 } else { u });
+  }
+  r
+}
+
+// Normal numbers from the uniform ones with the Box-Muller transform.
+fn gauss(n: usize, seed: i64) -> Vec<f64> {
+  let u = unif(2 * n, seed);
+  (0..n)
+    .map(|k| {
+      let g = (-2. * u[k].ln()).sqrt() * (2. * PI * u[n + k]).cos();
+      if g == 0. {
+        1e-99
+      } else {
+        g
+      }
+    })
+    .collect()
+}
+
+// Optimum in [-4, 4]^dim on a grid of step 8e-4.
+fn compute_xopt(seed: i64, dim: usize) -> DVector<f64> {
+  let u = unif(dim, seed);
+  DVector::from_fn(dim, |d, _| {
+    let x = 8. * (1e4 * u[d]).floor() / 1e4 - 4.;
+    if x == 0. {
+      -1e-5
+    } else {
+      x
+    }
+  })
+}
+
+// Optimal value, rounded to two decimals and clamped to [-1000, 1000].
+fn compute_fopt(function: usize, instance: usize) -> f64 {
+  let seed = (function + 10000 * instance) as i64;
+  let ratio = gauss(1, seed)[0] / gauss(1, seed + 1)[0];
+  (round(100. * 100. * ratio) / 100.).clamp(-1000., 1000.)
+}
+
+// Random orthogonal matrix, by Gram-Schmidt on the columns of a normal matrix.
+fn rotation(seed: i64, dim: usize) -> DMatrix<f64> {
+  let g = gauss(dim * dim, seed);
+  let mut b = DMatrix::from_fn(dim, dim, |row, column| g[column * dim + row]);
+  for column in 0..dim {
+    for previous in 0..column {
+      let product = b.column(column).dot(&b.column(previous));
+      let projection = b.column(previous) * product;
+      let mut current = b.column_mut(column);
+      current -= projection;
+    }
+    let norm = b.column(column).norm();
+    b.column_mut(column).unscale_mut(norm);
+  }
+  b
+}
+
+fn round(x: f64) -> f64 {
+  (x + 0.5).floor()
+}
+
+// Diagonal of the conditioning with ratio alpha between the extreme scales.
+fn lambda(alpha: f64, dim: usize) -> DVector<f64> {
+  DVector::from_fn(dim, |d, _| alpha.powf(0.5 * d as f64 / (dim - 1) as f64))
+}
+
+// Smooth oscillations around the identity.
+fn osz(x: f64) -> f64 {
+  if x == 0. {
+    return 0.;
+  }
+  let x_hat = x.abs().ln();
+  let (c1, c2) = if x > 0. { (10., 7.9) } else { (5.5, 3.1) };
+  x.signum() * (x_hat + 0.049 * ((c1 * x_hat).sin() + (c2 * x_hat).sin())).exp()
+}
+
+fn t_osz(x: &DVector<f64>) -> DVector<f64> {
+  x.map(osz)
+}
+
+// Breaks the symmetry of the positive components.
+fn t_asy(x: &DVector<f64>, beta: f64) -> DVector<f64> {
+  let dim = x.len();
+  DVector::from_fn(dim, |d, _| {
+    if x[d] > 0. {
+      x[d].powf(1. + beta * d as f64 / (dim - 1) as f64 * x[d].sqrt())
+    } else {
+      x[d]
+    }
+  })
+}
+
+fn bueche_rastrigin_scaling(x: &DVector<f64>) -> DVector<f64> {
+  let dim = x.len();
+  DVector::from_fn(dim, |d, _| {
+    let factor = 10f64.powf(0.5 * d as f64 / (dim - 1) as f64);
+    if x[d] > 0. && d % 2 == 0 {
+      10. * factor * x[d]
+    } else {
+      factor * x[d]
+    }
+  })
+}
+
+// Squared distance out of [-5, 5]^dim.
+fn penalty(x: &DVector<f64>) -> f64 {
+  x.iter().map(|x| (x.abs() - 5.).max(0.).powi(2)).sum()
+}
+
+fn ellipsoid_with(z: &DVector<f64>, condition: f64) -> f64 {
+  let dim = z.len();
+  z.iter().enumerate().map(|(d, z)| condition.powf(d as f64 / (dim - 1) as f64) * z * z).sum()
+}
+
+fn ellipsoid(z: &DVector<f64>) -> f64 {
+  ellipsoid_with(z, 1e6)
+}
+
+fn rastrigin(z: &DVector<f64>) -> f64 {
+  let cosines: f64 = z.iter().map(|z| (2. * PI * z).cos()).sum();
+  10. * (z.len() as f64 - cosines) + z.norm_squared()
+}
+
+fn linear_slope(x: &DVector<f64>, xopt: &DVector<f64>) -> f64 {
+  let dim = x.len();
+  let mut sum = 0.;
+  for d in 0..dim {
+    let s = xopt[d].signum() * 10f64.powf(d as f64 / (dim - 1) as f64);
+    // Beyond the optimum the slope is flat.
+    let x = if x[d] * xopt[d] < 25. { x[d] } else { xopt[d] };
+    sum += 5. * s.abs() - s * x;
+  }
+  sum
+}
+
+fn rosenbrock(z: &DVector<f64>) -> f64 {
+  z.as_slice().windows(2).map(|w| 100. * (w[0] * w[0] - w[1]).powi(2) + (1. - w[0]).powi(2)).sum()
+}
+
+fn weierstrass(z: &DVector<f64>) -> f64 {
+  let terms: Vec<(f64, f64)> = (0..12).map(|k| (0.5f64.powi(k), 3f64.powi(k))).collect();
+  let f0: f64 = terms.iter().map(|(a, b)| a * (PI * b).cos()).sum();
+  let sum: f64 = z.iter().map(|z| terms.iter().map(|(a, b)| a * (2. * PI * b * (z + 0.5)).cos()).sum::<f64>()).sum();
+  10. * (sum / z.len() as f64 - f0).powi(3)
+}
+
+fn schaffers(z: &DVector<f64>) -> f64 {
+  let sum: f64 = z
+    .as_slice()
+    .windows(2)
+    .map(|w| {
+      let s = w[0] * w[0] + w[1] * w[1];
+      s.powf(0.25) * (1. + (50. * s.powf(0.1)).sin().powi(2))
+    })
+    .sum();
+  (sum / (z.len() - 1) as f64).powi(2)
+}
+
+fn griewank_rosenbrock(z: &DVector<f64>) -> f64 {
+  let sum: f64 = z
+    .as_slice()
+    .windows(2)
+    .map(|w| {
+      let s = 100. * (w[0] * w[0] - w[1]).powi(2) + (1. - w[0]).powi(2);
+      s / 4000. - s.cos()
+    })
+    .sum();
+  10. + 10. * sum / (z.len() - 1) as f64
+}
+
+fn katsuura(z: &DVector<f64>) -> f64 {
+  let d = z.len() as f64;
+  let mut product = 1.;
+  for (k, z) in z.iter().enumerate() {
+    let sum: f64 = (1..33)
+      .map(|j| {
+        let p = 2f64.powi(j);
+        (p * z - round(p * z)).abs() / p
+      })
+      .sum();
+    product *= (1. + (k + 1) as f64 * sum).powf(10. / d.powf(1.2));
+  }
+  10. / (d * d) * (product - 1.)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // Optimal values of the first instances in the COCO reference implementation.
+  const FOPT: [f64; 24] = [
+    79.48, -209.88, -462.09, -462.09, -9.21, 35.9, 92.94, 149.15, 123.83, -54.94, 76.27, -621.11, 29.97, -52.35, 1000.,
+    71.35, -16.94, -16.94, -102.55, -546.5, 40.78, -1000., 6.87, 102.61,
+  ];
+
+  // Where the function reaches its optimal value. Rosenbrock is minimal at z = 1, and
+  // Gallagher at the center of its global peak.
+  fn optimum(bbob: &Bbob) -> DVector<f64> {
+    match bbob.function {
+      9 | 19 => bbob.m.clone().try_inverse().unwrap() * DVector::from_element(bbob.dim, 0.5),
+      21 | 22 => bbob.r2.transpose() * &bbob.gallagher.as_ref().unwrap().centers[0],
+      _ => bbob.xopt.clone(),
+    }
+  }
+
+  #[test]
+  fn optimum_has_the_optimal_value() {
+    for function in 1..=24 {
+      for dim in [2, 5, 10, 20] {
+        let bbob = Bbob::new(function, 1, dim);
+        assert!(
+          (bbob.fopt() - FOPT[function - 1]).abs() < 1e-9,
+          "F{} has fopt {}",
+          function,
+          bbob.fopt()
+        );
+        let value = bbob.evaluate(&optimum(&bbob));
+        assert!(
+          (value - bbob.fopt()).abs() < 1e-8,
+          "F{} in {}D is {} above fopt at its optimum",
+          function,
+          dim,
+          value - bbob.fopt()
+        );
+      }
+    }
+  }
+
+  #[test]
+  fn optimum_is_below_its_neighbours() {
+    for function in 1..=24 {
+      let bbob = Bbob::new(function, 2, 10);
+      let optimum = optimum(&bbob);
+      for d in 0..10 {
+        for step in [-0.1, 0.1] {
+          let mut x = optimum.clone();
+          x[d] += step;
+          // The linear slope is flat beyond its optimum on the boundary.
+          if function == 5 && x[d].abs() > 5. {
+            continue;
+          }
+          assert!(
+            bbob.evaluate(&x) > bbob.fopt(),
+            "F{} is not above fopt at {}",
+            function,
+            x
+          );
+        }
+      }
+    }
+  }
+}
@@ -0,0 +1,218 @@
+use crate::compare;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Targets of the .dat files per decade of distance to the optimum, and budgets of the
+// .tdat files per decade of evaluations, as in the COCO logger.
+const TARGETS_PER_DECADE: f64 = 5.;
+const EVALUATIONS_PER_DECADE: f64 = 20.;
+
+// An attempt on a BBOB problem, iteration by iteration.
+struct Run {
+  instance: usize,
+  fopt: f64,
+  evaluations: Vec<f64>,
+  // Global best fitness of every iteration.
+  measured: Vec<f64>,
+}
+
+impl Run {
+  fn load(attempt: &Path, instance: usize) -> Result<Run, Box<dyn std::error::Error>> {
+    let summary = compare::read_json(&attempt.join("summary.json"))?;
+    let config = compare::read_json(&attempt.join("config.json"))?;
+    let measured: Vec<f64> = summary["global_best_fitness"]
+      .as_array()
+      .into_iter()
+      .flatten()
+      .map(|f| f.as_f64().unwrap_or(f64::INFINITY))
+      .collect();
+    let evaluations = summary["evaluation_count_progress"]
+      .as_array()
+      .ok_or("summary.json has no evaluation_count_progress")?
+      .iter()
+      .filter_map(|e| e.as_f64())
+      .collect();
+    let fopt = config["problem"]["optimum"].as_f64().ok_or("config.json has no optimum")?;
+    Ok(Run {
+      instance,
+      fopt,
+      evaluations,
+      measured,
+    })
+  }
+
+  // Lines of the .dat and .tdat files. An iteration is logged once the best fitness
+  // reaches a new target, or the evaluations a new budget, and the last one always is.
+  fn log(&self) -> (String, String) {
+    let header = format!(
+      "% function evaluation | noise-free fitness - Fopt ({}) | best noise-free fitness - Fopt | \
+       measured fitness | best measured fitness\n",
+      scientific(self.fopt, 12, false)
+    );
+    let (mut dat, mut tdat) = (header.clone(), header);
+    let (mut target, mut budget) = (f64::INFINITY, 1.);
+    let mut best = f64::INFINITY;
+    let last = self.measured.len().min(self.evaluations.len());
+    for t in 0..last {
+      best = best.min(self.measured[t]);
+      let line = format!(
+        "{} {} {} {} {}\n",
+        self.evaluations[t],
+        scientific(self.measured[t] - self.fopt, 9, true),
+        scientific(best - self.fopt, 9, true),
+        scientific(self.measured[t], 9, true),
+        scientific(best, 9, true)
+      );
+      if best - self.fopt < target || t + 1 == last {
+        dat.push_str(&line);
+        let exponent = (TARGETS_PER_DECADE * (best - self.fopt).log10()).floor();
+        target = 10f64.powf(exponent / TARGETS_PER_DECADE);
+      }
+      if self.evaluations[t] >= budget || t + 1 == last {
+        tdat.push_str(&line);
+        let exponent = (EVALUATIONS_PER_DECADE * self.evaluations[t].log10()).floor() + 1.;
+        budget = 10f64.powf(exponent / EVALUATIONS_PER_DECADE);
+      }
+    }
+    (dat, tdat)
+  }
+
+  // Entry of the .info file: instance, evaluations and final distance to the optimum.
+  fn info(&self) -> String {
+    let evaluations = self.evaluations.last().copied().unwrap_or(0.);
+    let best = self.measured.iter().copied().fold(f64::INFINITY, f64::min);
+    format!(
+      "{}:{}|{}",
+      self.instance,
+      evaluations,
+      scientific(best - self.fopt, 1, false)
+    )
+  }
+}
+
+// Scientific notation as printed by C, with at least two exponent digits, e.g. 1.5e+01.
+fn scientific(x: f64, precision: usize, sign: bool) -> String {
+  let formatted = match sign {
+    true => format!("{:+.*e}", precision, x),
+    false => format!("{:.*e}", precision, x),
+  };
+  match formatted.split_once('e') {
+    Some((mantissa, exponent)) => {
+      let exponent: i32 = exponent.parse().unwrap_or(0);
+      format!(
+        "{}e{}{:02}",
+        mantissa,
+        if exponent < 0 { '-' } else { '+' },
+        exponent.abs()
+      )
+    }
+    // Infinite or NaN.
+    None => formatted,
+  }
+}
+
+// Function and instance of a problem named BBOB_Fxx_Iyy.
+fn parse_problem(name: &str) -> Option<(usize, usize)> {
+  let (function, instance) = name.strip_prefix("BBOB_F")?.split_once("_I")?;
+  Some((function.parse().ok()?, instance.parse().ok()?))
+}
+
+// Writes the BBOB attempts of every optimizer of a test in the legacy COCO format to
+// `data/<test>/coco/<optimizer>`, one folder per algorithm for cocopp.
+pub fn coco(test_name: &str, dim: usize) -> Result<(), Box<dyn std::error::Error>> {
+  let directory = PathBuf::from(format!("data/{}/{}", test_name, dim));
+  let mut runs = compare::ByProblem::<Vec<Run>>::new();
+  for (problem, by_optimizer) in compare::attempts(&directory)? {
+    let (function, instance) = match parse_problem(&problem) {
+      Some(parsed) => parsed,
+      None => continue,
+    };
+    for (optimizer, attempts) in by_optimizer {
+      for attempt in attempts.iter() {
+        let run = Run::load(attempt, instance)?;
+        runs.entry(optimizer.clone()).or_default().entry(function.to_string()).or_default().push(run);
+      }
+    }
+  }
+
+  let out_directory = PathBuf::from(format!("data/{}/coco", test_name));
+  for (optimizer, by_function) in runs.iter() {
+    for (function, runs) in by_function.iter() {
+      let name = format!("bbobexp_f{}_DIM{}", function, dim);
+      let data_directory = out_directory.join(optimizer).join(format!("data_f{}", function));
+      fs::create_dir_all(&data_directory)?;
+      let (mut dat, mut tdat) = (String::new(), String::new());
+      for run in runs.iter() {
+        let (run_dat, run_tdat) = run.log();
+        dat.push_str(&run_dat);
+        tdat.push_str(&run_tdat);
+      }
+      fs::write(data_directory.join(format!("{}.dat", name)), dat)?;
+      fs::write(data_directory.join(format!("{}.tdat", name)), tdat)?;
+
+      let mut info = format!(
+        "funcId = {}, DIM = {}, Precision = 1.000e-08, algId = '{}'\n%\ndata_f{}/{}.dat",
+        function, dim, optimizer, function, name
+      );
+      for run in runs.iter() {
+        write!(info, ", {}", run.info())?;
+      }
+      info.push('\n');
+      fs::write(out_directory.join(optimizer).join(format!("{}.info", name)), info)?;
+    }
+  }
+  println!("COCO data written to {}.", out_directory.display());
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // Evaluations of the first column of the lines of a file, after the header.
+  fn evaluations(file: &str) -> Vec<&str> {
+    file.lines().skip(1).filter_map(|line| line.split(' ').next()).collect()
+  }
+
+  #[test]
+  fn numbers_are_printed_as_in_c() {
+    assert_eq!(scientific(15., 1, false), "1.5e+01");
+    assert_eq!(scientific(-0.00123, 2, true), "-1.23e-03");
+    assert_eq!(scientific(0.5, 3, true), "+5.000e-01");
+    assert_eq!(scientific(1e120, 0, false), "1e+120");
+  }
+
+  #[test]
+  fn problems_are_parsed_from_their_names() {
+    assert_eq!(parse_problem("BBOB_F03_I02"), Some((3, 2)));
+    assert_eq!(parse_problem("BBOB_F24_I15"), Some((24, 15)));
+    assert_eq!(parse_problem("CEC2017_F01"), None);
+    assert_eq!(parse_problem("BBOB_F03"), None);
+  }
+
+  #[test]
+  fn runs_log_new_targets_and_budgets() {
+    let run = Run {
+      instance: 3,
+      fopt: 10.,
+      evaluations: vec![10., 20., 30., 40.],
+      measured: vec![110., 20., 30., 10.5],
+    };
+    let (dat, tdat) = run.log();
+    assert!(dat.starts_with("% function evaluation | noise-free fitness - Fopt (1.000000000000e+01) |"));
+    // The third iteration reaches no new target, and the budgets grow slower than the
+    // evaluations.
+    assert_eq!(evaluations(&dat), vec!["10", "20", "40"]);
+    assert_eq!(evaluations(&tdat), vec!["10", "20", "30", "40"]);
+    assert_eq!(
+      dat.lines().nth(3).unwrap(),
+      "40 +5.000000000e-01 +5.000000000e-01 +1.050000000e+01 +1.050000000e+01"
+    );
+    assert_eq!(
+      tdat.lines().nth(3).unwrap(),
+      "30 +2.000000000e+01 +1.000000000e+01 +3.000000000e+01 +2.000000000e+01"
+    );
+    assert_eq!(run.info(), "3:40|5.0e-01");
+  }
+}
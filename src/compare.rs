@@ -0,0 +1,526 @@
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Significance level of all tests.
+const ALPHA: f64 = 0.05;
+
+// Critical values of the studentized range divided by sqrt(2), for the Nemenyi test at
+// ALPHA with 2 to 10 optimizers (Demšar, 2006).
+const NEMENYI_Q: [f64; 9] = [1.960, 2.343, 2.569, 2.728, 2.850, 2.949, 3.031, 3.102, 3.164];
+
+// Values by problem, then by optimizer.
+pub type ByProblem<T> = BTreeMap<String, BTreeMap<String, T>>;
+
+// Final best fitness of every attempt.
+type Results = ByProblem<Vec<f64>>;
+
+// Compares the optimizers of a test on every problem they all ran, and writes the
+// tables to comparison.md and comparison.tex in `data/<test>/<dim>`.
+pub fn compare(test_name: &str, dim: usize) -> Result<(), Box<dyn std::error::Error>> {
+  let directory = PathBuf::from(format!("data/{}/{}", test_name, dim));
+  let results = load_results(&directory)?;
+  let mut optimizers: Vec<String> = results.values().flat_map(|by_optimizer| by_optimizer.keys().cloned()).collect();
+  optimizers.sort();
+  optimizers.dedup();
+  let problems: Vec<&String> =
+    results.keys().filter(|problem| optimizers.iter().all(|name| results[*problem].contains_key(name))).collect();
+  if optimizers.len() < 2 || problems.is_empty() {
+    return Err(
+      format!(
+        "No problem was run by all of at least two optimizers in {}.",
+        directory.display()
+      )
+      .into(),
+    );
+  }
+
+  let tables = tables(&results, &problems, &optimizers);
+  let markdown: Vec<String> = tables.iter().map(|table| table.markdown()).collect();
+  let latex: Vec<String> = tables.iter().map(|table| table.latex()).collect();
+  fs::write(directory.join("comparison.md"), markdown.join("\n"))?;
+  fs::write(directory.join("comparison.tex"), latex.join("\n"))?;
+  println!("{}", markdown.join("\n"));
+  Ok(())
+}
+
+// Directories of the finished attempts, i.e. with a summary.json, under
+// `<optimizer>/<problem>/<attempt>`, by problem and optimizer, in attempt order.
+pub fn attempts(directory: &Path) -> Result<ByProblem<Vec<PathBuf>>, Box<dyn std::error::Error>> {
+  let mut attempts = ByProblem::<Vec<PathBuf>>::new();
+  for optimizer in fs::read_dir(directory)? {
+    let optimizer = optimizer?.path();
+    if !optimizer.is_dir() {
+      continue;
+    }
+    for problem in fs::read_dir(&optimizer)? {
+      let problem = problem?.path();
+      if !problem.is_dir() {
+        continue;
+      }
+      let mut finished = Vec::new();
+      for attempt in fs::read_dir(&problem)? {
+        let attempt = attempt?.path();
+        if attempt.join("summary.json").exists() {
+          finished.push(attempt);
+        }
+      }
+      finished.sort_by_key(|attempt| {
+        (
+          file_name(attempt).parse::<usize>().unwrap_or(usize::MAX),
+          file_name(attempt),
+        )
+      });
+      if !finished.is_empty() {
+        attempts.entry(file_name(&problem)).or_default().insert(file_name(&optimizer), finished);
+      }
+    }
+  }
+  Ok(attempts)
+}
+
+pub fn read_json(path: &Path) -> Result<Value, Box<dyn std::error::Error>> {
+  Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+}
+
+// Lowest global best fitness of every iteration of an attempt, so far.
+pub fn best_so_far(summary: &Value) -> Vec<f64> {
+  let mut best = f64::INFINITY;
+  let progress = summary["global_best_fitness"].as_array().into_iter().flatten();
+  progress
+    .map(|f| {
+      best = best.min(f.as_f64().unwrap_or(f64::INFINITY));
+      best
+    })
+    .collect()
+}
+
+// The result of an attempt is the lowest global best fitness of the run.
+fn load_results(directory: &Path) -> Result<Results, Box<dyn std::error::Error>> {
+  let mut results = Results::new();
+  for (problem, by_optimizer) in attempts(directory)? {
+    for (optimizer, attempts) in by_optimizer {
+      let mut finals = Vec::new();
+      for attempt in attempts.iter() {
+        let summary = read_json(&attempt.join("summary.json"))?;
+        finals.push(best_so_far(&summary).last().copied().unwrap_or(f64::INFINITY));
+      }
+      results.entry(problem.clone()).or_default().insert(optimizer, finals);
+    }
+  }
+  Ok(results)
+}
+
+fn file_name(path: &Path) -> String {
+  path.file_name().unwrap().to_string_lossy().into_owned()
+}
+
+fn tables(results: &Results, problems: &[&String], optimizers: &[String]) -> Vec<Table> {
+  let (k, n) = (optimizers.len(), problems.len());
+
+  // Ranks of the mean results on every problem, and their average over the suite.
+  let ranks: Vec<Vec<f64>> = problems
+    .iter()
+    .map(|problem| average_ranks(&optimizers.iter().map(|name| mean(&results[*problem][name])).collect::<Vec<_>>()))
+    .collect();
+  let average: Vec<f64> = (0..k).map(|j| ranks.iter().map(|r| r[j]).sum::<f64>() / n as f64).collect();
+  let control = (0..k).min_by(|&a, &b| average[a].total_cmp(&average[b])).unwrap();
+
+  // Per-problem results, marked with the rank-sum test against the control.
+  let mut per_problem = Table::new(
+    format!(
+      "Mean (std) of the final best fitness. +/-/= : significantly better/worse/no different than {} \
+       (Wilcoxon rank-sum, p < {}).",
+      optimizers[control], ALPHA
+    ),
+    std::iter::once("Problem".to_owned()).chain(optimizers.iter().cloned()).collect(),
+  );
+  // Wins, ties and losses of the row against the column.
+  let mut wtl = vec![vec![(0, 0, 0); k]; k];
+  for problem in problems.iter() {
+    let by_optimizer = &results[*problem];
+    let means: Vec<f64> = optimizers.iter().map(|name| mean(&by_optimizer[name])).collect();
+    let best = (0..k).min_by(|&a, &b| means[a].total_cmp(&means[b])).unwrap();
+    let mut row = vec![((*problem).clone(), false)];
+    for (j, name) in optimizers.iter().enumerate() {
+      let mark = match j == control {
+        true => "",
+        false => match outcome(&by_optimizer[name], &by_optimizer[&optimizers[control]]) {
+          Outcome::Win => " +",
+          Outcome::Loss => " -",
+          Outcome::Tie => " =",
+        },
+      };
+      let cell = format!("{:.4e} ({:.2e}){}", means[j], std(&by_optimizer[name]), mark);
+      row.push((cell, j == best));
+    }
+    per_problem.rows.push(row);
+
+    for a in 0..k {
+      for b in 0..k {
+        if a != b {
+          let counts = &mut wtl[a][b];
+          match outcome(&by_optimizer[&optimizers[a]], &by_optimizer[&optimizers[b]]) {
+            Outcome::Win => counts.0 += 1,
+            Outcome::Tie => counts.1 += 1,
+            Outcome::Loss => counts.2 += 1,
+          }
+        }
+      }
+    }
+  }
+
+  let mut wins = Table::new(
+    "Wins/ties/losses of the row against the column (Wilcoxon rank-sum).".to_owned(),
+    std::iter::once(String::new()).chain(optimizers.iter().cloned()).collect(),
+  );
+  for (a, name) in optimizers.iter().enumerate() {
+    let mut row = vec![(name.clone(), false)];
+    for (b, (w, t, l)) in wtl[a].iter().enumerate() {
+      row.push(match a == b {
+        true => ("-".to_owned(), false),
+        false => (format!("{}/{}/{}", w, t, l), false),
+      });
+    }
+    wins.rows.push(row);
+  }
+
+  // Friedman over the suite, then Holm against the control and the Nemenyi critical
+  // difference of the average ranks.
+  let (chi_squared, p) = friedman(&average, n);
+  let se = (k as f64 * (k as f64 + 1.) / (6. * n as f64)).sqrt();
+  let holm = holm(
+    &(0..k).map(|j| 2. * (1. - normal_cdf((average[j] - average[control]).abs() / se))).collect::<Vec<_>>(),
+    control,
+  );
+  let cd = match NEMENYI_Q.get(k - 2) {
+    Some(q) => format!("{:.3}", q * se),
+    None => "n/a".to_owned(),
+  };
+  let mut suite = Table::new(
+    format!(
+      "Average ranks over {} problems. Friedman chi2 = {:.3}, p = {:.3e}. Nemenyi critical difference = {}. \
+       Holm p-values against {}.",
+      n, chi_squared, p, cd, optimizers[control]
+    ),
+    vec!["Optimizer".to_owned(), "Average rank".to_owned(), "Holm p".to_owned()],
+  );
+  for (j, name) in optimizers.iter().enumerate() {
+    let holm = match j == control {
+      true => ("-".to_owned(), false),
+      false => (format!("{:.3e}", holm[j]), holm[j] < ALPHA),
+    };
+    suite.rows.push(vec![
+      (name.clone(), false),
+      (format!("{:.3}", average[j]), j == control),
+      holm,
+    ]);
+  }
+
+  vec![per_problem, wins, suite]
+}
+
+enum Outcome {
+  Win,
+  Tie,
+  Loss,
+}
+
+// Whether `a` is significantly better than `b`, i.e. lower, by the rank-sum test.
+fn outcome(a: &[f64], b: &[f64]) -> Outcome {
+  if rank_sum_test(a, b) >= ALPHA {
+    return Outcome::Tie;
+  }
+  match median(a) < median(b) {
+    true => Outcome::Win,
+    false => Outcome::Loss,
+  }
+}
+
+fn mean(values: &[f64]) -> f64 {
+  values.iter().sum::<f64>() / values.len() as f64
+}
+
+fn std(values: &[f64]) -> f64 {
+  let mean = mean(values);
+  (values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / values.len() as f64).sqrt()
+}
+
+fn median(values: &[f64]) -> f64 {
+  let mut sorted = values.to_vec();
+  sorted.sort_by(|a, b| a.total_cmp(b));
+  let n = sorted.len();
+  (sorted[(n - 1) / 2] + sorted[n / 2]) / 2.
+}
+
+// Ranks starting at 1, the lowest value first. Ties get the average of their ranks.
+fn average_ranks(values: &[f64]) -> Vec<f64> {
+  let mut order: Vec<usize> = (0..values.len()).collect();
+  order.sort_by(|&a, &b| values[a].total_cmp(&values[b]));
+  let mut ranks = vec![0.; values.len()];
+  let mut start = 0;
+  while start < order.len() {
+    let mut end = start;
+    while end + 1 < order.len() && values[order[end + 1]] == values[order[start]] {
+      end += 1;
+    }
+    for &idx in order[start..=end].iter() {
+      ranks[idx] = (start + end) as f64 / 2. + 1.;
+    }
+    start = end + 1;
+  }
+  ranks
+}
+
+// Two-sided p-value of the Wilcoxon rank-sum (Mann-Whitney U) test, with the normal
+// approximation corrected for ties and continuity.
+fn rank_sum_test(a: &[f64], b: &[f64]) -> f64 {
+  let (n1, n2) = (a.len() as f64, b.len() as f64);
+  let all: Vec<f64> = a.iter().chain(b.iter()).copied().collect();
+  let ranks = average_ranks(&all);
+  let u = ranks[..a.len()].iter().sum::<f64>() - n1 * (n1 + 1.) / 2.;
+
+  let n = n1 + n2;
+  let mut sorted = all.clone();
+  sorted.sort_by(|x, y| x.total_cmp(y));
+  let ties: f64 = sorted
+    .chunk_by(|x, y| x == y)
+    .map(|group| {
+      let t = group.len() as f64;
+      t * t * t - t
+    })
+    .sum();
+  let variance = n1 * n2 / 12. * ((n + 1.) - ties / (n * (n - 1.)));
+  if variance <= 0. {
+    return 1.;
+  }
+  let z = ((u - n1 * n2 / 2.).abs() - 0.5).max(0.) / variance.sqrt();
+  2. * (1. - normal_cdf(z))
+}
+
+// Friedman statistic of the average ranks of k optimizers over n problems, and its
+// chi-squared p-value.
+fn friedman(average: &[f64], n: usize) -> (f64, f64) {
+  let k = average.len() as f64;
+  let sum: f64 = average.iter().map(|r| r * r).sum();
+  let chi_squared = 12. * n as f64 / (k * (k + 1.)) * (sum - k * (k + 1.).powi(2) / 4.);
+  (chi_squared, regularized_gamma_q((k - 1.) / 2., chi_squared / 2.))
+}
+
+// Holm-adjusted p-values of the comparisons against the control, which is left NaN.
+fn holm(p: &[f64], control: usize) -> Vec<f64> {
+  let mut order: Vec<usize> = (0..p.len()).filter(|&j| j != control).collect();
+  order.sort_by(|&a, &b| p[a].total_cmp(&p[b]));
+  let m = order.len();
+  let mut adjusted = vec![f64::NAN; p.len()];
+  let mut running: f64 = 0.;
+  for (i, &j) in order.iter().enumerate() {
+    running = running.max(((m - i) as f64 * p[j]).min(1.));
+    adjusted[j] = running;
+  }
+  adjusted
+}
+
+fn normal_cdf(x: f64) -> f64 {
+  0.5 * erfc(-x / std::f64::consts::SQRT_2)
+}
+
+// Complementary error function, with a relative error below 1.2e-7 (Numerical Recipes).
+fn erfc(x: f64) -> f64 {
+  let z = x.abs();
+  let t = 1. / (1. + 0.5 * z);
+  let poly = -z * z - 1.26551223
+    + t
+      * (1.00002368
+        + t
+          * (0.37409196
+            + t
+              * (0.09678418
+                + t
+                  * (-0.18628806
+                    + t * (0.27886807 + t * (-1.13520398 + t * (1.48851587 + t * (-0.82215223 + t * 0.17087277))))))));
+  let r = t * poly.exp();
+  match x >= 0. {
+    true => r,
+    false => 2. - r,
+  }
+}
+
+// Logarithm of the gamma function (Lanczos approximation).
+fn ln_gamma(x: f64) -> f64 {
+  const COEFFICIENTS: [f64; 6] = [
+    76.18009172947146,
+    -86.50532032941677,
+    24.01409824083091,
+    -1.231739572450155,
+    0.1208650973866179e-2,
+    -0.5395239384953e-5,
+  ];
+  let tmp = x + 5.5 - (x + 0.5) * (x + 5.5).ln();
+  let series: f64 =
+    1.000000000190015 + COEFFICIENTS.iter().enumerate().map(|(j, c)| c / (x + 1. + j as f64)).sum::<f64>();
+  -tmp + (2.5066282746310005 * series / x).ln()
+}
+
+// Upper regularized incomplete gamma function Q(a, x), by its series below a + 1 and
+// its continued fraction above.
+fn regularized_gamma_q(a: f64, x: f64) -> f64 {
+  if x <= 0. {
+    return 1.;
+  }
+  let prefactor = (-x + a * x.ln() - ln_gamma(a)).exp();
+  if x < a + 1. {
+    let (mut term, mut sum, mut ap) = (1. / a, 1. / a, a);
+    for _ in 0..1000 {
+      ap += 1.;
+      term *= x / ap;
+      sum += term;
+      if term.abs() < sum.abs() * 1e-15 {
+        break;
+      }
+    }
+    return 1. - sum * prefactor;
+  }
+  let tiny = 1e-300;
+  let mut b = x + 1. - a;
+  let mut c = 1. / tiny;
+  let mut d = 1. / b;
+  let mut h = d;
+  for i in 1..1000 {
+    let an = -(i as f64) * (i as f64 - a);
+    b += 2.;
+    d = an * d + b;
+    if d.abs() < tiny {
+      d = tiny;
+    }
+    c = b + an / c;
+    if c.abs() < tiny {
+      c = tiny;
+    }
+    d = 1. / d;
+    let delta = d * c;
+    h *= delta;
+    if (delta - 1.).abs() < 1e-15 {
+      break;
+    }
+  }
+  prefactor * h
+}
+
+// A table of the report, rendered to Markdown or LaTeX. Cells flagged true are bold.
+struct Table {
+  caption: String,
+  header: Vec<String>,
+  rows: Vec<Vec<(String, bool)>>,
+}
+
+impl Table {
+  fn new(caption: String, header: Vec<String>) -> Table {
+    Table {
+      caption,
+      header,
+      rows: Vec::new(),
+    }
+  }
+
+  fn markdown(&self) -> String {
+    let mut out = format!("{}\n\n| {} |\n", self.caption, self.header.join(" | "));
+    out.push_str(&format!("|{}\n", " --- |".repeat(self.header.len())));
+    for row in self.rows.iter() {
+      let cells: Vec<String> = row
+        .iter()
+        .map(|(cell, bold)| match bold {
+          true => format!("**{}**", cell),
+          false => cell.clone(),
+        })
+        .collect();
+      out.push_str(&format!("| {} |\n", cells.join(" | ")));
+    }
+    out
+  }
+
+  fn latex(&self) -> String {
+    let escape = |s: &str| s.replace('_', "\\_").replace('%', "\\%");
+    let mut out = format!(
+      "\\begin{{table}}[ht]\n\\centering\n\\caption{{{}}}\n\\begin{{tabular}}{{l{}}}\n\\hline\n",
+      escape(&self.caption),
+      "r".repeat(self.header.len() - 1)
+    );
+    let header: Vec<String> = self.header.iter().map(|h| escape(h)).collect();
+    out.push_str(&format!("{} \\\\\n\\hline\n", header.join(" & ")));
+    for row in self.rows.iter() {
+      let cells: Vec<String> = row
+        .iter()
+        .map(|(cell, bold)| match bold {
+          true => format!("\\textbf{{{}}}", escape(cell)),
+          false => escape(cell),
+        })
+        .collect();
+      out.push_str(&format!("{} \\\\\n", cells.join(" & ")));
+    }
+    out.push_str("\\hline\n\\end{tabular}\n\\end{table}\n");
+    out
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn close(a: f64, b: f64, tolerance: f64) -> bool {
+    (a - b).abs() < tolerance
+  }
+
+  #[test]
+  fn ties_share_their_ranks() {
+    assert_eq!(average_ranks(&[3., 1., 3., 2.]), vec![3.5, 1., 3.5, 2.]);
+    assert_eq!(median(&[4., 1., 3., 2.]), 2.5);
+  }
+
+  #[test]
+  fn rank_sum_test_matches_the_normal_approximation() {
+    let a = [1., 2., 3., 4., 5.];
+    let b = [6., 7., 8., 9., 10.];
+    // U = 0, with a variance of 25 * 11 / 12 and the continuity correction.
+    assert!(close(rank_sum_test(&a, &b), 0.0121858, 1e-6));
+    assert!(close(rank_sum_test(&b, &a), 0.0121858, 1e-6));
+    // Only ties.
+    assert_eq!(rank_sum_test(&[1., 1., 1.], &[1., 1., 1.]), 1.);
+    assert!(matches!(outcome(&a, &b), Outcome::Win));
+    assert!(matches!(outcome(&b, &a), Outcome::Loss));
+    assert!(matches!(outcome(&a, &[1.5, 2.5, 3.5, 4.5, 5.5]), Outcome::Tie));
+  }
+
+  #[test]
+  fn friedman_statistic_and_p_value() {
+    // 12 * 4 / 12 * (1 + 4 + 9 - 12), and exp(-8 / 2) with two degrees of freedom.
+    let (chi_squared, p) = friedman(&[1., 2., 3.], 4);
+    assert!(close(chi_squared, 8., 1e-12));
+    assert!(close(p, (-4f64).exp(), 1e-9));
+    // Identical ranks.
+    assert!(close(friedman(&[2., 2., 2.], 4).1, 1., 1e-12));
+  }
+
+  #[test]
+  fn holm_adjusts_against_the_control() {
+    let adjusted = holm(&[0.5, 0.01, 0.04, 0.03], 0);
+    assert!(adjusted[0].is_nan());
+    assert!(close(adjusted[1], 0.03, 1e-12));
+    assert!(close(adjusted[2], 0.06, 1e-12));
+    assert!(close(adjusted[3], 0.06, 1e-12));
+    assert_eq!(holm(&[0.9, 0.8], 1)[0], 0.9);
+  }
+
+  #[test]
+  fn distributions() {
+    assert!(close(normal_cdf(0.), 0.5, 1e-7));
+    assert!(close(normal_cdf(1.96), 0.9750021, 1e-6));
+    assert!(close(normal_cdf(-1.96), 1. - 0.9750021, 1e-6));
+    // Q(1, x) = exp(-x), below and above a + 1.
+    assert!(close(regularized_gamma_q(1., 0.5), (-0.5f64).exp(), 1e-9));
+    assert!(close(regularized_gamma_q(1., 4.), (-4f64).exp(), 1e-9));
+    // 95% quantile of the chi-squared distribution with 5 degrees of freedom.
+    assert!(close(regularized_gamma_q(2.5, 11.0705 / 2.), 0.05, 1e-5));
+  }
+}
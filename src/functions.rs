@@ -73,3 +73,14 @@ pub fn cec17_impl(v: &DVector<f64>, func_num: usize) -> f64 {
   // println!("result: {:?}", result);
   result
 }
+
+// Evaluates all vectors with a single call, the C function taking them back to back.
+pub fn cec17_batch_impl(vs: &[DVector<f64>], func_num: usize) -> Vec<f64> {
+  let nx = vs[0].len();
+  let x: Vec<f64> = vs.iter().flat_map(|v| v.iter().copied()).collect();
+  let mut result = vec![0.0f64; vs.len()];
+  unsafe {
+    cec17_test_func(x.as_ptr(), result.as_mut_ptr(), nx, vs.len(), func_num);
+  }
+  result
+}
@@ -65,6 +65,7 @@ pub fn grid_search<U: Position + Velocity + Clone, T: Optimizer<U> + DataExporte
         attempts,
         false,
         &bar,
+        None,
       );
     });
   });
@@ -116,6 +117,7 @@ pub fn grid_search_dim<U: Position + Velocity + Clone, T: Optimizer<U> + DataExp
         attempts,
         false,
         &bar,
+        None,
       );
     });
   });
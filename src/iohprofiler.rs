@@ -0,0 +1,186 @@
+use serde_json::{json, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+// The instances of a function run in parallel and share its files.
+static FILES: Mutex<()> = Mutex::new(());
+
+// An attempt in the IOHprofiler format: a block of the .dat file and an entry of the
+// runs of the .json file.
+pub struct Run {
+  pub dat: String,
+  pub json: Value,
+}
+
+// Logs the attempts of a problem for IOHanalyzer, enabled with the `iohprofiler`
+// parameter. The attempts run in parallel, so the runs are collected and written
+// together once they are all over.
+pub struct Logger {
+  root: PathBuf,
+  algorithm: String,
+  // Columns of the .dat files after evaluations, raw_y and best_y.
+  tracked: Vec<String>,
+  runs: Mutex<Vec<(usize, Run)>>,
+}
+
+impl Logger {
+  pub fn new(root: PathBuf, algorithm: String, tracked: Vec<String>) -> Self {
+    Logger {
+      root,
+      algorithm,
+      tracked,
+      runs: Mutex::new(Vec::new()),
+    }
+  }
+
+  pub fn add(&self, attempt: usize, run: Run) {
+    self.runs.lock().unwrap().push((attempt, run));
+  }
+
+  // Writes IOHprofiler_f<id>_<function>_DIM<dim>.json and its .dat file in
+  // data_f<id>_<function>. The instances of a function are runs of the same scenario,
+  // so the runs are merged with those of the other instances already written, and
+  // replace those of the same instance.
+  pub fn finish(&self, problem: &str, dim: usize) -> Result<(), Box<dyn std::error::Error>> {
+    let mut runs = std::mem::take(&mut *self.runs.lock().unwrap());
+    runs.sort_by_key(|(attempt, _)| *attempt);
+    let (function_id, function_name) = function(problem);
+    let instance = instance(problem);
+
+    let _files = FILES.lock().unwrap();
+    let data_directory = format!("data_f{}_{}", function_id, function_name);
+    let path = format!("{}/IOHprofiler_f{}_DIM{}.dat", data_directory, function_id, dim);
+    let name = format!("IOHprofiler_f{}_{}_DIM{}.json", function_id, function_name, dim);
+    let mut runs: Vec<Run> = previous_runs(&self.root.join(&name), &self.root.join(&path))
+      .into_iter()
+      .filter(|run| run.json["instance"].as_u64() != Some(instance as u64))
+      .chain(runs.into_iter().map(|(_, run)| run))
+      .collect();
+    runs.sort_by_key(|run| run.json["instance"].as_u64());
+
+    fs::create_dir_all(self.root.join(&data_directory))?;
+    let dat: String = runs.iter().map(|run| run.dat.as_str()).collect();
+    fs::write(self.root.join(&path), dat)?;
+
+    // Every column of the .dat files.
+    let mut attributes = vec!["evaluations".to_owned(), "raw_y".to_owned(), "best_y".to_owned()];
+    attributes.extend(self.tracked.iter().cloned());
+    let info = json!({
+      "version": "0.3.5",
+      "suite": "psobench",
+      "function_id": function_id,
+      "function_name": function_name,
+      "maximization": false,
+      "algorithm": {
+        "name": self.algorithm,
+        "info": "psobench",
+      },
+      "attributes": attributes,
+      "scenarios": [{
+        "dimension": dim,
+        "path": path,
+        "runs": runs.iter().map(|run| run.json.clone()).collect::<Vec<Value>>(),
+      }],
+    });
+    fs::write(self.root.join(name), serde_json::to_string_pretty(&info)?)?;
+    Ok(())
+  }
+}
+
+// Runs already written to a scenario, with the blocks of the .dat file in the order of
+// the runs of the .json file. None are kept if the files are missing or do not match.
+fn previous_runs(info: &Path, data: &Path) -> Vec<Run> {
+  let (info, data) = match (fs::read_to_string(info), fs::read_to_string(data)) {
+    (Ok(info), Ok(data)) => (info, data),
+    _ => return Vec::new(),
+  };
+  let runs = match serde_json::from_str::<Value>(&info) {
+    Ok(info) => info["scenarios"][0]["runs"].as_array().cloned().unwrap_or_default(),
+    Err(_) => return Vec::new(),
+  };
+  // Every block starts with the header line.
+  let mut blocks: Vec<String> = Vec::new();
+  for line in data.lines() {
+    if line.starts_with("evaluations") || blocks.is_empty() {
+      blocks.push(String::new());
+    }
+    let block = blocks.last_mut().unwrap();
+    block.push_str(line);
+    block.push('\n');
+  }
+  if blocks.len() != runs.len() {
+    return Vec::new();
+  }
+  blocks.into_iter().zip(runs).map(|(dat, json)| Run { dat, json }).collect()
+}
+
+// Function id and name of a problem, e.g. 5 and CEC2017_F05, or 1 and BBOB_F01 for the
+// instances of BBOB_F01. Other problems are numbered 0.
+fn function(problem: &str) -> (usize, String) {
+  let name = match problem.rsplit_once("_I") {
+    Some((name, instance)) if instance.parse::<usize>().is_ok() => name,
+    _ => problem,
+  };
+  match name.rsplit_once("_F").and_then(|(_, id)| id.parse().ok()) {
+    Some(id) => (id, name.to_owned()),
+    None => (0, name.to_owned()),
+  }
+}
+
+// Instance of a BBOB problem, 1 otherwise.
+pub fn instance(problem: &str) -> usize {
+  match problem.rsplit_once("_I") {
+    Some((_, instance)) => instance.parse().unwrap_or(1),
+    None => 1,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn run(instance: usize, best: f64) -> Run {
+    Run {
+      dat: format!("evaluations raw_y best_y g\n10 {} {} 1\n", best, best),
+      json: json!({"instance": instance, "evals": 10, "best": {"evals": 10, "y": best}}),
+    }
+  }
+
+  #[test]
+  fn problems_are_named_after_their_function() {
+    assert_eq!(function("BBOB_F03_I02"), (3, "BBOB_F03".to_owned()));
+    assert_eq!(function("CEC2017_F05"), (5, "CEC2017_F05".to_owned()));
+    assert_eq!(function("Sphere100"), (0, "Sphere100".to_owned()));
+    assert_eq!(instance("BBOB_F03_I02"), 2);
+    assert_eq!(instance("CEC2017_F05"), 1);
+  }
+
+  #[test]
+  fn instances_share_a_scenario() {
+    let root = std::env::temp_dir().join(format!("psobench-iohprofiler-{}", std::process::id()));
+    let logger = Logger::new(root.clone(), "gsa".to_owned(), vec!["g".to_owned()]);
+    for (problem, best) in [("BBOB_F03_I02", 2.), ("BBOB_F03_I01", 1.), ("BBOB_F03_I02", 3.)] {
+      logger.add(0, run(instance(problem), best));
+      logger.finish(problem, 5).unwrap();
+    }
+
+    let info = fs::read_to_string(root.join("IOHprofiler_f3_BBOB_F03_DIM5.json")).unwrap();
+    let info: Value = serde_json::from_str(&info).unwrap();
+    assert_eq!(info["attributes"], json!(["evaluations", "raw_y", "best_y", "g"]));
+    assert_eq!(info["function_name"], "BBOB_F03");
+    let scenario = &info["scenarios"][0];
+    assert_eq!(scenario["path"], "data_f3_BBOB_F03/IOHprofiler_f3_DIM5.dat");
+    // The runs of the second instance were replaced.
+    let runs: Vec<(u64, f64)> = scenario["runs"]
+      .as_array()
+      .unwrap()
+      .iter()
+      .map(|run| (run["instance"].as_u64().unwrap(), run["best"]["y"].as_f64().unwrap()))
+      .collect();
+    assert_eq!(runs, vec![(1, 1.), (2, 3.)]);
+    let dat = fs::read_to_string(root.join("data_f3_BBOB_F03/IOHprofiler_f3_DIM5.dat")).unwrap();
+    assert_eq!(dat, format!("{}{}", run(1, 1.).dat, run(2, 3.).dat));
+    fs::remove_dir_all(root).unwrap();
+  }
+}
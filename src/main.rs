@@ -1,11 +1,17 @@
 extern crate nalgebra as na;
 extern crate rand;
+mod bbob;
+mod coco;
+mod compare;
 mod functions;
 mod grid_search;
+mod iohprofiler;
 mod optimizers;
 mod parameters;
 mod particles;
 mod problems;
+mod profiles;
+mod recording;
 mod utils;
 use crate::optimizers::{gsa::Normalizer, traits::ParamValue};
 #[allow(unused_imports)]
@@ -13,6 +19,7 @@ use optimizers::{gsa::Gsa, mgsa::Mgsa, pso::Pso, rgsa::Rgsa};
 #[allow(unused_imports)]
 use particles::{
   gsa::GsaParticle,
+  initializer::Init,
   mgsa::MgsaParticle,
   pso::PsoParticle,
   rgsa::RgsaParticle,
@@ -42,6 +49,12 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     "single" => single(dims, g0, alpha, gamma, theta, elite, sigma, edge)?,
     "cec" => cec(dims, g0, alpha, gamma, theta, elite, sigma, edge)?,
     "grid" => grid(dims, g0, alpha, gamma, theta, elite, sigma, edge)?,
+    // coco <test> <dim>
+    "coco" => coco::coco(&args[2], args[3].parse()?)?,
+    // compare <test> <dim>
+    "compare" => compare::compare(&args[2], args[3].parse()?)?,
+    // profiles <test> <dim>
+    "profiles" => profiles::profiles(&args[2], args[3].parse()?)?,
     _ => panic!("Unknown argument: {}. Please use fn1, fn2, or fn3", args[1]),
   }
 
@@ -83,12 +96,15 @@ fn single(
           "behavior",
           ParamValue::Behavior(Behavior {
             edge: Edge::Pass,
-            vmax: false,
+            vmax: None,
+            pos_init: Init::Uniform,
+            vel_init: None,
           }),
         ),
       ],
       problem.clone(),
       true,
+      None,
     )?;
   }
   Ok(())
@@ -127,7 +143,9 @@ fn cec(
           "behavior",
           ParamValue::Behavior(Behavior {
             edge: Edge::Pass,
-            vmax: false,
+            vmax: None,
+            pos_init: Init::Uniform,
+            vel_init: None,
           }),
         ),
       ],
@@ -182,7 +200,15 @@ fn grid(
         ("elite", ParamValue::Bool(elite)),
         ("tiled", ParamValue::Bool(false)),
         ("normalizer", ParamValue::Normalizer(Normalizer::MinMax)),
-        ("behavior", ParamValue::Behavior(Behavior { edge, vmax: false })),
+        (
+          "behavior",
+          ParamValue::Behavior(Behavior {
+            edge,
+            vmax: None,
+            pos_init: Init::Uniform,
+            vel_init: None,
+          }),
+        ),
       ],
     )?;
   }
@@ -0,0 +1,414 @@
+use crate::optimizers::traits::{
+  Data, DataExporter, GlobalBestPos, Name, OptimizationProblem, Optimizer, ParamValue, Particles,
+};
+use crate::particles::boundary::Boundary;
+use crate::particles::traits::{Behavior, Particle, Position, Trial, Velocity};
+use crate::problems;
+use crate::rand::Rng;
+use crate::recording::Recorder;
+use crate::utils;
+use nalgebra::DVector;
+use problems::Problem;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::{collections::HashMap, path::PathBuf};
+
+#[derive(Clone)]
+pub struct Abc<T> {
+  name: String,
+  problem: Problem,
+  particles: Vec<T>,
+  global_best_pos: Option<DVector<f64>>,
+  global_worst_pos: Option<DVector<f64>>,
+  // Fitness of each food source.
+  fitness: Vec<f64>,
+  // Number of failed improvements after which a source is abandoned.
+  limit: usize,
+  // Random numbers of the run, seeded with the `seed` parameter.
+  rng: StdRng,
+  data: Vec<(f64, f64, Option<Vec<T>>)>,
+  additional_data: Vec<Vec<Vec<(String, f64)>>>,
+  recorder: Recorder,
+  out_directory: PathBuf,
+  save: bool,
+}
+
+impl<T: Particle + Position + Velocity + Trial + Clone> Optimizer<T> for Abc<T> {
+  fn new(
+    name: String,
+    problem: Problem,
+    parameters: HashMap<String, ParamValue>,
+    out_directory: PathBuf,
+    save: bool,
+  ) -> Abc<T> {
+    assert!(
+      parameters.contains_key("particle_count"),
+      "Key 'particle_count' not found."
+    );
+    let number_of_particles = match parameters["particle_count"] {
+      ParamValue::Int(val) => val as usize,
+      _ => {
+        eprintln!("Error: parameter 'particle_count' should be of type Param::Int.");
+        std::process::exit(1);
+      }
+    };
+
+    let limit = match parameters.contains_key("limit") {
+      true => match parameters["limit"] {
+        ParamValue::Int(val) => val as usize,
+        _ => {
+          eprintln!("Error: parameter 'limit' should be of type Param::Int.");
+          std::process::exit(1);
+        }
+      },
+      false => number_of_particles * problem.dim(),
+    };
+    assert!(number_of_particles >= 2, "ABC needs at least 2 food sources.");
+
+    let seed = match parameters.contains_key("seed") {
+      true => match parameters["seed"] {
+        ParamValue::Int(val) => val as u64,
+        _ => {
+          eprintln!("Error: parameter 'seed' should be of type Param::Int.");
+          std::process::exit(1);
+        }
+      },
+      false => rand::thread_rng().gen(),
+    };
+
+    assert!(parameters.contains_key("behavior"), "Key 'behavior' not found.");
+    let behavior = match parameters["behavior"] {
+      ParamValue::Behavior(val) => val,
+      _ => {
+        eprintln!("Error: parameter 'behavior' should be of type Param::Behavior.");
+        std::process::exit(1);
+      }
+    };
+
+    let mut abc = Abc {
+      name,
+      problem,
+      particles: Vec::new(),
+      global_best_pos: None,
+      global_worst_pos: None,
+      fitness: Vec::new(),
+      limit,
+      rng: StdRng::seed_from_u64(seed),
+      data: Vec::new(),
+      additional_data: Vec::new(),
+      recorder: Recorder::default(),
+      out_directory,
+      save,
+    };
+
+    abc.init(number_of_particles, behavior);
+    abc
+  }
+
+  fn init(&mut self, number_of_particles: usize, behavior: Behavior) {
+    let problem = &mut self.problem();
+    problem.sampler().reset(number_of_particles);
+    behavior.edge.setup(problem);
+    let mut particles: Vec<T> = Vec::new();
+    for _ in 0..number_of_particles {
+      particles.push(T::new(problem, behavior));
+    }
+
+    self.particles = particles;
+    self.update_best_and_worst();
+    self.fitness = self.evaluate();
+
+    utils::create_directory(self.out_directory().to_path_buf(), true, false);
+  }
+
+  fn calculate_vel(&mut self, i: usize) -> DVector<f64> {
+    assert!(i < self.particles().len());
+    let n = self.particles().len();
+    let dim = self.problem.dim();
+
+    // Perturb one dimension relative to a random other food source.
+    let mut k = self.rng.gen_range(0..n - 1);
+    if k >= i {
+      k += 1;
+    }
+    let d = self.rng.gen_range(0..dim);
+    let phi: f64 = self.rng.gen_range(-1.0..1.0);
+
+    let mut vel = DVector::from_element(dim, 0.);
+    vel[d] = phi * (self.particles()[i].pos()[d] - self.particles()[k].pos()[d]);
+    vel
+  }
+
+  fn step(&mut self, _iter: usize, _iterations: usize) {
+    // Employed bees.
+    for i in 0..self.particles().len() {
+      self.search_neighborhood(i);
+    }
+
+    // Onlooker bees pick sources proportionally to their quality.
+    let quality: Vec<f64> = self.fitness.iter().map(|&f| if f >= 0. { 1. / (1. + f) } else { 1. + f.abs() }).collect();
+    let total_quality = quality.iter().sum::<f64>();
+    for _ in 0..self.particles().len() {
+      // Without any quality to go by, e.g. when every source is infeasible, pick uniformly.
+      if !(total_quality > 0. && total_quality.is_finite()) {
+        let i = self.rng.gen_range(0..quality.len());
+        self.search_neighborhood(i);
+        continue;
+      }
+      let mut target = self.rng.gen_range(0.0..total_quality);
+      let mut i = 0;
+      while i < quality.len() - 1 && target >= quality[i] {
+        target -= quality[i];
+        i += 1;
+      }
+      self.search_neighborhood(i);
+    }
+
+    // Scout bee abandons the most exhausted source.
+    let mut exhausted = 0;
+    for i in 0..self.particles().len() {
+      if self.particles()[i].trial() > self.particles()[exhausted].trial() {
+        exhausted = i;
+      }
+    }
+    if self.particles()[exhausted].trial() > self.limit {
+      let particle = &mut self.particles[exhausted];
+      Position::init(particle, &mut self.problem);
+      Velocity::init(particle, &mut self.problem);
+      particle.set_trial(0);
+      self.fitness[exhausted] = particle.fitness(&mut self.problem);
+    }
+
+    // Update best and worst. The fitness of every particle is cached, so this does not
+    // evaluate them again.
+    let (gbest, gworst) = self.update_best_and_worst();
+
+    // Save the data for current iteration.
+    self.record(gbest, gworst);
+  }
+
+  fn reinitialize(&mut self, idx: usize, pos: Option<DVector<f64>>) {
+    self.fitness.resize(self.particles.len(), f64::INFINITY);
+    let particle = &mut self.particles[idx];
+    match pos {
+      Some(pos) => particle.set_pos(pos),
+      None => Position::init(particle, &mut self.problem),
+    }
+    Velocity::init(particle, &mut self.problem);
+    particle.set_trial(0);
+    self.fitness[idx] = particle.fitness(&mut self.problem);
+  }
+
+  fn remove_particle(&mut self, idx: usize) {
+    self.particles.remove(idx);
+    self.fitness.remove(idx);
+  }
+}
+
+impl<T: Particle + Position + Velocity + Trial + Clone> Abc<T> {
+  fn search_neighborhood(&mut self, i: usize) {
+    let vel = self.calculate_vel(i);
+    let particle = &mut self.particles[i];
+    let old_pos = particle.pos().clone();
+    particle.update_vel(vel, &mut self.problem);
+    particle.move_pos(&mut self.problem);
+
+    // Greedy selection between the old and the new source.
+    let fitness = particle.fitness(&mut self.problem);
+    if fitness < self.fitness[i] {
+      self.fitness[i] = fitness;
+      particle.set_trial(0);
+    } else {
+      particle.set_pos(old_pos);
+      particle.set_cached_fitness(Some(self.fitness[i]));
+      particle.set_vel(DVector::from_element(self.problem.dim(), 0.));
+      particle.set_trial(particle.trial() + 1);
+    }
+  }
+}
+
+impl<T> Particles<T> for Abc<T> {
+  fn particles(&self) -> &Vec<T> {
+    &self.particles
+  }
+
+  fn particles_mut(&mut self) -> &mut Vec<T> {
+    &mut self.particles
+  }
+
+  fn particles_and_problem_mut(&mut self) -> (&mut Vec<T>, &mut Problem) {
+    (&mut self.particles, &mut self.problem)
+  }
+}
+
+impl<T> GlobalBestPos for Abc<T> {
+  fn global_best_pos(&self) -> DVector<f64> {
+    self.global_best_pos.clone().unwrap()
+  }
+
+  fn global_worst_pos(&self) -> DVector<f64> {
+    self.global_worst_pos.clone().unwrap()
+  }
+
+  fn option_global_best_pos(&self) -> &Option<DVector<f64>> {
+    &self.global_best_pos
+  }
+
+  fn option_global_worst_pos(&self) -> &Option<DVector<f64>> {
+    &self.global_worst_pos
+  }
+
+  fn set_global_best_pos(&mut self, pos: DVector<f64>) {
+    self.global_best_pos = Some(pos);
+  }
+
+  fn set_global_worst_pos(&mut self, pos: DVector<f64>) {
+    self.global_worst_pos = Some(pos);
+  }
+}
+
+impl<T> OptimizationProblem for Abc<T> {
+  fn problem(&mut self) -> &mut Problem {
+    &mut self.problem
+  }
+}
+
+impl<T> Name for Abc<T> {
+  fn name(&self) -> &String {
+    &self.name
+  }
+}
+
+impl<T: Clone> Data<T> for Abc<T> {
+  fn data(&self) -> &Vec<(f64, f64, Option<Vec<T>>)> {
+    &self.data
+  }
+
+  fn saving(&self) -> bool {
+    self.save
+  }
+
+  fn additional_data(&self) -> &Vec<Vec<Vec<(String, f64)>>> {
+    &self.additional_data
+  }
+
+  fn data_mut(&mut self) -> &mut Vec<(f64, f64, Option<Vec<T>>)> {
+    &mut self.data
+  }
+
+  fn additional_data_mut(&mut self) -> &mut Vec<Vec<Vec<(String, f64)>>> {
+    &mut self.additional_data
+  }
+
+  fn recorder(&mut self) -> &mut Recorder {
+    &mut self.recorder
+  }
+
+  fn add_data_impl(&mut self, datum: (f64, f64, Option<Vec<T>>)) {
+    self.data.push(datum);
+  }
+
+  fn add_additional_data_impl(&mut self, datum: Vec<Vec<(String, f64)>>) {
+    self.additional_data.push(datum);
+  }
+}
+
+impl<T: Position + Velocity + Clone> DataExporter<T> for Abc<T> {
+  fn out_directory(&self) -> &PathBuf {
+    &self.out_directory
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::optimizers::testing;
+  use crate::particles::abc::AbcParticle;
+  use crate::particles::initializer::Init;
+  use crate::particles::traits::Edge;
+
+  fn abc(particle_count: isize, edge: Edge) -> Abc<AbcParticle> {
+    let parameters = utils::param_hashmap_generator(vec![
+      ("particle_count", ParamValue::Int(particle_count)),
+      (
+        "behavior",
+        ParamValue::Behavior(Behavior {
+          edge,
+          vmax: None,
+          pos_init: Init::Sobol,
+          vel_init: Some(Init::Zero),
+        }),
+      ),
+      testing::seed(),
+    ]);
+    Abc::new(
+      "abc".to_owned(),
+      problems::sphere_100(5),
+      parameters,
+      PathBuf::new(),
+      false,
+    )
+  }
+
+  #[test]
+  fn improves_on_sphere() {
+    testing::assert_improves(&mut abc(20, Edge::Pass), 100, 0.05);
+  }
+
+  #[test]
+  fn bees_change_one_dimension_and_keep_the_better_source() {
+    let mut optimizer = abc(10, Edge::Pass);
+    for _ in 0..50 {
+      let i = optimizer.rng.gen_range(0..10);
+      let (pos, fitness, trial) = (
+        optimizer.particles[i].pos().clone(),
+        optimizer.fitness[i],
+        optimizer.particles[i].trial(),
+      );
+      optimizer.search_neighborhood(i);
+      let particle = &optimizer.particles[i];
+      let changed = (0..5).filter(|&d| particle.pos()[d] != pos[d]).count();
+      assert!(changed <= 1);
+      if changed == 0 {
+        assert_eq!(optimizer.fitness[i], fitness);
+        assert_eq!(particle.trial(), trial + 1);
+      } else {
+        assert!(optimizer.fitness[i] < fitness);
+        assert_eq!(particle.trial(), 0);
+      }
+    }
+  }
+
+  #[test]
+  fn scouts_abandon_an_exhausted_source() {
+    // No neighbour improves on the optimum, so the trials keep growing.
+    let mut optimizer = abc(10, Edge::Pass);
+    let pos = DVector::from_element(5, -50.);
+    optimizer.particles[3].set_pos(pos.clone());
+    optimizer.fitness[3] = 0.;
+    optimizer.particles[3].set_trial(optimizer.limit);
+    optimizer.step(0, 10);
+    let particle = &optimizer.particles[3];
+    assert_eq!(particle.trial(), 0);
+    assert_ne!(particle.pos(), &pos);
+    assert_eq!(optimizer.fitness[3], problems::sphere_100(5).f(particle.pos()));
+  }
+
+  #[test]
+  fn onlookers_pick_uniformly_without_quality() {
+    let mut optimizer = abc(10, Edge::Infinity);
+    for idx in 0..10 {
+      optimizer.particles[idx].set_pos(DVector::from_element(5, 1e4 * (idx + 1) as f64));
+      optimizer.fitness[idx] = f64::INFINITY;
+    }
+    optimizer.step(0, 10);
+    assert!(optimizer.fitness.iter().all(|f| f.is_infinite()));
+    assert_eq!(optimizer.data().len(), 1);
+  }
+
+  #[test]
+  #[should_panic(expected = "at least 2")]
+  fn rejects_a_single_source() {
+    abc(1, Edge::Pass);
+  }
+}
@@ -0,0 +1,408 @@
+use crate::optimizers::traits::{
+  Data, DataExporter, GlobalBestPos, Name, OptimizationProblem, Optimizer, ParamValue, Particles,
+};
+use crate::particles::boundary::Boundary;
+use crate::particles::traits::{Behavior, Echolocation, Particle, Position, Velocity};
+use crate::problems;
+use crate::rand::Rng;
+use crate::recording::Recorder;
+use crate::utils;
+use nalgebra::DVector;
+use problems::Problem;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::{collections::HashMap, path::PathBuf};
+
+#[derive(Clone)]
+pub struct Bat<T> {
+  name: String,
+  problem: Problem,
+  particles: Vec<T>,
+  global_best_pos: Option<DVector<f64>>,
+  global_worst_pos: Option<DVector<f64>>,
+  // Fitness of the current position of each bat.
+  fitness: Vec<f64>,
+  // The best solution found so far.
+  best: Option<(f64, DVector<f64>)>,
+  f_min: f64,
+  f_max: f64,
+  loudness: f64,
+  pulse_rate: f64,
+  alpha: f64,
+  gamma: f64,
+  // Random numbers of the run, seeded with the `seed` parameter.
+  rng: StdRng,
+  data: Vec<(f64, f64, Option<Vec<T>>)>,
+  additional_data: Vec<Vec<Vec<(String, f64)>>>,
+  recorder: Recorder,
+  out_directory: PathBuf,
+  save: bool,
+}
+
+impl<T: Particle + Position + Velocity + Echolocation + Clone> Optimizer<T> for Bat<T> {
+  fn new(
+    name: String,
+    problem: Problem,
+    parameters: HashMap<String, ParamValue>,
+    out_directory: PathBuf,
+    save: bool,
+  ) -> Bat<T> {
+    assert!(
+      parameters.contains_key("particle_count"),
+      "Key 'particle_count' not found."
+    );
+    let number_of_particles = match parameters["particle_count"] {
+      ParamValue::Int(val) => val as usize,
+      _ => {
+        eprintln!("Error: parameter 'particle_count' should be of type Param::Int.");
+        std::process::exit(1);
+      }
+    };
+
+    assert!(parameters.contains_key("f_min"), "Key 'f_min' not found.");
+    let f_min = match parameters["f_min"] {
+      ParamValue::Float(val) => val,
+      _ => {
+        eprintln!("Error: parameter 'f_min' should be of type Param::Float.");
+        std::process::exit(1);
+      }
+    };
+
+    assert!(parameters.contains_key("f_max"), "Key 'f_max' not found.");
+    let f_max = match parameters["f_max"] {
+      ParamValue::Float(val) => val,
+      _ => {
+        eprintln!("Error: parameter 'f_max' should be of type Param::Float.");
+        std::process::exit(1);
+      }
+    };
+
+    assert!(parameters.contains_key("loudness"), "Key 'loudness' not found.");
+    let loudness = match parameters["loudness"] {
+      ParamValue::Float(val) => val,
+      _ => {
+        eprintln!("Error: parameter 'loudness' should be of type Param::Float.");
+        std::process::exit(1);
+      }
+    };
+
+    assert!(parameters.contains_key("pulse_rate"), "Key 'pulse_rate' not found.");
+    let pulse_rate = match parameters["pulse_rate"] {
+      ParamValue::Float(val) => val,
+      _ => {
+        eprintln!("Error: parameter 'pulse_rate' should be of type Param::Float.");
+        std::process::exit(1);
+      }
+    };
+
+    assert!(parameters.contains_key("alpha"), "Key 'alpha' not found.");
+    let alpha = match parameters["alpha"] {
+      ParamValue::Float(val) => val,
+      _ => {
+        eprintln!("Error: parameter 'alpha' should be of type Param::Float.");
+        std::process::exit(1);
+      }
+    };
+
+    assert!(parameters.contains_key("gamma"), "Key 'gamma' not found.");
+    let gamma = match parameters["gamma"] {
+      ParamValue::Float(val) => val,
+      _ => {
+        eprintln!("Error: parameter 'gamma' should be of type Param::Float.");
+        std::process::exit(1);
+      }
+    };
+
+    let seed = match parameters.contains_key("seed") {
+      true => match parameters["seed"] {
+        ParamValue::Int(val) => val as u64,
+        _ => {
+          eprintln!("Error: parameter 'seed' should be of type Param::Int.");
+          std::process::exit(1);
+        }
+      },
+      false => rand::thread_rng().gen(),
+    };
+
+    assert!(parameters.contains_key("behavior"), "Key 'behavior' not found.");
+    let behavior = match parameters["behavior"] {
+      ParamValue::Behavior(val) => val,
+      _ => {
+        eprintln!("Error: parameter 'behavior' should be of type Param::Behavior.");
+        std::process::exit(1);
+      }
+    };
+
+    let mut bat = Bat {
+      name,
+      problem,
+      particles: Vec::new(),
+      global_best_pos: None,
+      global_worst_pos: None,
+      fitness: Vec::new(),
+      best: None,
+      f_min,
+      f_max,
+      loudness,
+      pulse_rate,
+      alpha,
+      gamma,
+      rng: StdRng::seed_from_u64(seed),
+      data: Vec::new(),
+      additional_data: Vec::new(),
+      recorder: Recorder::default(),
+      out_directory,
+      save,
+    };
+
+    bat.init(number_of_particles, behavior);
+    bat
+  }
+
+  fn init(&mut self, number_of_particles: usize, behavior: Behavior) {
+    let (loudness, pulse_rate) = (self.loudness, self.pulse_rate);
+    let problem = &mut self.problem();
+    problem.sampler().reset(number_of_particles);
+    behavior.edge.setup(problem);
+    let mut particles: Vec<T> = Vec::new();
+    for _ in 0..number_of_particles {
+      let mut particle = T::new(problem, behavior);
+      particle.set_loudness(loudness);
+      particle.set_pulse_rate(pulse_rate);
+      particles.push(particle);
+    }
+
+    self.particles = particles;
+    let (gbest, _) = self.update_best_and_worst();
+    self.fitness = self.evaluate();
+    self.best = Some((gbest, self.global_best_pos()));
+
+    utils::create_directory(self.out_directory().to_path_buf(), true, false);
+  }
+
+  fn calculate_vel(&mut self, i: usize) -> DVector<f64> {
+    assert!(i < self.particles().len());
+    let frequency = self.f_min + (self.f_max - self.f_min) * self.rng.gen_range(0.0..1.0);
+    let best = &self.best.as_ref().unwrap().1;
+    self.particles()[i].vel() + (self.particles()[i].pos() - best) * frequency
+  }
+
+  fn step(&mut self, iter: usize, _iterations: usize) {
+    let mean_loudness = self.particles().iter().map(|p| p.loudness()).sum::<f64>() / self.particles().len() as f64;
+    for i in 0..self.particles().len() {
+      let vel = self.calculate_vel(i);
+      let best = self.best.clone().unwrap();
+      let particle = &mut self.particles[i];
+      let old_pos = particle.pos().clone();
+      particle.update_vel(vel, &mut self.problem);
+
+      if self.rng.gen_range(0.0..1.0) > particle.pulse_rate() {
+        // Local random walk around the best solution.
+        let vel = particle.vel().clone();
+        let walk = best.1.map(|x| x + self.rng.gen_range(-1.0..1.0) * mean_loudness) - particle.pos();
+        particle.set_vel(walk);
+        particle.move_pos(&mut self.problem);
+        particle.set_vel(vel);
+      } else {
+        particle.move_pos(&mut self.problem);
+      }
+
+      let fitness = particle.fitness(&mut self.problem);
+      if fitness <= best.0 {
+        self.best = Some((fitness, particle.pos().clone()));
+      }
+      if fitness <= self.fitness[i] && self.rng.gen_range(0.0..1.0) < particle.loudness() {
+        self.fitness[i] = fitness;
+        particle.set_loudness(self.alpha * particle.loudness());
+        particle.set_pulse_rate(self.pulse_rate * (1. - (-self.gamma * (iter + 1) as f64).exp()));
+      } else {
+        particle.set_pos(old_pos);
+        particle.set_cached_fitness(Some(self.fitness[i]));
+      }
+    }
+
+    // Update best and worst. The fitness of every particle is cached, so this does not
+    // evaluate them again.
+    let (gbest, gworst) = self.update_best_and_worst();
+
+    // Save the data for current iteration.
+    self.record(gbest, gworst);
+  }
+
+  fn reinitialize(&mut self, idx: usize, pos: Option<DVector<f64>>) {
+    self.fitness.resize(self.particles.len(), f64::INFINITY);
+    let particle = &mut self.particles[idx];
+    match pos {
+      Some(pos) => particle.set_pos(pos),
+      None => Position::init(particle, &mut self.problem),
+    }
+    Velocity::init(particle, &mut self.problem);
+    particle.set_loudness(self.loudness);
+    particle.set_pulse_rate(self.pulse_rate);
+    self.fitness[idx] = particle.fitness(&mut self.problem);
+  }
+
+  fn remove_particle(&mut self, idx: usize) {
+    self.particles.remove(idx);
+    self.fitness.remove(idx);
+  }
+}
+
+impl<T> Particles<T> for Bat<T> {
+  fn particles(&self) -> &Vec<T> {
+    &self.particles
+  }
+
+  fn particles_mut(&mut self) -> &mut Vec<T> {
+    &mut self.particles
+  }
+
+  fn particles_and_problem_mut(&mut self) -> (&mut Vec<T>, &mut Problem) {
+    (&mut self.particles, &mut self.problem)
+  }
+}
+
+impl<T> GlobalBestPos for Bat<T> {
+  fn global_best_pos(&self) -> DVector<f64> {
+    self.global_best_pos.clone().unwrap()
+  }
+
+  fn global_worst_pos(&self) -> DVector<f64> {
+    self.global_worst_pos.clone().unwrap()
+  }
+
+  fn option_global_best_pos(&self) -> &Option<DVector<f64>> {
+    &self.global_best_pos
+  }
+
+  fn option_global_worst_pos(&self) -> &Option<DVector<f64>> {
+    &self.global_worst_pos
+  }
+
+  fn set_global_best_pos(&mut self, pos: DVector<f64>) {
+    self.global_best_pos = Some(pos);
+  }
+
+  fn set_global_worst_pos(&mut self, pos: DVector<f64>) {
+    self.global_worst_pos = Some(pos);
+  }
+}
+
+impl<T> OptimizationProblem for Bat<T> {
+  fn problem(&mut self) -> &mut Problem {
+    &mut self.problem
+  }
+}
+
+impl<T> Name for Bat<T> {
+  fn name(&self) -> &String {
+    &self.name
+  }
+}
+
+impl<T: Clone> Data<T> for Bat<T> {
+  fn data(&self) -> &Vec<(f64, f64, Option<Vec<T>>)> {
+    &self.data
+  }
+
+  fn saving(&self) -> bool {
+    self.save
+  }
+
+  fn additional_data(&self) -> &Vec<Vec<Vec<(String, f64)>>> {
+    &self.additional_data
+  }
+
+  fn data_mut(&mut self) -> &mut Vec<(f64, f64, Option<Vec<T>>)> {
+    &mut self.data
+  }
+
+  fn additional_data_mut(&mut self) -> &mut Vec<Vec<Vec<(String, f64)>>> {
+    &mut self.additional_data
+  }
+
+  fn recorder(&mut self) -> &mut Recorder {
+    &mut self.recorder
+  }
+
+  fn add_data_impl(&mut self, datum: (f64, f64, Option<Vec<T>>)) {
+    self.data.push(datum);
+  }
+
+  fn add_additional_data_impl(&mut self, datum: Vec<Vec<(String, f64)>>) {
+    self.additional_data.push(datum);
+  }
+}
+
+impl<T: Position + Velocity + Clone> DataExporter<T> for Bat<T> {
+  fn out_directory(&self) -> &PathBuf {
+    &self.out_directory
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::optimizers::testing;
+  use crate::particles::bat::BatParticle;
+
+  fn bat(loudness: f64) -> Bat<BatParticle> {
+    let parameters = utils::param_hashmap_generator(vec![
+      ("particle_count", ParamValue::Int(20)),
+      ("f_min", ParamValue::Float(0.)),
+      ("f_max", ParamValue::Float(2.)),
+      ("loudness", ParamValue::Float(loudness)),
+      ("pulse_rate", ParamValue::Float(0.5)),
+      ("alpha", ParamValue::Float(0.9)),
+      ("gamma", ParamValue::Float(0.9)),
+      testing::behavior(),
+      testing::seed(),
+    ]);
+    Bat::new(
+      "bat".to_owned(),
+      problems::sphere_100(5),
+      parameters,
+      PathBuf::new(),
+      false,
+    )
+  }
+
+  #[test]
+  fn improves_on_sphere() {
+    testing::assert_improves(&mut bat(1.), 100, 0.05);
+  }
+
+  #[test]
+  fn accepted_moves_quiet_the_bat_and_raise_its_pulse_rate() {
+    let mut optimizer = bat(1.);
+    let before: Vec<(DVector<f64>, f64)> =
+      optimizer.particles().iter().map(|p| p.pos().clone()).zip(optimizer.fitness.clone()).collect();
+    optimizer.step(0, 10);
+
+    let mut accepted = 0;
+    for (i, (pos, fitness)) in before.into_iter().enumerate() {
+      let particle = &optimizer.particles()[i];
+      if particle.pos() == &pos {
+        assert_eq!(particle.loudness(), 1.);
+        assert_eq!(particle.pulse_rate(), 0.5);
+        assert_eq!(optimizer.fitness[i], fitness);
+      } else {
+        // A loudness of 1 accepts every move that is no worse.
+        accepted += 1;
+        assert_eq!(particle.loudness(), 0.9);
+        assert_eq!(particle.pulse_rate(), 0.5 * (1. - (-0.9f64).exp()));
+        assert!(optimizer.fitness[i] <= fitness);
+      }
+    }
+    assert!(accepted > 0);
+  }
+
+  #[test]
+  fn silent_bats_never_move() {
+    let mut optimizer = bat(0.);
+    let before: Vec<DVector<f64>> = optimizer.particles().iter().map(|p| p.pos().clone()).collect();
+    optimizer.run(10);
+    let after: Vec<DVector<f64>> = optimizer.particles().iter().map(|p| p.pos().clone()).collect();
+    assert_eq!(after, before);
+  }
+}
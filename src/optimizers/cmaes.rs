@@ -0,0 +1,636 @@
+use crate::optimizers::traits::{
+  Data, DataExporter, GlobalBestPos, Name, OptimizationProblem, Optimizer, ParamValue, Particles,
+};
+use crate::particles::boundary::Boundary;
+use crate::particles::traits::{Behavior, Particle, Position, Velocity};
+use crate::problems;
+use crate::rand::Rng;
+use crate::recording::Recorder;
+use crate::utils;
+use nalgebra::{DMatrix, DVector, SymmetricEigen};
+use problems::Problem;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use rand_distr::StandardNormal;
+use std::{collections::HashMap, path::PathBuf};
+use strum_macros::EnumIter;
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, EnumIter, Debug, PartialEq)]
+pub enum Restart {
+  None,
+  Ipop,
+  Bipop,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Regime {
+  Large,
+  Small,
+}
+
+#[derive(Clone)]
+pub struct Cmaes<T> {
+  name: String,
+  problem: Problem,
+  particles: Vec<T>,
+  global_best_pos: Option<DVector<f64>>,
+  global_worst_pos: Option<DVector<f64>>,
+  data: Vec<(f64, f64, Option<Vec<T>>)>,
+  additional_data: Vec<Vec<Vec<(String, f64)>>>,
+  recorder: Recorder,
+  out_directory: PathBuf,
+  save: bool,
+  behavior: Behavior,
+  restart: Restart,
+  // Random numbers of the run, seeded with the `seed` parameter.
+  rng: StdRng,
+
+  // Strategy parameters. These depend on the population size and are
+  // recomputed on every restart.
+  default_lambda: usize,
+  lambda: usize,
+  weights: DVector<f64>,
+  mu_eff: f64,
+  cc: f64,
+  cs: f64,
+  c1: f64,
+  cmu: f64,
+  damps: f64,
+  chi_n: f64,
+
+  // Distribution state.
+  sigma0: f64,
+  sigma: f64,
+  mean: DVector<f64>,
+  c: DMatrix<f64>,
+  b: DMatrix<f64>,
+  d: DVector<f64>,
+  pc: DVector<f64>,
+  ps: DVector<f64>,
+  generation: usize,
+  eigen_generation: usize,
+  best_history: Vec<f64>,
+
+  // Restart bookkeeping for IPOP/BIPOP.
+  regime: Regime,
+  restarts: usize,
+  large_restarts: usize,
+  large_evaluations: usize,
+  small_evaluations: usize,
+  evaluations_at_restart: usize,
+  // Set when a generation meets a stopping criterion. The restart happens at the start
+  // of the next generation, so that the recorded one keeps its own population.
+  restart_pending: bool,
+}
+
+impl<T: Particle + Position + Velocity + Clone> Optimizer<T> for Cmaes<T> {
+  fn new(
+    name: String,
+    problem: Problem,
+    parameters: HashMap<String, ParamValue>,
+    out_directory: PathBuf,
+    save: bool,
+  ) -> Cmaes<T> {
+    let dim = problem.dim();
+    let default_lambda = 4 + (3. * (dim as f64).ln()).floor() as usize;
+    let number_of_particles = match parameters.contains_key("particle_count") {
+      true => match parameters["particle_count"] {
+        ParamValue::Int(val) => val as usize,
+        _ => {
+          eprintln!("Error: parameter 'particle_count' should be of type Param::Int.");
+          std::process::exit(1);
+        }
+      },
+      false => default_lambda,
+    };
+
+    let sigma0 = match parameters.contains_key("sigma0") {
+      true => match parameters["sigma0"] {
+        ParamValue::Float(val) => val,
+        _ => {
+          eprintln!("Error: parameter 'sigma0' should be of type Param::Float.");
+          std::process::exit(1);
+        }
+      },
+      false => 0.3 * (problem.domain().1 - problem.domain().0),
+    };
+
+    let restart = match parameters.contains_key("restart") {
+      true => match parameters["restart"] {
+        ParamValue::Restart(val) => val,
+        _ => {
+          eprintln!("Error: parameter 'restart' should be of type Param::Restart.");
+          std::process::exit(1);
+        }
+      },
+      false => Restart::None,
+    };
+
+    let seed = match parameters.contains_key("seed") {
+      true => match parameters["seed"] {
+        ParamValue::Int(val) => val as u64,
+        _ => {
+          eprintln!("Error: parameter 'seed' should be of type Param::Int.");
+          std::process::exit(1);
+        }
+      },
+      false => rand::thread_rng().gen(),
+    };
+
+    assert!(parameters.contains_key("behavior"), "Key 'behavior' not found.");
+    let behavior = match parameters["behavior"] {
+      ParamValue::Behavior(val) => val,
+      _ => {
+        eprintln!("Error: parameter 'behavior' should be of type Param::Behavior.");
+        std::process::exit(1);
+      }
+    };
+
+    let mut cmaes = Cmaes {
+      name,
+      problem,
+      particles: Vec::new(),
+      global_best_pos: None,
+      global_worst_pos: None,
+      data: Vec::new(),
+      additional_data: Vec::new(),
+      recorder: Recorder::default(),
+      out_directory,
+      save,
+      behavior,
+      restart,
+      rng: StdRng::seed_from_u64(seed),
+      default_lambda: number_of_particles,
+      lambda: number_of_particles,
+      weights: DVector::from_element(0, 0.),
+      mu_eff: 0.,
+      cc: 0.,
+      cs: 0.,
+      c1: 0.,
+      cmu: 0.,
+      damps: 0.,
+      chi_n: 0.,
+      sigma0,
+      sigma: sigma0,
+      mean: DVector::from_element(dim, 0.),
+      c: DMatrix::identity(dim, dim),
+      b: DMatrix::identity(dim, dim),
+      d: DVector::from_element(dim, 1.),
+      pc: DVector::from_element(dim, 0.),
+      ps: DVector::from_element(dim, 0.),
+      generation: 0,
+      eigen_generation: 0,
+      best_history: Vec::new(),
+      regime: Regime::Large,
+      restarts: 0,
+      large_restarts: 0,
+      large_evaluations: 0,
+      small_evaluations: 0,
+      evaluations_at_restart: 0,
+      restart_pending: false,
+    };
+
+    cmaes.init(number_of_particles, behavior);
+    cmaes
+  }
+
+  fn init(&mut self, number_of_particles: usize, behavior: Behavior) {
+    self.reset_distribution(number_of_particles, self.sigma0);
+
+    let problem = &mut self.problem();
+    problem.sampler().reset(number_of_particles);
+    behavior.edge.setup(problem);
+    let mut particles: Vec<T> = Vec::new();
+    for _ in 0..number_of_particles {
+      particles.push(T::new(problem, behavior));
+    }
+
+    self.particles = particles;
+
+    // The first generation is drawn from N(m, σ²C) like every later one.
+    let vels = self.calculate_vels(0, 0);
+    self.move_particles(vels);
+    self.update_best_and_worst();
+
+    utils::create_directory(self.out_directory().to_path_buf(), true, false);
+  }
+
+  fn calculate_vel(&mut self, _i: usize) -> DVector<f64> {
+    // The "velocity" of a CMA-ES sample is its step from the current mean.
+    let z = DVector::from_iterator(
+      self.mean.len(),
+      (0..self.mean.len()).map(|_| self.rng.sample(StandardNormal)),
+    );
+    self.sigma * &self.b * self.d.component_mul(&z)
+  }
+
+  fn restart_count(&self) -> usize {
+    self.restarts
+  }
+
+  fn calculate_vels(&mut self, _iter: usize, _iterations: usize) -> Vec<DVector<f64>> {
+    if self.restart_pending {
+      self.restart_pending = false;
+      self.restart_distribution();
+    }
+
+    // Sample the population around the mean.
+    let mean = self.mean.clone();
+    for particle in self.particles_mut().iter_mut() {
+      particle.set_pos(mean.clone());
+    }
+    (0..self.lambda).map(|k| self.calculate_vel(k)).collect()
+  }
+
+  fn post_step(&mut self, _iter: usize, _iterations: usize, _gbest: f64, _gworst: f64) {
+    let fitness = self.evaluate();
+    let mut order: Vec<usize> = (0..self.lambda).collect();
+    order.sort_by(|&a, &b| fitness[a].total_cmp(&fitness[b]));
+
+    self.update_distribution(&order);
+    self.best_history.push(fitness[order[0]]);
+
+    let condition_number = (self.d.max() / self.d.min()).powi(2);
+    let sigma = self.sigma;
+    self.restart_pending = self.restart != Restart::None && self.should_restart(&fitness, condition_number);
+    self.add_additional_data(
+      self.save,
+      vec![vec![
+        ("sigma".to_owned(), sigma),
+        ("condition_number".to_owned(), condition_number),
+      ]],
+    );
+  }
+
+  // Every particle is resampled each iteration, so only the strategy parameters have to follow.
+  fn resize(&mut self, count: usize) {
+    // The recombination needs at least two samples.
+    let count = count.max(2);
+    self.set_population_size(count);
+
+    self.particles.truncate(count);
+    while self.particles.len() < count {
+      self.particles.push(T::new(&mut self.problem, self.behavior));
+    }
+  }
+}
+
+impl<T: Particle + Position + Velocity + Clone> Cmaes<T> {
+  fn reset_distribution(&mut self, lambda: usize, sigma: f64) {
+    self.set_population_size(lambda);
+
+    let dim = self.problem.dim();
+    self.sigma = sigma;
+    let (lo, up) = self.problem.domain();
+    self.mean = DVector::from_fn(dim, |_, _| self.rng.gen_range(lo..up));
+    self.c = DMatrix::identity(dim, dim);
+    self.b = DMatrix::identity(dim, dim);
+    self.d = DVector::from_element(dim, 1.);
+    self.pc = DVector::from_element(dim, 0.);
+    self.ps = DVector::from_element(dim, 0.);
+    self.generation = 0;
+    self.eigen_generation = 0;
+    self.best_history = Vec::new();
+  }
+
+  // Recomputes the strategy parameters that depend on the population size.
+  fn set_population_size(&mut self, lambda: usize) {
+    assert!(lambda >= 2, "CMA-ES needs a population of at least 2 to recombine.");
+    let n = self.problem.dim() as f64;
+    let mu = lambda / 2;
+
+    let raw_weights = DVector::from_iterator(
+      mu,
+      (0..mu).map(|i| (lambda as f64 / 2. + 0.5).ln() - ((i + 1) as f64).ln()),
+    );
+    let weights = &raw_weights / raw_weights.sum();
+    let mu_eff = weights.sum().powi(2) / weights.norm_squared();
+
+    self.lambda = lambda;
+    self.weights = weights;
+    self.mu_eff = mu_eff;
+    self.cc = (4. + mu_eff / n) / (n + 4. + 2. * mu_eff / n);
+    self.cs = (mu_eff + 2.) / (n + mu_eff + 5.);
+    self.c1 = 2. / ((n + 1.3).powi(2) + mu_eff);
+    self.cmu = f64::min(
+      1. - self.c1,
+      2. * (mu_eff - 2. + 1. / mu_eff) / ((n + 2.).powi(2) + mu_eff),
+    );
+    self.damps = 1. + 2. * f64::max(0., ((mu_eff - 1.) / (n + 1.)).sqrt() - 1.) + self.cs;
+    self.chi_n = n.sqrt() * (1. - 1. / (4. * n) + 1. / (21. * n * n));
+  }
+
+  fn update_distribution(&mut self, order: &[usize]) {
+    let n = self.problem.dim() as f64;
+    let mu = self.weights.len();
+    self.generation += 1;
+
+    let old_mean = self.mean.clone();
+    let mut mean = DVector::from_element(old_mean.len(), 0.);
+    for (i, &idx) in order.iter().take(mu).enumerate() {
+      mean += self.weights[i] * self.particles[idx].pos();
+    }
+    self.mean = mean;
+    let y_w = (&self.mean - &old_mean) / self.sigma;
+
+    // Step-size path.
+    let inv_sqrt_c = &self.b * DMatrix::from_diagonal(&self.d.map(|x| 1. / x)) * self.b.transpose();
+    self.ps = (1. - self.cs) * &self.ps + (self.cs * (2. - self.cs) * self.mu_eff).sqrt() * inv_sqrt_c * &y_w;
+
+    // Covariance path.
+    let hsig =
+      self.ps.norm() / (1. - (1. - self.cs).powi(2 * self.generation as i32)).sqrt() / self.chi_n < 1.4 + 2. / (n + 1.);
+    let hsig = if hsig { 1. } else { 0. };
+    self.pc = (1. - self.cc) * &self.pc + hsig * (self.cc * (2. - self.cc) * self.mu_eff).sqrt() * &y_w;
+
+    // Rank-one and rank-mu update.
+    let mut rank_mu = DMatrix::from_element(self.c.nrows(), self.c.ncols(), 0.);
+    for (i, &idx) in order.iter().take(mu).enumerate() {
+      let y = (self.particles[idx].pos() - &old_mean) / self.sigma;
+      rank_mu += self.weights[i] * &y * y.transpose();
+    }
+    let rank_one = &self.pc * self.pc.transpose() + (1. - hsig) * self.cc * (2. - self.cc) * &self.c;
+    self.c = (1. - self.c1 - self.cmu) * &self.c + self.c1 * rank_one + self.cmu * rank_mu;
+
+    self.sigma *= ((self.cs / self.damps) * (self.ps.norm() / self.chi_n - 1.)).exp();
+
+    // Decompose lazily, as the eigen decomposition is O(n^3).
+    let lag = f64::max(1., 1. / ((self.c1 + self.cmu) * n * 10.)) as usize;
+    if self.generation - self.eigen_generation >= lag {
+      self.eigen_generation = self.generation;
+      self.c = (&self.c + self.c.transpose()) * 0.5;
+      let eigen = SymmetricEigen::new(self.c.clone());
+      self.b = eigen.eigenvectors;
+      self.d = eigen.eigenvalues.map(|x| x.max(f64::EPSILON).sqrt());
+    }
+  }
+
+  fn should_restart(&self, fitness: &[f64], condition_number: f64) -> bool {
+    let n = self.problem.dim() as f64;
+    if !self.sigma.is_finite() || condition_number > 1e14 {
+      return true;
+    }
+
+    // TolX: all standard deviations and the evolution path are tiny.
+    let tol_x =
+      (0..self.pc.len()).all(|i| self.sigma * f64::max(self.pc[i].abs(), self.c[(i, i)].sqrt()) < 1e-12 * self.sigma0);
+    if tol_x {
+      return true;
+    }
+
+    // TolFun: no progress over a window of recent generations.
+    let window = 10 + (30. * n / self.lambda as f64).ceil() as usize;
+    if self.best_history.len() >= window {
+      let recent = &self.best_history[self.best_history.len() - window..];
+      let history_range =
+        recent.iter().fold(f64::NEG_INFINITY, |a, &b| a.max(b)) - recent.iter().fold(f64::INFINITY, |a, &b| a.min(b));
+      let fitness_range =
+        fitness.iter().fold(f64::NEG_INFINITY, |a, &b| a.max(b)) - fitness.iter().fold(f64::INFINITY, |a, &b| a.min(b));
+      if history_range < 1e-12 && fitness_range < 1e-12 {
+        return true;
+      }
+    }
+    false
+  }
+
+  fn restart_distribution(&mut self) {
+    self.restarts += 1;
+    let spent = self.problem.cnt() - self.evaluations_at_restart;
+    self.evaluations_at_restart = self.problem.cnt();
+    match self.regime {
+      Regime::Large => self.large_evaluations += spent,
+      Regime::Small => self.small_evaluations += spent,
+    }
+
+    let (lambda, sigma) = match self.restart {
+      Restart::None => (self.lambda, self.sigma0),
+      Restart::Ipop => {
+        self.large_restarts += 1;
+        (
+          self.default_lambda * 2usize.pow(self.large_restarts as u32),
+          self.sigma0,
+        )
+      }
+      Restart::Bipop => {
+        if self.large_evaluations <= self.small_evaluations {
+          self.regime = Regime::Large;
+          self.large_restarts += 1;
+          (
+            self.default_lambda * 2usize.pow(self.large_restarts as u32),
+            self.sigma0,
+          )
+        } else {
+          self.regime = Regime::Small;
+          let u: f64 = self.rng.gen_range(0.0..1.0);
+          let large_lambda = (self.default_lambda * 2usize.pow(self.large_restarts as u32)) as f64;
+          let ratio = 0.5 * large_lambda / self.default_lambda as f64;
+          let lambda = (self.default_lambda as f64 * ratio.powf(u * u)).floor() as usize;
+          (lambda.max(self.default_lambda), self.sigma0 * 10f64.powf(-2. * u))
+        }
+      }
+    };
+
+    self.reset_distribution(lambda, sigma);
+    self.resize(lambda);
+  }
+}
+
+impl<T> Particles<T> for Cmaes<T> {
+  fn particles(&self) -> &Vec<T> {
+    &self.particles
+  }
+
+  fn particles_mut(&mut self) -> &mut Vec<T> {
+    &mut self.particles
+  }
+
+  fn particles_and_problem_mut(&mut self) -> (&mut Vec<T>, &mut Problem) {
+    (&mut self.particles, &mut self.problem)
+  }
+}
+
+impl<T> GlobalBestPos for Cmaes<T> {
+  fn global_best_pos(&self) -> DVector<f64> {
+    self.global_best_pos.clone().unwrap()
+  }
+
+  fn global_worst_pos(&self) -> DVector<f64> {
+    self.global_worst_pos.clone().unwrap()
+  }
+
+  fn option_global_best_pos(&self) -> &Option<DVector<f64>> {
+    &self.global_best_pos
+  }
+
+  fn option_global_worst_pos(&self) -> &Option<DVector<f64>> {
+    &self.global_worst_pos
+  }
+
+  fn set_global_best_pos(&mut self, pos: DVector<f64>) {
+    self.global_best_pos = Some(pos);
+  }
+
+  fn set_global_worst_pos(&mut self, pos: DVector<f64>) {
+    self.global_worst_pos = Some(pos);
+  }
+}
+
+impl<T> OptimizationProblem for Cmaes<T> {
+  fn problem(&mut self) -> &mut Problem {
+    &mut self.problem
+  }
+}
+
+impl<T> Name for Cmaes<T> {
+  fn name(&self) -> &String {
+    &self.name
+  }
+}
+
+impl<T: Clone> Data<T> for Cmaes<T> {
+  fn data(&self) -> &Vec<(f64, f64, Option<Vec<T>>)> {
+    &self.data
+  }
+
+  fn saving(&self) -> bool {
+    self.save
+  }
+
+  fn additional_data(&self) -> &Vec<Vec<Vec<(String, f64)>>> {
+    &self.additional_data
+  }
+
+  fn data_mut(&mut self) -> &mut Vec<(f64, f64, Option<Vec<T>>)> {
+    &mut self.data
+  }
+
+  fn additional_data_mut(&mut self) -> &mut Vec<Vec<Vec<(String, f64)>>> {
+    &mut self.additional_data
+  }
+
+  fn recorder(&mut self) -> &mut Recorder {
+    &mut self.recorder
+  }
+
+  fn add_data_impl(&mut self, datum: (f64, f64, Option<Vec<T>>)) {
+    self.data.push(datum);
+  }
+
+  fn add_additional_data_impl(&mut self, datum: Vec<Vec<(String, f64)>>) {
+    self.additional_data.push(datum);
+  }
+}
+
+impl<T: Position + Velocity + Clone> DataExporter<T> for Cmaes<T> {
+  fn out_directory(&self) -> &PathBuf {
+    &self.out_directory
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::optimizers::testing;
+  use crate::particles::cmaes::CmaesParticle;
+  use crate::particles::initializer::Init;
+  use crate::particles::traits::Edge;
+
+  fn cmaes(particle_count: usize, sigma0: f64, restart: Restart) -> Cmaes<CmaesParticle> {
+    let parameters = utils::param_hashmap_generator(vec![
+      ("particle_count", ParamValue::Int(particle_count as isize)),
+      ("sigma0", ParamValue::Float(sigma0)),
+      ("restart", ParamValue::Restart(restart)),
+      testing::seed(),
+      (
+        "behavior",
+        ParamValue::Behavior(Behavior {
+          edge: Edge::Pass,
+          vmax: None,
+          pos_init: Init::Uniform,
+          vel_init: None,
+        }),
+      ),
+    ]);
+    Cmaes::new(
+      "cmaes".to_owned(),
+      problems::sphere_100(5),
+      parameters,
+      PathBuf::new(),
+      false,
+    )
+  }
+
+  #[test]
+  fn samples_the_first_generation_around_the_mean() {
+    let optimizer = cmaes(10, 1., Restart::None);
+    let mean = optimizer.mean.clone();
+    // Samples of N(m, I) lie about √5 from the mean, uniform particles about 100.
+    let distance = optimizer.particles.iter().map(|p| (p.pos() - &mean).norm()).sum::<f64>() / 10.;
+    assert!(distance < 5., "{}", distance);
+    assert!(optimizer.particles.iter().all(|p| p.cached_fitness().is_some()));
+  }
+
+  #[test]
+  fn adapts_the_step_size() {
+    let mut optimizer = cmaes(10, 60., Restart::None);
+    let initial = optimizer.problem.f(&optimizer.mean.clone());
+    optimizer.run(200);
+    assert!(optimizer.sigma < 1e-2 * optimizer.sigma0, "σ = {}", optimizer.sigma);
+    let last = optimizer.data().last().unwrap().0;
+    assert!(last < 1e-6 * initial, "{} from {}", last, initial);
+  }
+
+  #[test]
+  fn ipop_restart_doubles_the_population() {
+    let mut optimizer = cmaes(6, 60., Restart::Ipop);
+    optimizer.run(5);
+    optimizer.restart_distribution();
+    assert_eq!(optimizer.restart_count(), 1);
+    assert_eq!(optimizer.lambda, 12);
+    assert_eq!(optimizer.particles().len(), 12);
+    assert_eq!(optimizer.sigma, optimizer.sigma0);
+    optimizer.restart_distribution();
+    assert_eq!(optimizer.lambda, 24);
+
+    // The doubled population keeps running.
+    optimizer.run(5);
+    assert_eq!(optimizer.data().len(), 10);
+  }
+
+  #[test]
+  fn restarts_after_the_generation_is_recorded() {
+    let mut optimizer = cmaes(6, 60., Restart::Ipop);
+    optimizer.run(2);
+    // An ill-conditioned covariance meets the stopping criteria.
+    optimizer.c[(0, 0)] = 1e16;
+    optimizer.step(2, 10);
+    assert!(optimizer.restart_pending);
+    assert_eq!(optimizer.restart_count(), 0);
+    assert_eq!(optimizer.particles().len(), 6);
+
+    optimizer.step(3, 10);
+    assert_eq!(optimizer.restart_count(), 1);
+    assert_eq!(optimizer.particles().len(), 12);
+    // The new distribution started from the identity and went through one generation.
+    assert!(optimizer.c[(0, 0)] < 10., "{}", optimizer.c);
+    assert_eq!(optimizer.data().len(), 4);
+  }
+
+  #[test]
+  fn runs_with_the_same_seed_repeat() {
+    let fitness = |optimizer: &Cmaes<CmaesParticle>| optimizer.data().iter().map(|d| d.0).collect::<Vec<f64>>();
+    let mut first = cmaes(6, 60., Restart::None);
+    let mut second = cmaes(6, 60., Restart::None);
+    first.run(20);
+    second.run(20);
+    assert_eq!(first.mean, second.mean);
+    assert_eq!(fitness(&first), fitness(&second));
+  }
+
+  #[test]
+  #[should_panic(expected = "at least 2")]
+  fn rejects_a_single_sample() {
+    cmaes(1, 60., Restart::None);
+  }
+}
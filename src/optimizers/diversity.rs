@@ -0,0 +1,173 @@
+use crate::optimizers::observer::{Observer, Snapshot};
+use crate::particles::traits::{Position, Velocity};
+use nalgebra::DVector;
+use serde_json::{json, Map, Value};
+
+// Number of neighbours of the entropy estimate, as in the visualizer.
+const ENTROPY_NEIGHBOURS: usize = 10;
+
+// Diversity of the swarm at every iteration, with the measures the visualizer computes
+// from data.json, so that they are available without saving the trajectory.
+#[allow(dead_code)]
+#[derive(Default)]
+pub struct Diversity {
+  knn_entropy: Vec<f64>,
+  dwd_position: Vec<f64>,
+  dwd_velocity: Vec<f64>,
+  adp_position: Vec<f64>,
+  adp_velocity: Vec<f64>,
+  radius: Vec<f64>,
+  dimension_wise: Vec<f64>,
+}
+
+impl<T: Position + Velocity> Observer<T> for Diversity {
+  fn on_iteration_end(&mut self, snapshot: &Snapshot<T>) {
+    let pos: Vec<&DVector<f64>> = snapshot.particles.iter().map(|p| p.pos()).collect();
+    let vel: Vec<&DVector<f64>> = snapshot.particles.iter().map(|p| p.vel()).collect();
+
+    self.knn_entropy.push(knn_entropy(&pos, &vel));
+    let position = standardized(&pos);
+    let velocity = standardized(&vel);
+    self.dwd_position.push(dwd(&position));
+    self.dwd_velocity.push(dwd(&velocity));
+    self.adp_position.push(adp(&position));
+    self.adp_velocity.push(adp(&velocity));
+    self.radius.push(radius(&pos));
+    self.dimension_wise.push(dimension_wise(&pos));
+  }
+
+  fn summary(&self) -> Map<String, Value> {
+    // Exploration and exploitation are relative to the most diverse iteration of the run.
+    let max = self.dimension_wise.iter().copied().fold(0., f64::max);
+    let exploration: Vec<f64> = self.dimension_wise.iter().map(|div| 100. * div / max).collect();
+    let exploitation: Vec<f64> = self.dimension_wise.iter().map(|div| 100. * (max - div).abs() / max).collect();
+
+    let mut summary = Map::new();
+    summary.insert("knn_entropy".to_owned(), json!(self.knn_entropy));
+    summary.insert("dwd_position".to_owned(), json!(self.dwd_position));
+    summary.insert("dwd_velocity".to_owned(), json!(self.dwd_velocity));
+    summary.insert("adp_position".to_owned(), json!(self.adp_position));
+    summary.insert("adp_velocity".to_owned(), json!(self.adp_velocity));
+    summary.insert("radius".to_owned(), json!(self.radius));
+    summary.insert("dimension_wise_diversity".to_owned(), json!(self.dimension_wise));
+    summary.insert("exploration".to_owned(), json!(exploration));
+    summary.insert("exploitation".to_owned(), json!(exploitation));
+    summary
+  }
+}
+
+// Mean log distance to the k-th nearest neighbour in the joint position-velocity space.
+fn knn_entropy(pos: &[&DVector<f64>], vel: &[&DVector<f64>]) -> f64 {
+  let n = pos.len();
+  let k = ENTROPY_NEIGHBOURS.min(n.saturating_sub(1));
+  if k == 0 {
+    return f64::NAN;
+  }
+  let mut sum = 0.;
+  for i in 0..n {
+    let mut distances: Vec<f64> = (0..n)
+      .filter(|&j| j != i)
+      .map(|j| ((pos[i] - pos[j]).norm_squared() + (vel[i] - vel[j]).norm_squared()).sqrt())
+      .collect();
+    distances.select_nth_unstable_by(k - 1, |a, b| a.total_cmp(b));
+    sum += distances[k - 1].ln();
+  }
+  sum / n as f64
+}
+
+// Every component minus the mean of its dimension, over its standard deviation.
+// Dimensions where all particles agree are zero.
+fn standardized(data: &[&DVector<f64>]) -> Vec<DVector<f64>> {
+  if data.is_empty() {
+    return Vec::new();
+  }
+  let n = data.len() as f64;
+  let mean = data.iter().fold(DVector::zeros(data[0].len()), |sum, x| sum + *x) / n;
+  let std =
+    data.iter().fold(DVector::zeros(mean.len()), |sum, x| sum + (*x - &mean).map(|d| d * d)).map(|v| (v / n).sqrt());
+  data.iter().map(|x| (*x - &mean).zip_map(&std, |d, s| if s > 0. { d / s } else { 0. })).collect()
+}
+
+// Mean absolute standardized deviation over all components.
+fn dwd(data: &[DVector<f64>]) -> f64 {
+  let count: usize = data.iter().map(|x| x.len()).sum();
+  data.iter().map(|x| x.lp_norm(1)).sum::<f64>() / count as f64
+}
+
+// Root of the mean squared norm of the standardized particles.
+fn adp(data: &[DVector<f64>]) -> f64 {
+  (data.iter().map(|x| x.norm_squared()).sum::<f64>() / data.len() as f64).sqrt()
+}
+
+// Largest distance of a particle to the centroid.
+fn radius(pos: &[&DVector<f64>]) -> f64 {
+  if pos.is_empty() {
+    return f64::NAN;
+  }
+  let centroid = pos.iter().fold(DVector::zeros(pos[0].len()), |sum, x| sum + *x) / pos.len() as f64;
+  pos.iter().map(|x| (*x - &centroid).norm()).fold(0., f64::max)
+}
+
+// Mean distance to the median along every dimension, averaged over the dimensions.
+fn dimension_wise(pos: &[&DVector<f64>]) -> f64 {
+  if pos.is_empty() {
+    return f64::NAN;
+  }
+  let (n, dim) = (pos.len(), pos[0].len());
+  let mut total = 0.;
+  for d in 0..dim {
+    let mut column: Vec<f64> = pos.iter().map(|x| x[d]).collect();
+    column.sort_by(|a, b| a.total_cmp(b));
+    let median = (column[(n - 1) / 2] + column[n / 2]) / 2.;
+    total += column.iter().map(|x| (median - x).abs()).sum::<f64>() / n as f64;
+  }
+  total / dim as f64
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // Twelve particles in two dimensions, so that the tenth neighbour of the visualizer
+  // exists. The expected values come from `visualizer/pso.py` on the same swarm.
+  fn swarm() -> (Vec<DVector<f64>>, Vec<DVector<f64>>) {
+    let pos = (0..12).map(|i| DVector::from_vec(vec![i as f64, ((i * i) % 7) as f64])).collect();
+    let vel = (0..12).map(|i| DVector::from_vec(vec![(i % 3) as f64 - 1., 0.5 * i as f64])).collect();
+    (pos, vel)
+  }
+
+  fn assert_close(actual: f64, expected: f64) {
+    assert!((actual - expected).abs() < 1e-12, "{} != {}", actual, expected);
+  }
+
+  #[test]
+  fn measures_match_the_visualizer() {
+    let (pos, vel) = swarm();
+    let pos: Vec<&DVector<f64>> = pos.iter().collect();
+    let vel: Vec<&DVector<f64>> = vel.iter().collect();
+
+    assert_close(knn_entropy(&pos, &vel), 2.1390274633848683);
+    assert_close(dwd(&standardized(&pos)), 0.8315140316541295);
+    assert_close(dwd(&standardized(&vel)), 0.8427723850906039);
+    // Standardized data has a unit variance in every dimension.
+    assert_close(adp(&standardized(&pos)), 2f64.sqrt());
+    assert_close(adp(&standardized(&vel)), 2f64.sqrt());
+  }
+
+  #[test]
+  fn radius_is_the_farthest_particle_from_the_centroid() {
+    let (pos, _) = swarm();
+    let pos: Vec<&DVector<f64>> = pos.iter().collect();
+    // The centroid is (5.5, 23/12), and the farthest particle is (0, 0).
+    assert_close(radius(&pos), (5.5f64.powi(2) + (23. / 12f64).powi(2)).sqrt());
+
+    // Three corners of a square, around (1/3, -1/3): the two opposite ones are the farthest.
+    let corners = [
+      DVector::from_vec(vec![1., 1.]),
+      DVector::from_vec(vec![-1., -1.]),
+      DVector::from_vec(vec![1., -1.]),
+    ];
+    let corners: Vec<&DVector<f64>> = corners.iter().collect();
+    assert_close(radius(&corners), 20f64.sqrt() / 3.);
+  }
+}
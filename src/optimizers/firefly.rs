@@ -0,0 +1,322 @@
+use crate::optimizers::traits::{
+  Data, DataExporter, GlobalBestPos, Name, OptimizationProblem, Optimizer, ParamValue, Particles,
+};
+use crate::particles::boundary::Boundary;
+use crate::particles::traits::{Behavior, Particle, Position, Velocity};
+use crate::problems;
+use crate::rand::Rng;
+use crate::recording::Recorder;
+use crate::utils;
+use nalgebra::DVector;
+use problems::Problem;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::{collections::HashMap, path::PathBuf};
+
+#[derive(Clone)]
+pub struct Firefly<T> {
+  name: String,
+  problem: Problem,
+  particles: Vec<T>,
+  global_best_pos: Option<DVector<f64>>,
+  global_worst_pos: Option<DVector<f64>>,
+  alpha: f64,
+  alpha_decay: f64,
+  beta0: f64,
+  gamma: f64,
+  // Random numbers of the run, seeded with the `seed` parameter.
+  rng: StdRng,
+  data: Vec<(f64, f64, Option<Vec<T>>)>,
+  additional_data: Vec<Vec<Vec<(String, f64)>>>,
+  recorder: Recorder,
+  out_directory: PathBuf,
+  save: bool,
+}
+
+impl<T: Particle + Position + Velocity + Clone> Optimizer<T> for Firefly<T> {
+  fn new(
+    name: String,
+    problem: Problem,
+    parameters: HashMap<String, ParamValue>,
+    out_directory: PathBuf,
+    save: bool,
+  ) -> Firefly<T> {
+    assert!(
+      parameters.contains_key("particle_count"),
+      "Key 'particle_count' not found."
+    );
+    let number_of_particles = match parameters["particle_count"] {
+      ParamValue::Int(val) => val as usize,
+      _ => {
+        eprintln!("Error: parameter 'particle_count' should be of type Param::Int.");
+        std::process::exit(1);
+      }
+    };
+
+    assert!(parameters.contains_key("alpha"), "Key 'alpha' not found.");
+    let alpha = match parameters["alpha"] {
+      ParamValue::Float(val) => val,
+      _ => {
+        eprintln!("Error: parameter 'alpha' should be of type Param::Float.");
+        std::process::exit(1);
+      }
+    };
+
+    assert!(parameters.contains_key("beta0"), "Key 'beta0' not found.");
+    let beta0 = match parameters["beta0"] {
+      ParamValue::Float(val) => val,
+      _ => {
+        eprintln!("Error: parameter 'beta0' should be of type Param::Float.");
+        std::process::exit(1);
+      }
+    };
+
+    assert!(parameters.contains_key("gamma"), "Key 'gamma' not found.");
+    let gamma = match parameters["gamma"] {
+      ParamValue::Float(val) => val,
+      _ => {
+        eprintln!("Error: parameter 'gamma' should be of type Param::Float.");
+        std::process::exit(1);
+      }
+    };
+
+    let alpha_decay = match parameters.contains_key("alpha_decay") {
+      true => match parameters["alpha_decay"] {
+        ParamValue::Float(val) => val,
+        _ => {
+          eprintln!("Error: parameter 'alpha_decay' should be of type Param::Float.");
+          std::process::exit(1);
+        }
+      },
+      false => 1.,
+    };
+
+    let seed = match parameters.contains_key("seed") {
+      true => match parameters["seed"] {
+        ParamValue::Int(val) => val as u64,
+        _ => {
+          eprintln!("Error: parameter 'seed' should be of type Param::Int.");
+          std::process::exit(1);
+        }
+      },
+      false => rand::thread_rng().gen(),
+    };
+
+    assert!(parameters.contains_key("behavior"), "Key 'behavior' not found.");
+    let behavior = match parameters["behavior"] {
+      ParamValue::Behavior(val) => val,
+      _ => {
+        eprintln!("Error: parameter 'behavior' should be of type Param::Behavior.");
+        std::process::exit(1);
+      }
+    };
+
+    let mut firefly = Firefly {
+      name,
+      problem,
+      particles: Vec::new(),
+      global_best_pos: None,
+      global_worst_pos: None,
+      alpha,
+      alpha_decay,
+      beta0,
+      gamma,
+      rng: StdRng::seed_from_u64(seed),
+      data: Vec::new(),
+      additional_data: Vec::new(),
+      recorder: Recorder::default(),
+      out_directory,
+      save,
+    };
+
+    firefly.init(number_of_particles, behavior);
+    firefly
+  }
+
+  fn init(&mut self, number_of_particles: usize, behavior: Behavior) {
+    let problem = &mut self.problem();
+    problem.sampler().reset(number_of_particles);
+    behavior.edge.setup(problem);
+    let mut particles: Vec<T> = Vec::new();
+    for _ in 0..number_of_particles {
+      particles.push(T::new(problem, behavior));
+    }
+
+    self.particles = particles;
+    self.update_best_and_worst();
+
+    utils::create_directory(self.out_directory().to_path_buf(), true, false);
+  }
+
+  fn calculate_vel(&mut self, i: usize) -> DVector<f64> {
+    assert!(i < self.particles().len());
+    let width = self.problem().domain().1 - self.problem().domain().0;
+    let x_i = self.particles()[i].pos().clone();
+    let f_i = self.particle_fitness(i);
+
+    // Move towards every brighter firefly. Distances are measured relative to
+    // the domain width so that `gamma` does not depend on the problem scale.
+    let mut vel = DVector::from_element(x_i.len(), 0.);
+    for j in 0..self.particles().len() {
+      if i == j || self.particle_fitness(j) >= f_i {
+        continue;
+      }
+      let x_j = self.particles()[j].pos().clone();
+      let r = (&x_j - &x_i).norm() / width;
+      let beta = self.beta0 * (-self.gamma * r * r).exp();
+      vel += beta * (x_j - &x_i);
+    }
+
+    for e in vel.iter_mut() {
+      *e += self.alpha * (self.rng.gen_range(0.0..1.0) - 0.5) * width;
+    }
+    vel
+  }
+
+  fn post_step(&mut self, _iter: usize, _iterations: usize, _gbest: f64, _gworst: f64) {
+    self.alpha *= self.alpha_decay;
+  }
+}
+
+impl<T> Particles<T> for Firefly<T> {
+  fn particles(&self) -> &Vec<T> {
+    &self.particles
+  }
+
+  fn particles_mut(&mut self) -> &mut Vec<T> {
+    &mut self.particles
+  }
+
+  fn particles_and_problem_mut(&mut self) -> (&mut Vec<T>, &mut Problem) {
+    (&mut self.particles, &mut self.problem)
+  }
+}
+
+impl<T> GlobalBestPos for Firefly<T> {
+  fn global_best_pos(&self) -> DVector<f64> {
+    self.global_best_pos.clone().unwrap()
+  }
+
+  fn global_worst_pos(&self) -> DVector<f64> {
+    self.global_worst_pos.clone().unwrap()
+  }
+
+  fn option_global_best_pos(&self) -> &Option<DVector<f64>> {
+    &self.global_best_pos
+  }
+
+  fn option_global_worst_pos(&self) -> &Option<DVector<f64>> {
+    &self.global_worst_pos
+  }
+
+  fn set_global_best_pos(&mut self, pos: DVector<f64>) {
+    self.global_best_pos = Some(pos);
+  }
+
+  fn set_global_worst_pos(&mut self, pos: DVector<f64>) {
+    self.global_worst_pos = Some(pos);
+  }
+}
+
+impl<T> OptimizationProblem for Firefly<T> {
+  fn problem(&mut self) -> &mut Problem {
+    &mut self.problem
+  }
+}
+
+impl<T> Name for Firefly<T> {
+  fn name(&self) -> &String {
+    &self.name
+  }
+}
+
+impl<T: Clone> Data<T> for Firefly<T> {
+  fn data(&self) -> &Vec<(f64, f64, Option<Vec<T>>)> {
+    &self.data
+  }
+
+  fn saving(&self) -> bool {
+    self.save
+  }
+
+  fn additional_data(&self) -> &Vec<Vec<Vec<(String, f64)>>> {
+    &self.additional_data
+  }
+
+  fn data_mut(&mut self) -> &mut Vec<(f64, f64, Option<Vec<T>>)> {
+    &mut self.data
+  }
+
+  fn additional_data_mut(&mut self) -> &mut Vec<Vec<Vec<(String, f64)>>> {
+    &mut self.additional_data
+  }
+
+  fn recorder(&mut self) -> &mut Recorder {
+    &mut self.recorder
+  }
+
+  fn add_data_impl(&mut self, datum: (f64, f64, Option<Vec<T>>)) {
+    self.data.push(datum);
+  }
+
+  fn add_additional_data_impl(&mut self, datum: Vec<Vec<(String, f64)>>) {
+    self.additional_data.push(datum);
+  }
+}
+
+impl<T: Position + Velocity + Clone> DataExporter<T> for Firefly<T> {
+  fn out_directory(&self) -> &PathBuf {
+    &self.out_directory
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::optimizers::testing;
+  use crate::particles::basic::BasicParticle;
+
+  fn firefly(particle_count: isize, alpha: f64) -> Firefly<BasicParticle> {
+    let parameters = utils::param_hashmap_generator(vec![
+      ("particle_count", ParamValue::Int(particle_count)),
+      ("alpha", ParamValue::Float(alpha)),
+      ("alpha_decay", ParamValue::Float(0.97)),
+      ("beta0", ParamValue::Float(1.)),
+      ("gamma", ParamValue::Float(1.)),
+      testing::behavior(),
+      testing::seed(),
+    ]);
+    Firefly::new(
+      "firefly".to_owned(),
+      problems::sphere_100(5),
+      parameters,
+      PathBuf::new(),
+      false,
+    )
+  }
+
+  #[test]
+  fn improves_on_sphere() {
+    testing::assert_improves(&mut firefly(20, 0.2), 100, 0.05);
+  }
+
+  #[test]
+  fn attraction_fades_with_distance() {
+    let mut optimizer = firefly(3, 0.);
+    let pos = |x: f64| DVector::from_element(5, x);
+    optimizer.particles_mut()[0].set_pos(pos(-50.));
+    optimizer.particles_mut()[1].set_pos(pos(50.));
+    optimizer.particles_mut()[2].set_pos(pos(90.));
+
+    // The brightest firefly stays put.
+    assert_eq!(optimizer.calculate_vel(0), pos(0.));
+
+    // β = β0·e^(-γr²), with r relative to the domain width.
+    let beta = |r: f64| (-r * r).exp();
+    let r = |a: f64, b: f64| (pos(b) - pos(a)).norm() / 200.;
+    assert_eq!(optimizer.calculate_vel(1), beta(r(50., -50.)) * pos(-100.));
+    let vel = optimizer.calculate_vel(2);
+    let expected = beta(r(90., -50.)) * pos(-140.) + beta(r(90., 50.)) * pos(-40.);
+    assert!((vel - expected).norm() < 1e-12);
+  }
+}
@@ -1,16 +1,19 @@
 use crate::optimizers::traits::{
   Data, DataExporter, GlobalBestPos, Name, OptimizationProblem, Optimizer, ParamValue, Particles,
 };
+use crate::particles::boundary::Boundary;
+use crate::particles::swarm::{self, rows, scale_rows, Swarm};
 use crate::particles::traits::{Behavior, Edge, Mass, Particle, Position, Velocity};
 use crate::problems;
 use crate::rand::Rng;
+use crate::recording::Recorder;
 use crate::utils;
-use nalgebra::DVector;
+use nalgebra::{DMatrix, DVector};
 use problems::Problem;
-use serde_json::json;
+use rand::rngs::StdRng;
+use rayon::prelude::*;
+use serde_json::{json, Value};
 use std::collections::HashMap;
-use std::fs;
-use std::mem;
 use std::path::PathBuf;
 use strum_macros::EnumIter;
 
@@ -25,6 +28,130 @@ pub enum Normalizer {
   Sigmoid4,
 }
 
+impl Normalizer {
+  // Converts the fitness of each particle into its mass.
+  pub fn mass(&self, fitness: Vec<f64>) -> Vec<f64> {
+    let mass = match self {
+      Normalizer::MinMax => utils::original_gsa_mass,
+      Normalizer::ZScore => utils::z_mass,
+      Normalizer::Robust => utils::robust_mass,
+      Normalizer::Rank => utils::rank_mass,
+      Normalizer::Sigmoid2 => utils::sigmoid2_mass,
+      Normalizer::Sigmoid4 => utils::sigmoid4_mass,
+    };
+    utils::feasible_mass(fitness, mass)
+  }
+}
+
+// Sum of the randomly weighted gravitational pulls on particle `i` from every
+// other influencing particle. With `tiled_width`, the domain wraps around and
+// each pull comes from the closest image of the other particle. The weights are
+// drawn dimension by dimension, one per influencing particle including `i`
+// itself, as `gravitational_accelerations` does for every row.
+pub fn gravitational_acceleration<T: Position + Mass>(
+  particles: &[T],
+  i: usize,
+  influences: &[bool],
+  g: f64,
+  tiled_width: Option<f64>,
+  rng: &mut StdRng,
+) -> DVector<f64> {
+  // Displacement towards every influencing particle and its pull per unit of it.
+  let pulls: Vec<(DVector<f64>, f64)> = (0..particles.len())
+    .filter(|&j| influences[j])
+    .map(|j| {
+      let r = match tiled_width {
+        Some(width) => (particles[j].pos() - particles[i].pos()).map(|r| swarm::closest_image(r, width)),
+        None => particles[j].pos() - particles[i].pos(),
+      };
+      let pull = match i != j {
+        true => g * particles[j].mass() / (r.norm() + f64::EPSILON),
+        false => 0.,
+      };
+      (r, pull)
+    })
+    .collect();
+
+  DVector::from_fn(particles[i].pos().len(), |d, _| {
+    pulls.iter().map(|(r, pull)| pull * rng.gen_range(0.0..1.0) * r[d]).sum()
+  })
+}
+
+// Accelerations of every particle at once, one row per particle. Row i is the
+// `gravitational_acceleration` of particle i drawn from `rngs[i]`, up to rounding,
+// so the result does not depend on `parallel`, which splits the rows between threads.
+pub fn gravitational_accelerations(
+  swarm: &Swarm,
+  masses: &[f64],
+  influences: &[bool],
+  g: f64,
+  tiled_width: Option<f64>,
+  rngs: &mut [StdRng],
+  parallel: bool,
+) -> DMatrix<f64> {
+  let n = swarm.pos.nrows();
+  let sources: Vec<usize> = (0..n).filter(|&j| influences[j]).collect();
+  let chunk_size = match parallel {
+    true => n.div_ceil(rayon::current_num_threads()).max(1),
+    false => n.max(1),
+  };
+  let blocks: Vec<DMatrix<f64>> = rngs
+    .par_chunks_mut(chunk_size)
+    .enumerate()
+    .map(|(c, rngs)| {
+      let rows: Vec<usize> = (c * chunk_size..(c * chunk_size + rngs.len())).collect();
+      acceleration_block(swarm, &rows, &sources, masses, g, tiled_width, rngs)
+    })
+    .collect();
+
+  let mut a = DMatrix::zeros(n, swarm.pos.ncols());
+  for (c, block) in blocks.iter().enumerate() {
+    a.rows_mut(c * chunk_size, block.nrows()).copy_from(block);
+  }
+  a
+}
+
+// Accelerations of the `rows` particles. The pulls are weighted per pair and
+// component, so the sum over the influencing particles is a product per dimension.
+fn acceleration_block(
+  swarm: &Swarm,
+  rows: &[usize],
+  sources: &[usize],
+  masses: &[f64],
+  g: f64,
+  tiled_width: Option<f64>,
+  rngs: &mut [StdRng],
+) -> DMatrix<f64> {
+  let distances = swarm.distances(rows, sources, tiled_width);
+
+  // Pull of each influencing particle on each particle per unit of displacement.
+  let pull = DMatrix::from_fn(rows.len(), sources.len(), |r, s| match rows[r] != sources[s] {
+    true => g * masses[sources[s]] / (distances[(r, s)] + f64::EPSILON),
+    false => 0.,
+  });
+
+  let mut a = DMatrix::zeros(rows.len(), swarm.pos.ncols());
+  for (d, x) in swarm.pos.column_iter().enumerate() {
+    let weighted = DMatrix::from_fn(rows.len(), sources.len(), |r, s| {
+      pull[(r, s)] * rngs[r].gen_range(0.0..1.0)
+    });
+    let x_rows = DVector::from_fn(rows.len(), |r, _| x[rows[r]]);
+    let x_sources = DVector::from_fn(sources.len(), |s, _| x[sources[s]]);
+    // Sum of w_ij * (x_j - x_i) over the influencing j.
+    let mut a_d = &weighted * x_sources - weighted.column_sum().component_mul(&x_rows);
+    if let Some(width) = tiled_width {
+      for (s, &j) in sources.iter().enumerate() {
+        for (r, &i) in rows.iter().enumerate() {
+          let diff = x[j] - x[i];
+          a_d[r] += weighted[(r, s)] * (swarm::closest_image(diff, width) - diff);
+        }
+      }
+    }
+    a.set_column(d, &a_d);
+  }
+  a
+}
+
 #[derive(Clone)]
 pub struct Gsa<T> {
   name: String,
@@ -37,10 +164,16 @@ pub struct Gsa<T> {
   manual_k: Option<f64>,
   data: Vec<(f64, f64, Option<Vec<T>>)>,
   additional_data: Vec<Vec<Vec<(String, f64)>>>,
+  recorder: Recorder,
   out_directory: PathBuf,
   g0: f64,
   alpha: f64,
   save: bool,
+  // Splits the force computation between threads. The fitness is evaluated as the
+  // problem says, since the CEC functions share global state.
+  parallel: bool,
+  // Seeds the random numbers of every particle at every iteration.
+  seed: u64,
   normalizer: Normalizer,
   tiled: bool,
 }
@@ -109,9 +242,29 @@ impl<T: Particle + Position + Velocity + Mass + Clone> Optimizer<T> for Gsa<T> {
           std::process::exit(1);
         }
       }),
-      false => {
-        None
-      }
+      false => None,
+    };
+
+    let parallel = match parameters.contains_key("parallel") {
+      true => match parameters["parallel"] {
+        ParamValue::Bool(val) => val,
+        _ => {
+          eprintln!("Error: parameter 'parallel' should be of type Param::Bool.");
+          std::process::exit(1);
+        }
+      },
+      false => false,
+    };
+
+    let seed = match parameters.contains_key("seed") {
+      true => match parameters["seed"] {
+        ParamValue::Int(val) => val as u64,
+        _ => {
+          eprintln!("Error: parameter 'seed' should be of type Param::Int.");
+          std::process::exit(1);
+        }
+      },
+      false => rand::thread_rng().gen(),
     };
 
     assert!(parameters.contains_key("behavior"), "Key 'behavior' not found.");
@@ -142,10 +295,13 @@ impl<T: Particle + Position + Velocity + Mass + Clone> Optimizer<T> for Gsa<T> {
       manual_k,
       data: Vec::new(),
       additional_data: Vec::new(),
+      recorder: Recorder::default(),
       out_directory,
       g0,
       alpha,
       save,
+      parallel,
+      seed,
       normalizer,
       tiled,
     };
@@ -156,163 +312,100 @@ impl<T: Particle + Position + Velocity + Mass + Clone> Optimizer<T> for Gsa<T> {
 
   fn init(&mut self, number_of_particles: usize, behavior: Behavior) {
     let problem = &mut self.problem();
+    problem.sampler().reset(number_of_particles);
+    behavior.edge.setup(problem);
     let mut particles: Vec<T> = Vec::new();
     for _ in 0..number_of_particles {
       particles.push(T::new(problem, behavior));
     }
 
-    let mut global_best_pos = None;
-    for particle in particles.clone() {
-      if global_best_pos.is_none() || problem.f(particle.pos()) < problem.f(global_best_pos.as_ref().unwrap()) {
-        global_best_pos = Some(particle.pos().clone());
-      }
-    }
-
     self.particles = particles;
-    self.set_global_best_pos(global_best_pos.unwrap());
+    self.update_best_and_worst();
 
     utils::create_directory(self.out_directory().to_path_buf(), true, false);
   }
 
   fn calculate_vel(&mut self, i: usize) -> DVector<f64> {
     assert!(i < self.particles().len());
-    let mut a: DVector<f64> = DVector::from_element(self.problem().dim(), 0.);
-    let mut rng = rand::thread_rng();
-
-    for j in 0..self.particles().len() {
-      if i == j || !self.influences[j] {
-        continue;
-      }
-
-      let i = self.particles()[i].clone();
-      let j = self.particles()[j].clone();
-
-      let r = match self.tiled {
-        true => {
-          let width = self.problem().domain().1 - self.problem().domain().0;
-          let mut closest_j = j.pos().clone();
-
-          for (idx, x) in closest_j.iter_mut().enumerate() {
-            if (*x - width - i.pos()[idx]).abs() < (*x - i.pos()[idx]).abs() {
-              *x -= width;
-            } else if (*x + width - i.pos()[idx]).abs() < (*x - i.pos()[idx]).abs() {
-              *x += width;
-            }
-          }
-          closest_j - i.pos().clone()
-        }
-        false => j.pos() - i.pos(),
-      };
-
-      let mut a_delta = self.g * j.mass() / (r.norm() + f64::EPSILON) * r;
-
-      for e in a_delta.iter_mut() {
-        let rand: f64 = rng.gen_range(0.0..1.0);
-        *e *= rand;
-      }
-
-      a += a_delta;
-    }
+    let width = self.problem().domain().1 - self.problem().domain().0;
+    let mut rng = utils::particle_rng(self.seed, self.problem.iteration(), i);
+    let a = gravitational_acceleration(
+      self.particles(),
+      i,
+      &self.influences,
+      self.g,
+      self.tiled.then_some(width),
+      &mut rng,
+    );
 
     let rand: f64 = rng.gen_range(0.0..1.0);
     rand * self.particles()[i].vel() + a
   }
 
-  fn run(&mut self, iterations: usize) {
-    for iter in 0..iterations {
-      // if iter < 500 {
-      self.g = self.g0 * (-self.alpha * iter as f64 / iterations as f64).exp();
-      //   self.g = self.g0 * (-self.alpha * 0.5).exp();
-      // }
-
-      let mut fitness = Vec::new();
-      for idx in 0..self.particles().len() {
-        let pos = self.particles()[idx].pos().clone();
-        fitness.push(self.problem().f(&pos));
-      }
-
-      let m = match self.normalizer {
-        Normalizer::MinMax => utils::original_gsa_mass(fitness),
-        Normalizer::ZScore => utils::z_mass(fitness),
-        Normalizer::Robust => utils::robust_mass(fitness),
-        Normalizer::Rank => utils::rank_mass(fitness),
-        Normalizer::Sigmoid2 => utils::sigmoid2_mass(fitness),
-        Normalizer::Sigmoid4 => utils::sigmoid4_mass(fitness),
-      };
-      for (mass, particle) in m.iter().zip(self.particles_mut().iter_mut()) {
-        particle.set_mass(*mass);
-      }
-
-      // Only use k largest values. Make others not influence.
-      let mut m_sorted = m.clone();
-      m_sorted.sort_by(|a, b| a.partial_cmp(b).expect("Could not compare NaN."));
-
-      let particle_count = self.particles().len();
+  fn calculate_vels(&mut self, iter: usize, iterations: usize) -> Vec<DVector<f64>> {
+    // if iter < 500 {
+    self.g = self.g0 * (-self.alpha * iter as f64 / iterations as f64).exp();
+    //   self.g = self.g0 * (-self.alpha * 0.5).exp();
+    // }
+    let g = self.g;
+    self.recorder().set_metric("g", g);
 
-      let k = match self.manual_k {
-        Some(val) => (particle_count as f64 * val / 100.0) as usize,
-        None => {
-          let mut k = (-(particle_count as f64) / (iterations as f64) * iter as f64 + particle_count as f64) as usize;
-          k = std::cmp::max(k, 1);
-          k = std::cmp::min(k, particle_count);
-          k
-        }
-      };
+    let fitness = self.evaluate();
 
-      for (i, m_i) in m.iter().enumerate().take(particle_count) {
-        let loc = match m_sorted.binary_search_by(|v| v.partial_cmp(m_i).expect("Couldn't compare values")) {
-          Ok(val) => val,
-          Err(val) => val,
-        };
-        self.influences[i] = (particle_count - loc) <= k;
-        // self.influences[i] = true;
-      }
+    let m = self.normalizer.mass(fitness);
+    for (mass, particle) in m.iter().zip(self.particles_mut().iter_mut()) {
+      particle.set_mass(*mass);
+    }
 
-      // Calculate vels.
-      let mut vels = Vec::new();
-      for idx in 0..self.particles().len() {
-        vels.push(self.calculate_vel(idx));
+    // Only use k largest values. Make others not influence.
+    let mut m_sorted = m.clone();
+    m_sorted.sort_by(|a, b| a.partial_cmp(b).expect("Could not compare NaN."));
+
+    // The swarm may have been resized since the last iteration.
+    let particle_count = self.particles().len();
+    self.influences.resize(particle_count, false);
+
+    let k = match self.manual_k {
+      Some(val) => (particle_count as f64 * val / 100.0) as usize,
+      None => {
+        let mut k = (-(particle_count as f64) / (iterations as f64) * iter as f64 + particle_count as f64) as usize;
+        k = std::cmp::max(k, 1);
+        k = std::cmp::min(k, particle_count);
+        k
       }
+    };
+    self.recorder().set_metric("k", k as f64);
 
-      // Clear memory.
-      self.problem().clear_memo();
-
-      // Update the position, best and worst.
-      let mut new_global_best_pos = None;
-      let mut new_global_worst_pos = None;
-      // for (i, m_i) in 0.. {
-      for (i, vel) in vels.iter().enumerate().take(self.particles().len()) {
-        let mut temp_problem = mem::take(&mut self.problem);
-        let particle = &mut self.particles_mut()[i];
-        particle.update_vel(vel.clone(), &mut temp_problem);
-        particle.move_pos(&mut temp_problem);
-        let pos = particle.pos().clone();
-
-        // Update best.
-        if new_global_best_pos.is_none()
-          || (temp_problem.f(&pos) < temp_problem.f(&new_global_best_pos.clone().unwrap()))
-        {
-          new_global_best_pos = Some(pos.clone());
-        }
-
-        // Update worst.
-        if new_global_worst_pos.is_none()
-          || (temp_problem.f(&pos) > temp_problem.f(&new_global_worst_pos.clone().unwrap()))
-        {
-          new_global_worst_pos = Some(pos.clone());
-        }
-
-        self.problem = temp_problem;
-      }
-      self.update_global_best_pos(new_global_best_pos.unwrap());
-      self.update_global_worst_pos(new_global_worst_pos.unwrap());
-
-      // Save the data for current iteration.
-      let gbest = self.problem.f(&self.global_best_pos());
-      let gworst = self.problem.f(&self.global_worst_pos());
-      let particles = self.particles.clone();
-      self.add_data(self.save, gbest, gworst, particles);
+    for (i, m_i) in m.iter().enumerate().take(particle_count) {
+      let loc = match m_sorted.binary_search_by(|v| v.partial_cmp(m_i).expect("Couldn't compare values")) {
+        Ok(val) => val,
+        Err(val) => val,
+      };
+      self.influences[i] = (particle_count - loc) <= k;
+      // self.influences[i] = true;
     }
+
+    // Calculate vels.
+    let width = self.problem().domain().1 - self.problem().domain().0;
+    let iteration = self.problem.iteration();
+    let mut rngs: Vec<StdRng> = (0..particle_count).map(|i| utils::particle_rng(self.seed, iteration, i)).collect();
+    let swarm = Swarm::from_particles(self.particles());
+    let a = gravitational_accelerations(
+      &swarm,
+      &m,
+      &self.influences,
+      self.g,
+      self.tiled.then_some(width),
+      &mut rngs,
+      self.parallel,
+    );
+    let mut vels = swarm.vel;
+    scale_rows(
+      &mut vels,
+      &DVector::from_fn(particle_count, |i, _| rngs[i].gen_range(0.0..1.0)),
+    );
+    vels += a;
+    rows(&vels)
   }
 }
 
@@ -324,6 +417,10 @@ impl<T> Particles<T> for Gsa<T> {
   fn particles_mut(&mut self) -> &mut Vec<T> {
     &mut self.particles
   }
+
+  fn particles_and_problem_mut(&mut self) -> (&mut Vec<T>, &mut Problem) {
+    (&mut self.particles, &mut self.problem)
+  }
 }
 
 impl<T> GlobalBestPos for Gsa<T> {
@@ -369,10 +466,26 @@ impl<T: Clone> Data<T> for Gsa<T> {
     &self.data
   }
 
+  fn saving(&self) -> bool {
+    self.save
+  }
+
   fn additional_data(&self) -> &Vec<Vec<Vec<(String, f64)>>> {
     &self.additional_data
   }
 
+  fn data_mut(&mut self) -> &mut Vec<(f64, f64, Option<Vec<T>>)> {
+    &mut self.data
+  }
+
+  fn additional_data_mut(&mut self) -> &mut Vec<Vec<Vec<(String, f64)>>> {
+    &mut self.additional_data
+  }
+
+  fn recorder(&mut self) -> &mut Recorder {
+    &mut self.recorder
+  }
+
   fn add_data_impl(&mut self, datum: (f64, f64, Option<Vec<T>>)) {
     self.data.push(datum);
   }
@@ -387,61 +500,118 @@ impl<T: Position + Velocity + Mass + Clone> DataExporter<T> for Gsa<T> {
     &self.out_directory
   }
 
-  fn save_data(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-    // Serialize it to a JSON string
-    let mut vec_data = Vec::new();
-    for t in 0..self.data().len() {
-      let mut iter_data = Vec::new();
-      let datum = self.data()[t].2.clone().unwrap();
-      for particle_datum in &datum {
-        let pos = particle_datum.pos().clone();
-        iter_data.push(json!({
-          "fitness": self.problem().f_no_memo(&pos),
-          "vel": particle_datum.vel().as_slice(),
-          "pos": particle_datum.pos().as_slice(),
-          "mass": particle_datum.mass(),
-        }));
-      }
-      vec_data.push(json!({
-        "global_best_fitness": self.data()[t].0,
-        "global_worst_fitness": self.data()[t].1,
-        "particles": iter_data
-      }));
-    }
-
-    let serialized = serde_json::to_string(&json!(vec_data))?;
-
-    fs::write(self.out_directory().join("data.json"), serialized)?;
-    Ok(())
+  fn particle_json(&mut self, particle: &T) -> Value {
+    json!({
+      "fitness": self.export_fitness(particle),
+      "vel": particle.vel().as_slice(),
+      "pos": particle.pos().as_slice(),
+      "mass": particle.mass(),
+    })
   }
+}
 
-  fn generate_data_json(&mut self) -> Result<String, Box<dyn std::error::Error>> {
-    let mut vec_data = Vec::new();
-
-    for t in 0..self.data().len() {
-      let mut iter_data = Vec::new();
-      // unwrap is for demonstration; handle errors as needed
-      let datum = self.data()[t].2.clone().unwrap();
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::optimizers::testing;
+  use crate::particles::gsa::GsaParticle;
+  use crate::particles::initializer::Init;
+  use strum::IntoEnumIterator;
+
+  fn gsa(normalizer: Normalizer) -> Gsa<GsaParticle> {
+    let parameters = utils::param_hashmap_generator(vec![
+      ("particle_count", ParamValue::Int(20)),
+      ("g0", utils::g0_from_normalizer(normalizer)),
+      ("alpha", ParamValue::Float(5.)),
+      ("normalizer", ParamValue::Normalizer(normalizer)),
+      ("tiled", ParamValue::Bool(false)),
+      testing::seed(),
+      (
+        "behavior",
+        ParamValue::Behavior(Behavior {
+          edge: Edge::Infinity,
+          vmax: None,
+          pos_init: Init::Uniform,
+          vel_init: None,
+        }),
+      ),
+    ]);
+    Gsa::new(
+      "gsa".to_owned(),
+      problems::sphere_100(5),
+      parameters,
+      PathBuf::new(),
+      false,
+    )
+  }
 
-      for particle_datum in &datum {
-        let pos = particle_datum.pos().clone();
-        iter_data.push(json!({
-            "fitness": self.problem().f_no_memo(&pos),
-            "vel": particle_datum.vel().as_slice(),
-            "pos": particle_datum.pos().as_slice(),
-            "mass": particle_datum.mass(),
-        }));
+  #[test]
+  fn infeasible_particles_have_no_mass() {
+    for normalizer in Normalizer::iter() {
+      let mut optimizer = gsa(normalizer);
+      for idx in 0..5 {
+        optimizer.particles_mut()[idx].set_pos(DVector::from_element(5, 1000. * (idx + 1) as f64));
       }
+      optimizer.calculate_vels(0, 60);
+      let masses: Vec<f64> = optimizer.particles().iter().map(|p| p.mass()).collect();
+      assert!(masses.iter().all(|m| m.is_finite()), "{:?}: {:?}", normalizer, masses);
+      assert!(masses[..5].iter().all(|&m| m == 0.), "{:?}: {:?}", normalizer, masses);
+
+      optimizer.run(60);
+      // The whole swarm may leave the domain, but no fitness may turn into NaN.
+      assert!(
+        optimizer.data().iter().all(|datum| !datum.0.is_nan()),
+        "{:?}",
+        normalizer
+      );
+      // Weigh the final positions: the particles outside the domain must not pull.
+      optimizer.calculate_vels(60, 60);
+      for particle in optimizer.particles() {
+        let mass = particle.mass();
+        match optimizer.problem.contains(particle.pos()) {
+          true => assert!(mass.is_finite(), "{:?}: {}", normalizer, mass),
+          false => assert_eq!(mass, 0., "{:?}", normalizer),
+        }
+      }
+    }
+  }
 
-      vec_data.push(json!({
-          "global_best_fitness": self.data()[t].0,
-          "global_worst_fitness": self.data()[t].1,
-          "particles": iter_data
-      }));
+  #[test]
+  fn accelerations_match_the_per_particle_sum() {
+    let mut optimizer = gsa(Normalizer::MinMax);
+    optimizer.run(5);
+    let particles = optimizer.particles();
+    let n = particles.len();
+    let masses: Vec<f64> = particles.iter().map(|p| p.mass()).collect();
+    let influences: Vec<bool> = (0..n).map(|i| i % 3 != 1).collect();
+    let swarm = Swarm::from_particles(particles);
+    for tiled_width in [None, Some(200.)] {
+      let mut rngs: Vec<StdRng> = (0..n).map(|i| utils::particle_rng(3, 0, i)).collect();
+      let a = gravitational_accelerations(&swarm, &masses, &influences, 2., tiled_width, &mut rngs, false);
+      for (i, rng) in rngs.iter_mut().enumerate() {
+        let mut own = utils::particle_rng(3, 0, i);
+        let a_i = gravitational_acceleration(particles, i, &influences, 2., tiled_width, &mut own);
+        let error = (a.row(i).transpose() - &a_i).norm();
+        assert!(error <= 1e-9 * a_i.norm(), "{}: {} from {}", i, error, a_i.norm());
+        // Both drew the same numbers.
+        assert_eq!(rng.gen::<u64>(), own.gen::<u64>());
+      }
     }
+  }
 
-    // Convert to a JSON string
-    let serialized = serde_json::to_string(&json!(vec_data))?;
-    Ok(serialized)
+  #[test]
+  fn parallel_runs_match_serial_ones() {
+    // The same swarm, whose forces are computed on 4 threads or on one.
+    let mut serial = gsa(Normalizer::Rank);
+    let mut parallel = serial.clone();
+    parallel.parallel = true;
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(4).build().unwrap();
+    pool.install(|| {
+      serial.run(20);
+      parallel.run(20);
+    });
+    let positions =
+      |optimizer: &Gsa<GsaParticle>| optimizer.particles().iter().map(|p| p.pos().clone()).collect::<Vec<_>>();
+    assert_eq!(positions(&parallel), positions(&serial));
   }
 }
@@ -0,0 +1,311 @@
+use crate::optimizers::traits::{
+  Data, DataExporter, GlobalBestPos, Name, OptimizationProblem, Optimizer, ParamValue, Particles,
+};
+use crate::particles::boundary::Boundary;
+use crate::particles::traits::{Behavior, Particle, Position, Velocity};
+use crate::problems;
+use crate::rand::Rng;
+use crate::recording::Recorder;
+use crate::utils;
+use nalgebra::DVector;
+use problems::Problem;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::{collections::HashMap, path::PathBuf};
+
+#[derive(Clone)]
+pub struct Gwo<T> {
+  name: String,
+  problem: Problem,
+  particles: Vec<T>,
+  global_best_pos: Option<DVector<f64>>,
+  global_worst_pos: Option<DVector<f64>>,
+  // The alpha, beta and delta wolves found so far, best first.
+  leaders: Vec<(f64, DVector<f64>)>,
+  a: f64,
+  // Random numbers of the run, seeded with the `seed` parameter.
+  rng: StdRng,
+  data: Vec<(f64, f64, Option<Vec<T>>)>,
+  additional_data: Vec<Vec<Vec<(String, f64)>>>,
+  recorder: Recorder,
+  out_directory: PathBuf,
+  save: bool,
+}
+
+impl<T: Particle + Position + Velocity + Clone> Optimizer<T> for Gwo<T> {
+  fn new(
+    name: String,
+    problem: Problem,
+    parameters: HashMap<String, ParamValue>,
+    out_directory: PathBuf,
+    save: bool,
+  ) -> Gwo<T> {
+    assert!(
+      parameters.contains_key("particle_count"),
+      "Key 'particle_count' not found."
+    );
+    let number_of_particles = match parameters["particle_count"] {
+      ParamValue::Int(val) => val as usize,
+      _ => {
+        eprintln!("Error: parameter 'particle_count' should be of type Param::Int.");
+        std::process::exit(1);
+      }
+    };
+    assert!(number_of_particles >= 3, "GWO needs at least 3 wolves.");
+
+    let seed = match parameters.contains_key("seed") {
+      true => match parameters["seed"] {
+        ParamValue::Int(val) => val as u64,
+        _ => {
+          eprintln!("Error: parameter 'seed' should be of type Param::Int.");
+          std::process::exit(1);
+        }
+      },
+      false => rand::thread_rng().gen(),
+    };
+
+    assert!(parameters.contains_key("behavior"), "Key 'behavior' not found.");
+    let behavior = match parameters["behavior"] {
+      ParamValue::Behavior(val) => val,
+      _ => {
+        eprintln!("Error: parameter 'behavior' should be of type Param::Behavior.");
+        std::process::exit(1);
+      }
+    };
+
+    let mut gwo = Gwo {
+      name,
+      problem,
+      particles: Vec::new(),
+      global_best_pos: None,
+      global_worst_pos: None,
+      leaders: Vec::new(),
+      a: 2.,
+      rng: StdRng::seed_from_u64(seed),
+      data: Vec::new(),
+      additional_data: Vec::new(),
+      recorder: Recorder::default(),
+      out_directory,
+      save,
+    };
+
+    gwo.init(number_of_particles, behavior);
+    gwo
+  }
+
+  fn init(&mut self, number_of_particles: usize, behavior: Behavior) {
+    let problem = &mut self.problem();
+    problem.sampler().reset(number_of_particles);
+    behavior.edge.setup(problem);
+    let mut particles: Vec<T> = Vec::new();
+    for _ in 0..number_of_particles {
+      particles.push(T::new(problem, behavior));
+    }
+
+    self.particles = particles;
+    self.update_best_and_worst();
+    self.update_leaders();
+
+    utils::create_directory(self.out_directory().to_path_buf(), true, false);
+  }
+
+  fn calculate_vel(&mut self, i: usize) -> DVector<f64> {
+    assert!(i < self.particles().len());
+    let x = self.particles()[i].pos().clone();
+
+    let mut new_pos = DVector::from_element(x.len(), 0.);
+    for (_, leader) in &self.leaders {
+      for d in 0..x.len() {
+        let big_a = 2. * self.a * self.rng.gen_range(0.0..1.0) - self.a;
+        let big_c = 2. * self.rng.gen_range(0.0..1.0);
+        let distance = (big_c * leader[d] - x[d]).abs();
+        new_pos[d] += leader[d] - big_a * distance;
+      }
+    }
+    new_pos /= self.leaders.len() as f64;
+
+    new_pos - x
+  }
+
+  fn calculate_vels(&mut self, iter: usize, iterations: usize) -> Vec<DVector<f64>> {
+    // `a` decreases linearly from 2 to 0.
+    self.a = 2. - 2. * iter as f64 / iterations as f64;
+    (0..self.particles().len()).map(|idx| self.calculate_vel(idx)).collect()
+  }
+
+  fn post_step(&mut self, _iter: usize, _iterations: usize, _gbest: f64, _gworst: f64) {
+    self.update_leaders();
+  }
+}
+
+impl<T: Particle + Position + Velocity + Clone> Gwo<T> {
+  fn update_leaders(&mut self) {
+    let fitness = self.evaluate();
+    let mut candidates = self.leaders.clone();
+    for (particle, f) in self.particles().iter().zip(fitness) {
+      candidates.push((f, particle.pos().clone()));
+    }
+
+    candidates.sort_by(|a, b| a.0.partial_cmp(&b.0).expect("Could not compare NaN."));
+    candidates.dedup_by(|a, b| a.1 == b.1);
+    candidates.truncate(3);
+    self.leaders = candidates;
+  }
+}
+
+impl<T> Particles<T> for Gwo<T> {
+  fn particles(&self) -> &Vec<T> {
+    &self.particles
+  }
+
+  fn particles_mut(&mut self) -> &mut Vec<T> {
+    &mut self.particles
+  }
+
+  fn particles_and_problem_mut(&mut self) -> (&mut Vec<T>, &mut Problem) {
+    (&mut self.particles, &mut self.problem)
+  }
+}
+
+impl<T> GlobalBestPos for Gwo<T> {
+  fn global_best_pos(&self) -> DVector<f64> {
+    self.global_best_pos.clone().unwrap()
+  }
+
+  fn global_worst_pos(&self) -> DVector<f64> {
+    self.global_worst_pos.clone().unwrap()
+  }
+
+  fn option_global_best_pos(&self) -> &Option<DVector<f64>> {
+    &self.global_best_pos
+  }
+
+  fn option_global_worst_pos(&self) -> &Option<DVector<f64>> {
+    &self.global_worst_pos
+  }
+
+  fn set_global_best_pos(&mut self, pos: DVector<f64>) {
+    self.global_best_pos = Some(pos);
+  }
+
+  fn set_global_worst_pos(&mut self, pos: DVector<f64>) {
+    self.global_worst_pos = Some(pos);
+  }
+}
+
+impl<T> OptimizationProblem for Gwo<T> {
+  fn problem(&mut self) -> &mut Problem {
+    &mut self.problem
+  }
+}
+
+impl<T> Name for Gwo<T> {
+  fn name(&self) -> &String {
+    &self.name
+  }
+}
+
+impl<T: Clone> Data<T> for Gwo<T> {
+  fn data(&self) -> &Vec<(f64, f64, Option<Vec<T>>)> {
+    &self.data
+  }
+
+  fn saving(&self) -> bool {
+    self.save
+  }
+
+  fn additional_data(&self) -> &Vec<Vec<Vec<(String, f64)>>> {
+    &self.additional_data
+  }
+
+  fn data_mut(&mut self) -> &mut Vec<(f64, f64, Option<Vec<T>>)> {
+    &mut self.data
+  }
+
+  fn additional_data_mut(&mut self) -> &mut Vec<Vec<Vec<(String, f64)>>> {
+    &mut self.additional_data
+  }
+
+  fn recorder(&mut self) -> &mut Recorder {
+    &mut self.recorder
+  }
+
+  fn add_data_impl(&mut self, datum: (f64, f64, Option<Vec<T>>)) {
+    self.data.push(datum);
+  }
+
+  fn add_additional_data_impl(&mut self, datum: Vec<Vec<(String, f64)>>) {
+    self.additional_data.push(datum);
+  }
+}
+
+impl<T: Position + Velocity + Clone> DataExporter<T> for Gwo<T> {
+  fn out_directory(&self) -> &PathBuf {
+    &self.out_directory
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::optimizers::testing;
+  use crate::particles::basic::BasicParticle;
+
+  fn gwo() -> Gwo<BasicParticle> {
+    let parameters = utils::param_hashmap_generator(vec![
+      ("particle_count", ParamValue::Int(20)),
+      testing::behavior(),
+      testing::seed(),
+    ]);
+    Gwo::new(
+      "gwo".to_owned(),
+      problems::sphere_100(5),
+      parameters,
+      PathBuf::new(),
+      false,
+    )
+  }
+
+  // Particle k sits k away from the optimum in every dimension.
+  fn line_up(optimizer: &mut Gwo<BasicParticle>, offset: f64) {
+    for (k, particle) in optimizer.particles_mut().iter_mut().enumerate() {
+      particle.set_pos(DVector::from_element(5, -50. + offset + k as f64));
+    }
+  }
+
+  #[test]
+  fn improves_on_sphere() {
+    testing::assert_improves(&mut gwo(), 100, 0.05);
+  }
+
+  #[test]
+  fn leads_with_the_three_best_positions_seen() {
+    let mut optimizer = gwo();
+    line_up(&mut optimizer, 0.);
+    let duplicate = optimizer.particles()[0].pos().clone();
+    optimizer.particles_mut()[3].set_pos(duplicate);
+    optimizer.update_leaders();
+    let leaders =
+      |optimizer: &Gwo<BasicParticle>| optimizer.leaders.iter().map(|(_, pos)| pos[0]).collect::<Vec<f64>>();
+    assert_eq!(leaders(&optimizer), vec![-50., -49., -48.]);
+    assert_eq!(optimizer.leaders[1].0, 5.);
+
+    // The leaders are kept when the whole pack moves to worse positions.
+    line_up(&mut optimizer, 10.);
+    optimizer.update_leaders();
+    assert_eq!(leaders(&optimizer), vec![-50., -49., -48.]);
+  }
+
+  #[test]
+  fn moves_to_the_mean_of_the_leaders_once_a_reaches_zero() {
+    let mut optimizer = gwo();
+    line_up(&mut optimizer, 0.);
+    optimizer.update_leaders();
+    optimizer.a = 0.;
+    for i in 0..optimizer.particles().len() {
+      let x = optimizer.particles()[i].pos().clone();
+      let new_pos = x + optimizer.calculate_vel(i);
+      assert!((new_pos - DVector::from_element(5, -49.)).norm() < 1e-12);
+    }
+  }
+}
@@ -1,16 +1,17 @@
 use crate::optimizers::traits::{
   Data, DataExporter, GlobalBestPos, Name, OptimizationProblem, Optimizer, ParamValue, Particles,
 };
+use crate::particles::boundary::Boundary;
 use crate::particles::traits::{Behavior, Mass, Particle, Position, Velocity};
 use crate::problems;
 use crate::rand::Rng;
+use crate::recording::Recorder;
 use crate::utils;
 use nalgebra::DVector;
 use problems::Problem;
-use serde_json::json;
+use rayon::prelude::*;
+use serde_json::{json, Value};
 use std::collections::HashMap;
-use std::fs;
-use std::mem;
 use std::path::PathBuf;
 
 type VelsAndAdditionalData = (Vec<DVector<f64>>, Vec<Vec<(String, f64)>>);
@@ -23,12 +24,19 @@ pub struct Mgsa<T> {
   global_best_pos: Option<DVector<f64>>,
   global_worst_pos: Option<DVector<f64>>,
   g: f64,
+  initial_spread: Option<f64>,
   data: Vec<(f64, f64, Option<Vec<T>>)>,
   additional_data: Vec<Vec<Vec<(String, f64)>>>,
+  recorder: Recorder,
   out_directory: PathBuf,
   g0: f64,
   elite: bool,
   save: bool,
+  // Splits the force computation between threads. The fitness is evaluated as the
+  // problem says, since the CEC functions share global state.
+  parallel: bool,
+  // Seeds the random numbers of every particle at every iteration.
+  seed: u64,
 }
 
 impl<T: Particle + Position + Velocity + Mass + Clone> Optimizer<T> for Mgsa<T> {
@@ -60,6 +68,28 @@ impl<T: Particle + Position + Velocity + Mass + Clone> Optimizer<T> for Mgsa<T>
       }
     };
 
+    let parallel = match parameters.contains_key("parallel") {
+      true => match parameters["parallel"] {
+        ParamValue::Bool(val) => val,
+        _ => {
+          eprintln!("Error: parameter 'parallel' should be of type Param::Bool.");
+          std::process::exit(1);
+        }
+      },
+      false => false,
+    };
+
+    let seed = match parameters.contains_key("seed") {
+      true => match parameters["seed"] {
+        ParamValue::Int(val) => val as u64,
+        _ => {
+          eprintln!("Error: parameter 'seed' should be of type Param::Int.");
+          std::process::exit(1);
+        }
+      },
+      false => rand::thread_rng().gen(),
+    };
+
     assert!(parameters.contains_key("behavior"), "Key 'behavior' not found.");
     let behavior = match parameters["behavior"] {
       ParamValue::Behavior(val) => val,
@@ -85,12 +115,16 @@ impl<T: Particle + Position + Velocity + Mass + Clone> Optimizer<T> for Mgsa<T>
       global_best_pos: None,
       global_worst_pos: None,
       g: g0,
+      initial_spread: None,
       data: Vec::new(),
       additional_data: Vec::new(),
+      recorder: Recorder::default(),
       out_directory,
       g0,
       elite,
       save,
+      parallel,
+      seed,
     };
 
     mgsa.init(number_of_particles, behavior);
@@ -99,25 +133,15 @@ impl<T: Particle + Position + Velocity + Mass + Clone> Optimizer<T> for Mgsa<T>
 
   fn init(&mut self, number_of_particles: usize, behavior: Behavior) {
     let problem = &mut self.problem();
+    problem.sampler().reset(number_of_particles);
+    behavior.edge.setup(problem);
     let mut particles: Vec<T> = Vec::new();
     for _ in 0..number_of_particles {
       particles.push(T::new(problem, behavior));
     }
 
-    let mut global_best_pos = None;
-    let mut global_worst_pos = None;
-    for particle in particles.clone() {
-      if global_best_pos.is_none() || problem.f(particle.pos()) < problem.f(global_best_pos.as_ref().unwrap()) {
-        global_best_pos = Some(particle.pos().clone());
-      }
-      if global_worst_pos.is_none() || problem.f(particle.pos()) > problem.f(global_worst_pos.as_ref().unwrap()) {
-        global_worst_pos = Some(particle.pos().clone());
-      }
-    }
-
     self.particles = particles;
-    self.set_global_best_pos(global_best_pos.unwrap());
-    self.set_global_worst_pos(global_worst_pos.unwrap());
+    self.update_best_and_worst();
 
     utils::create_directory(self.out_directory().to_path_buf(), true, false);
   }
@@ -126,129 +150,88 @@ impl<T: Particle + Position + Velocity + Mass + Clone> Optimizer<T> for Mgsa<T>
     panic!("deprecated");
   }
 
-  fn run(&mut self, iterations: usize) {
+  fn calculate_vels(&mut self, iter: usize, iterations: usize) -> Vec<DVector<f64>> {
     let n = self.particles().len();
 
-    let mut initial_spread = None;
-    let mut x_record: Vec<Vec<DVector<f64>>>;
-    let mut f_record: Vec<Vec<f64>>;
-
-    for iter in 0..iterations {
-      // println!("--{}--", iter);
-      x_record = Vec::new();
-      f_record = Vec::new();
-
-      let mut distances = Vec::new();
-      for i in 0..n {
-        for j in 0..n {
-          if i == j {
-            continue;
-          }
-          let distance = (self.particles()[i].pos() - self.particles()[j].pos()).norm();
-          distances.push(distance);
-        }
-      }
-      let use_avg = true;
-      let spread = match use_avg {
-        true => distances.iter().sum::<f64>() / distances.len() as f64,
-        false => utils::calculate_std(&distances),
-      };
-      if initial_spread.is_none() {
-        initial_spread = Some(spread);
-      }
-      // println!("spread: {}", spread / initial_spread.unwrap());
-      let spread_ratio = spread / initial_spread.unwrap();
-
-      // let ratio = (-self.alpha * iter as f64 / iterations as f64).exp();
-      // let iteration_ratio = 1. - iter as f64 / iterations as f64;
-
-      self.g = self.g0 * (-1. * spread_ratio).exp();
-
-      let mut fitness = Vec::new();
-      for idx in 0..n {
-        let pos = self.particles()[idx].pos().clone();
-        fitness.push(self.problem().f(&pos));
-      }
-
-      f_record.push(fitness);
-      let m_record = utils::original_gsa_mass_with_record(f_record.clone(), 100);
-
-      // let m = match self.normalizer {
-      //   Normalizer::MinMax => utils::original_gsa_mass(fitness),
-      //   Normalizer::ZScore => utils::z_mass(fitness),
-      //   Normalizer::Robust => utils::robust_mass(fitness),
-      //   Normalizer::Rank => utils::rank_mass(fitness),
-      //   Normalizer::Sigmoid2 => utils::sigmoid2_mass(fitness),
-      //   Normalizer::Sigmoid4 => utils::sigmoid4_mass(fitness),
-      // };
-      // let m: Vec<f64> = fitness.iter().map(|&f| 1. / f).collect();
-
-      for (mass, particle) in m_record[m_record.len() - 1].iter().zip(self.particles_mut().iter_mut()) {
-        particle.set_mass(*mass);
-      }
-
-      // Calculate vels.
-      let mut x: Vec<DVector<f64>> = Vec::new();
-      let mut v = Vec::new();
-      for idx in 0..n {
-        x.push(self.particles()[idx].pos().clone());
-        v.push(self.particles()[idx].vel().clone());
-      }
-      x_record.push(x);
-
-      let (vels, additional_data) = calculate_vels(
-        x_record.clone(),
-        m_record.clone(),
-        &v,
-        self.g,
-        iter as f64 / iterations as f64,
-        self.elite,
-      );
-
-      // Clear memory.
-      self.problem().clear_memo();
-
-      // Update the position, best and worst.
-      let mut new_global_best_pos = None;
-      let mut new_global_worst_pos = None;
-      // for (i, m_i) in 0.. {
-      for (i, vel) in vels.iter().enumerate().take(n) {
-        let mut temp_problem = mem::take(&mut self.problem);
-        let particle = &mut self.particles_mut()[i];
-        particle.update_vel(vel.clone(), &mut temp_problem);
-        particle.move_pos(&mut temp_problem);
-        let pos = particle.pos().clone();
-
-        // Update best.
-        if new_global_best_pos.is_none()
-          || (temp_problem.f(&pos) < temp_problem.f(&new_global_best_pos.clone().unwrap()))
-        {
-          new_global_best_pos = Some(pos.clone());
-        }
-
-        // Update worst.
-        if new_global_worst_pos.is_none()
-          || (temp_problem.f(&pos) > temp_problem.f(&new_global_worst_pos.clone().unwrap()))
-        {
-          new_global_worst_pos = Some(pos.clone());
-        }
-        self.problem = temp_problem;
-      }
-      self.update_global_best_pos(new_global_best_pos.clone().unwrap());
-      self.update_global_worst_pos(new_global_worst_pos.unwrap());
-
-      // Save the data for current iteration.
-      let gbest = self.problem.f(&self.global_best_pos());
-      let gworst = self.problem.f(&self.global_worst_pos());
-      println!("gbest: {}", gbest);
+    // println!("--{}--", iter);
+    let mut x_record: Vec<Vec<DVector<f64>>> = Vec::new();
+    let mut f_record: Vec<Vec<f64>> = Vec::new();
+
+    let positions: Vec<DVector<f64>> = self.particles().iter().map(|p| p.pos().clone()).collect();
+    // Measured before the move, and recorded so that `record_metrics` does not measure
+    // the swarm a second time.
+    let (diameter, spread) = utils::pairwise_distances(&positions);
+    self.recorder().set_metric("diameter", diameter);
+    self.recorder().set_metric("spread", spread);
+    if self.initial_spread.is_none() {
+      self.initial_spread = Some(spread);
+    }
+    let spread_ratio = spread / self.initial_spread.unwrap();
+    self.recorder().set_metric("spread_ratio", spread_ratio);
+
+    // let ratio = (-self.alpha * iter as f64 / iterations as f64).exp();
+    // let iteration_ratio = 1. - iter as f64 / iterations as f64;
+
+    self.g = self.g0 * (-spread_ratio).exp();
+    // With `elite`, only the heaviest masses attract, as in `calculate_vels`.
+    let k = match self.elite {
+      true => std::cmp::min(
+        std::cmp::max((n as f64 * (1. - iter as f64 / iterations as f64)) as usize, 1),
+        n,
+      ),
+      false => n,
+    };
+    let g = self.g;
+    self.recorder().set_metric("g", g);
+    self.recorder().set_metric("k", k as f64);
+
+    let fitness = self.evaluate();
+
+    f_record.push(fitness);
+    let m_record = utils::original_gsa_mass_with_record(f_record.clone(), 100);
+
+    // let m = match self.normalizer {
+    //   Normalizer::MinMax => utils::original_gsa_mass(fitness),
+    //   Normalizer::ZScore => utils::z_mass(fitness),
+    //   Normalizer::Robust => utils::robust_mass(fitness),
+    //   Normalizer::Rank => utils::rank_mass(fitness),
+    //   Normalizer::Sigmoid2 => utils::sigmoid2_mass(fitness),
+    //   Normalizer::Sigmoid4 => utils::sigmoid4_mass(fitness),
+    // };
+    // let m: Vec<f64> = fitness.iter().map(|&f| 1. / f).collect();
+
+    for (mass, particle) in m_record[m_record.len() - 1].iter().zip(self.particles_mut().iter_mut()) {
+      particle.set_mass(*mass);
+    }
 
-      let particles = self.particles.clone();
-      self.add_data(self.save, gbest, gworst, particles);
-      self.add_additional_data(self.save, additional_data);
+    // Calculate vels.
+    let mut x: Vec<DVector<f64>> = Vec::new();
+    let mut v = Vec::new();
+    for idx in 0..n {
+      x.push(self.particles()[idx].pos().clone());
+      v.push(self.particles()[idx].vel().clone());
     }
+    x_record.push(x);
+
+    let (vels, additional_data) = calculate_vels(
+      x_record.clone(),
+      m_record.clone(),
+      &v,
+      self.g,
+      iter as f64 / iterations as f64,
+      self.elite,
+      self.seed,
+      iter,
+      self.parallel,
+    );
+    self.add_additional_data(self.save, additional_data);
+    vels
   }
 }
 
+// With `parallel`, the particles are split between threads. Each draws from its own
+// generator, so the result is the same either way.
+#[allow(clippy::too_many_arguments)]
 fn calculate_vels(
   x: Vec<Vec<DVector<f64>>>,
   f: Vec<Vec<f64>>,
@@ -256,28 +239,35 @@ fn calculate_vels(
   large_g: f64,
   progress: f64,
   elite: bool,
+  seed: u64,
+  iter: usize,
+  parallel: bool,
 ) -> VelsAndAdditionalData {
   let t = x.len();
   let n = x[0].len();
   let d = x[0][0].len();
 
   let additional_data = Vec::new();
-  let mut vels = Vec::new();
-  for (k, _v) in v.iter().enumerate().take(n) {
-    let mut a: DVector<f64> = DVector::from_element(d, 0.);
-
-    for l in 0..t {
-      let p = std::cmp::min(std::cmp::max((n as f64 * (1. - progress)) as usize, 1), n);
+  let p = std::cmp::min(std::cmp::max((n as f64 * (1. - progress)) as usize, 1), n);
+  let influences: Vec<Vec<bool>> = (0..t)
+    .map(|l| {
       let mut sorted_f = f[l].clone();
       sorted_f.sort_by(|a, b| b.partial_cmp(a).unwrap_or(std::cmp::Ordering::Equal));
       let p_largest: Vec<f64> = sorted_f.iter().take(p).copied().collect();
-      let influences: Vec<bool> = match elite {
+      match elite {
         true => f[l].iter().map(|x| p_largest.contains(x)).collect(),
         false => vec![true; n],
-      };
+      }
+    })
+    .collect();
+
+  let vel = |k: usize| {
+    let mut rng = utils::particle_rng(seed, iter, k);
+    let mut a: DVector<f64> = DVector::from_element(d, 0.);
 
-      for (i, _influences) in influences.iter().enumerate().take(n) {
-        if (l == t - 1 && i == k) || !influences[i] {
+    for l in 0..t {
+      for i in 0..n {
+        if (l == t - 1 && i == k) || !influences[l][i] {
           continue;
         }
         let r: DVector<f64> = &x[l][i] - &x[t - 1][k];
@@ -288,7 +278,6 @@ fn calculate_vels(
         // let repellent= f_avg / (r.norm() + std::f64::EPSILON) * r.clone();
         let mut a_delta = gravity;
 
-        let mut rng = rand::thread_rng();
         for e in a_delta.iter_mut() {
           let rand: f64 = rng.gen_range(0.0..1.0);
           *e *= rand;
@@ -297,14 +286,16 @@ fn calculate_vels(
         a += large_g * a_delta;
       }
     }
-    let mut rng = rand::thread_rng();
-    let rand: f64 = rng.gen_range(0.0..1.0);
-    vels.push(rand * v[k].clone() + a);
-
     // vels.push(perturb_vector(&a, progress));
     // raw_vels.push(a);
     // vels.push(perturb_vector(&a, spread));
-  }
+    let rand: f64 = rng.gen_range(0.0..1.0);
+    rand * v[k].clone() + a
+  };
+  let vels: Vec<DVector<f64>> = match parallel {
+    true => (0..n).into_par_iter().map(vel).collect(),
+    false => (0..n).map(vel).collect(),
+  };
   // println!(
   //   "vel: {}",
   //   vels.iter().map(|x| x.norm()).sum::<f64>() / vels.len() as f64
@@ -624,6 +615,10 @@ impl<T> Particles<T> for Mgsa<T> {
   fn particles_mut(&mut self) -> &mut Vec<T> {
     &mut self.particles
   }
+
+  fn particles_and_problem_mut(&mut self) -> (&mut Vec<T>, &mut Problem) {
+    (&mut self.particles, &mut self.problem)
+  }
 }
 
 impl<T> GlobalBestPos for Mgsa<T> {
@@ -669,10 +664,26 @@ impl<T: Clone> Data<T> for Mgsa<T> {
     &self.data
   }
 
+  fn saving(&self) -> bool {
+    self.save
+  }
+
   fn additional_data(&self) -> &Vec<Vec<Vec<(String, f64)>>> {
     &self.additional_data
   }
 
+  fn data_mut(&mut self) -> &mut Vec<(f64, f64, Option<Vec<T>>)> {
+    &mut self.data
+  }
+
+  fn additional_data_mut(&mut self) -> &mut Vec<Vec<Vec<(String, f64)>>> {
+    &mut self.additional_data
+  }
+
+  fn recorder(&mut self) -> &mut Recorder {
+    &mut self.recorder
+  }
+
   fn add_data_impl(&mut self, datum: (f64, f64, Option<Vec<T>>)) {
     self.data.push(datum);
   }
@@ -687,32 +698,13 @@ impl<T: Position + Velocity + Mass + Clone> DataExporter<T> for Mgsa<T> {
     &self.out_directory
   }
 
-  fn save_data(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-    // Serialize it to a JSON string
-    let mut vec_data = Vec::new();
-    for t in 0..self.data().len() {
-      let mut iter_data = Vec::new();
-      let datum = self.data()[t].2.clone().unwrap();
-      for particle_datum in &datum {
-        let pos = particle_datum.pos().clone();
-        iter_data.push(json!({
-          "fitness": self.problem().f_no_memo(&pos),
-          "vel": particle_datum.vel().as_slice(),
-          "pos": particle_datum.pos().as_slice(),
-          "mass": particle_datum.mass(),
-        }));
-      }
-      vec_data.push(json!({
-        "global_best_fitness": self.data()[t].0,
-        "global_worst_fitness": self.data()[t].1,
-        "particles": iter_data
-      }));
-    }
-
-    let serialized = serde_json::to_string(&json!(vec_data))?;
-
-    fs::write(self.out_directory().join("data.json"), serialized)?;
-    Ok(())
+  fn particle_json(&mut self, particle: &T) -> Value {
+    json!({
+      "fitness": self.export_fitness(particle),
+      "vel": particle.vel().as_slice(),
+      "pos": particle.pos().as_slice(),
+      "mass": particle.mass(),
+    })
   }
 }
 
@@ -739,3 +731,38 @@ fn calculate_standard_deviation(data: Vec<f64>) -> f64 {
   let variance = calculate_variance(data.clone(), mean);
   variance.sqrt()
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::optimizers::testing;
+  use crate::particles::mgsa::MgsaParticle;
+
+  #[test]
+  fn parallel_runs_match_serial_ones() {
+    let parameters = utils::param_hashmap_generator(vec![
+      ("particle_count", ParamValue::Int(20)),
+      ("g0", ParamValue::Float(100.)),
+      ("elite", ParamValue::Bool(true)),
+      testing::behavior(),
+      testing::seed(),
+    ]);
+    let mut serial: Mgsa<MgsaParticle> = Mgsa::new(
+      "mgsa".to_owned(),
+      problems::sphere_100(5),
+      parameters,
+      PathBuf::new(),
+      false,
+    );
+    let mut parallel = serial.clone();
+    parallel.parallel = true;
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(4).build().unwrap();
+    pool.install(|| {
+      serial.run(20);
+      parallel.run(20);
+    });
+    let positions =
+      |optimizer: &Mgsa<MgsaParticle>| optimizer.particles().iter().map(|p| p.pos().clone()).collect::<Vec<_>>();
+    assert_eq!(positions(&parallel), positions(&serial));
+  }
+}
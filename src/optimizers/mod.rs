@@ -1,5 +1,19 @@
+pub mod abc;
+pub mod bat;
+pub mod cmaes;
+pub mod diversity;
+pub mod firefly;
 pub mod gsa;
+pub mod gwo;
 pub mod mgsa;
+pub mod observer;
 pub mod pso;
-pub mod traits;
+pub mod psogsa;
+pub mod resize;
+pub mod restart;
 pub mod rgsa;
+pub mod spectrum;
+#[cfg(test)]
+pub mod testing;
+pub mod traits;
+pub mod woa;
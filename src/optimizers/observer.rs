@@ -0,0 +1,159 @@
+use indicatif::ProgressBar;
+use nalgebra::DVector;
+use serde_json::{Map, Value};
+use std::sync::Arc;
+
+// Read-only view of a run at the end of an iteration.
+#[allow(dead_code)]
+pub struct Snapshot<'a, T> {
+  pub iter: usize,
+  pub particles: &'a [T],
+  pub gbest: f64,
+  pub gworst: f64,
+  // Lowest `gbest` of this and all previous iterations.
+  pub best_so_far: f64,
+  pub evaluations: usize,
+}
+
+// Hooks called by `Optimizer::run_observed`. They do nothing by default, so an
+// observer only implements the events it needs.
+pub trait Observer<T> {
+  fn on_init(&mut self, _particles: &[T]) {}
+  fn on_iteration_end(&mut self, _snapshot: &Snapshot<T>) {}
+  // The best fitness so far improved during the iteration.
+  fn on_improvement(&mut self, _iter: usize, _fitness: f64, _pos: &DVector<f64>) {}
+  fn on_restart(&mut self, _iter: usize, _particles: &[T]) {}
+  fn on_finish(&mut self, _particles: &[T]) {}
+  // Entries added to summary.json once the run is over.
+  fn summary(&self) -> Map<String, Value> {
+    Map::new()
+  }
+}
+
+// Creates the observers of every attempt, given its index.
+pub type ObserverFactory<T> = Arc<dyn Fn(usize) -> Vec<Box<dyn Observer<T>>> + Sync + Send>;
+
+// Prints the best fitness every `every` iterations.
+#[allow(dead_code)]
+pub struct Logger {
+  pub every: usize,
+}
+
+impl<T> Observer<T> for Logger {
+  fn on_iteration_end(&mut self, snapshot: &Snapshot<T>) {
+    if snapshot.iter.is_multiple_of(self.every.max(1)) {
+      println!(
+        "iter: {}, gbest: {}, best so far: {}, evaluations: {}",
+        snapshot.iter, snapshot.gbest, snapshot.best_so_far, snapshot.evaluations
+      );
+    }
+  }
+
+  fn on_restart(&mut self, iter: usize, particles: &[T]) {
+    println!("iter: {}, restarted with {} particles", iter, particles.len());
+  }
+}
+
+// Advances a progress bar once per iteration.
+#[allow(dead_code)]
+pub struct Progress {
+  pub bar: ProgressBar,
+}
+
+impl<T> Observer<T> for Progress {
+  fn on_iteration_end(&mut self, _snapshot: &Snapshot<T>) {
+    self.bar.inc(1);
+  }
+
+  fn on_finish(&mut self, _particles: &[T]) {
+    self.bar.finish();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::optimizers::pso::Pso;
+  use crate::optimizers::restart::Restarter;
+  use crate::optimizers::testing;
+  use crate::optimizers::traits::{Data, Optimizer, ParamValue};
+  use crate::particles::pso::PsoParticle;
+  use crate::problems;
+  use crate::utils;
+  use std::cell::RefCell;
+  use std::path::PathBuf;
+  use std::rc::Rc;
+
+  #[derive(Default)]
+  struct Events {
+    inits: usize,
+    iterations: usize,
+    improvements: Vec<usize>,
+    restarts: Vec<usize>,
+    finishes: usize,
+  }
+
+  struct Counter(Rc<RefCell<Events>>);
+
+  impl<T> Observer<T> for Counter {
+    fn on_init(&mut self, _particles: &[T]) {
+      self.0.borrow_mut().inits += 1;
+    }
+
+    fn on_iteration_end(&mut self, _snapshot: &Snapshot<T>) {
+      self.0.borrow_mut().iterations += 1;
+    }
+
+    fn on_improvement(&mut self, iter: usize, _fitness: f64, _pos: &DVector<f64>) {
+      self.0.borrow_mut().improvements.push(iter);
+    }
+
+    fn on_restart(&mut self, iter: usize, _particles: &[T]) {
+      self.0.borrow_mut().restarts.push(iter);
+    }
+
+    fn on_finish(&mut self, _particles: &[T]) {
+      self.0.borrow_mut().finishes += 1;
+    }
+  }
+
+  #[test]
+  fn run_observed_fires_every_event() {
+    // Without any stagnation allowed, the swarm restarts after every iteration but the last.
+    let parameters = utils::param_hashmap_generator(vec![
+      ("particle_count", ParamValue::Int(10)),
+      ("w", ParamValue::Float(0.7)),
+      ("phi_p", ParamValue::Float(1.5)),
+      ("phi_g", ParamValue::Float(1.5)),
+      ("restart_stagnation", ParamValue::Int(0)),
+      testing::behavior(),
+    ]);
+    let mut optimizer: Restarter<PsoParticle, Pso<PsoParticle>> = Restarter::new(
+      "restart".to_owned(),
+      problems::sphere_100(5),
+      parameters,
+      PathBuf::new(),
+      false,
+    );
+    let events = Rc::new(RefCell::new(Events::default()));
+    let mut observers: Vec<Box<dyn Observer<PsoParticle>>> = vec![Box::new(Counter(events.clone()))];
+    optimizer.run_observed(10, &mut observers);
+
+    // Iterations where the best fitness of the iteration beats every earlier one.
+    let mut best = f64::INFINITY;
+    let mut improvements = Vec::new();
+    for (iter, datum) in optimizer.data().iter().enumerate() {
+      if datum.0 < best {
+        best = datum.0;
+        improvements.push(iter);
+      }
+    }
+
+    let events = events.borrow();
+    assert_eq!((events.inits, events.iterations, events.finishes), (1, 10, 1));
+    assert_eq!(events.restarts, (0..9).collect::<Vec<usize>>());
+    assert_eq!(optimizer.restart_count(), 9);
+    assert_eq!(events.improvements, improvements);
+    assert_eq!(events.improvements.first(), Some(&0));
+  }
+}
@@ -1,13 +1,16 @@
 use crate::optimizers::traits::{
   Data, DataExporter, GlobalBestPos, Name, OptimizationProblem, Optimizer, ParamValue, Particles,
 };
-use crate::particles::traits::{Behavior, BestPosition, Particle, Position, Velocity};
+use crate::particles::boundary::Boundary;
+use crate::particles::swarm::{rows, scale_rows, stack, Swarm};
+use crate::particles::traits::{Behavior, BestPosition, Particle, Position, Velocity, Vmax};
 use crate::problems;
 use crate::rand::Rng;
+use crate::recording::Recorder;
 use crate::utils;
 use nalgebra::DVector;
 use problems::Problem;
-use std::{collections::HashMap, mem, path::PathBuf};
+use std::{collections::HashMap, path::PathBuf};
 
 #[derive(Clone)]
 pub struct Pso<T> {
@@ -18,6 +21,7 @@ pub struct Pso<T> {
   global_worst_pos: Option<DVector<f64>>,
   data: Vec<(f64, f64, Option<Vec<T>>)>,
   additional_data: Vec<Vec<Vec<(String, f64)>>>,
+  recorder: Recorder,
   out_directory: PathBuf,
   w: f64,
   phi_p: f64,
@@ -73,13 +77,18 @@ impl<T: Particle + Position + Velocity + BestPosition + Clone> Optimizer<T> for
     };
 
     assert!(parameters.contains_key("behavior"), "Key 'behavior' not found.");
-    let behavior = match parameters["behavior"] {
+    let mut behavior = match parameters["behavior"] {
       ParamValue::Behavior(val) => val,
       _ => {
         eprintln!("Error: parameter 'behavior' should be of type Param::Behavior.");
         std::process::exit(1);
       }
     };
+    // Velocities are clamped to the domain width per component unless the behavior says
+    // otherwise, as PSO always did before the clamp became part of the behavior.
+    if behavior.vmax.is_none() {
+      behavior.vmax = Some(Vmax::Component(1.));
+    }
 
     let mut pso = Pso {
       name,
@@ -89,6 +98,7 @@ impl<T: Particle + Position + Velocity + BestPosition + Clone> Optimizer<T> for
       global_worst_pos: None,
       data: Vec::new(),
       additional_data: Vec::new(),
+      recorder: Recorder::default(),
       out_directory,
       w,
       phi_p,
@@ -102,19 +112,19 @@ impl<T: Particle + Position + Velocity + BestPosition + Clone> Optimizer<T> for
 
   fn init(&mut self, number_of_particles: usize, behavior: Behavior) {
     let problem = &mut self.problem();
+    problem.sampler().reset(number_of_particles);
+    behavior.edge.setup(problem);
     let mut particles: Vec<T> = Vec::new();
     for _ in 0..number_of_particles {
       particles.push(T::new(problem, behavior));
     }
-    let mut global_best_pos = None;
-    for particle in particles.clone() {
-      if global_best_pos.is_none() || problem.f(particle.pos()) < problem.f(global_best_pos.as_ref().unwrap()) {
-        global_best_pos = Some(particle.pos().clone());
-      }
-    }
-
     self.particles = particles;
-    self.set_global_best_pos(global_best_pos.unwrap());
+    self.update_best_and_worst();
+
+    // Record the fitness of the initial personal bests.
+    for particle in self.particles.iter_mut() {
+      particle.update_best_pos(&mut self.problem);
+    }
 
     utils::create_directory(self.out_directory().to_path_buf(), true, false);
   }
@@ -126,61 +136,40 @@ impl<T: Particle + Position + Velocity + BestPosition + Clone> Optimizer<T> for
     let r_p: f64 = rng.gen_range(0.0..1.0);
     let r_g: f64 = rng.gen_range(0.0..1.0);
 
-    let mut new_vel = self.w * self.particles()[idx].vel()
+    self.w * self.particles()[idx].vel()
       + self.phi_p * r_p * (self.particles()[idx].best_pos() - self.particles()[idx].pos())
-      + self.phi_g * r_g * (self.global_best_pos() - self.particles()[idx].pos());
-    for e in new_vel.iter_mut() {
-      if *e > self.problem().domain().1 - self.problem().domain().0 {
-        *e = self.problem().domain().1 - self.problem().domain().0;
-      } else if *e < self.problem().domain().0 - self.problem().domain().1 {
-        *e = self.problem().domain().0 - self.problem().domain().1;
-      }
-    }
+      + self.phi_g * r_g * (self.global_best_pos() - self.particles()[idx].pos())
+  }
 
-    new_vel
+  // Same update as `calculate_vel`, for every particle at once.
+  fn calculate_vels(&mut self, _iter: usize, _iterations: usize) -> Vec<DVector<f64>> {
+    let n = self.particles().len();
+    let swarm = Swarm::from_particles(self.particles());
+    let best_positions: Vec<DVector<f64>> = self.particles().iter().map(|p| p.best_pos()).collect();
+    let mut cognitive = stack(&best_positions) - &swarm.pos;
+    let mut social = stack(&vec![self.global_best_pos(); n]) - &swarm.pos;
+
+    let mut rng = rand::thread_rng();
+    scale_rows(&mut cognitive, &DVector::from_fn(n, |_, _| rng.gen_range(0.0..1.0)));
+    scale_rows(&mut social, &DVector::from_fn(n, |_, _| rng.gen_range(0.0..1.0)));
+    rows(&(self.w * swarm.vel + self.phi_p * cognitive + self.phi_g * social))
   }
 
-  fn run(&mut self, iterations: usize) {
-    for _ in 0..iterations {
-      self.problem().clear_memo();
-
-      let mut new_global_best_pos = None;
-      let mut new_global_worst_pos = None;
-      for idx in 0..self.particles().len() {
-        let vel = self.calculate_vel(idx);
-        let mut temp_problem = mem::take(&mut self.problem);
-        let particle = &mut self.particles_mut()[idx];
-        particle.update_vel(vel, &mut temp_problem);
-        particle.move_pos(&mut temp_problem);
-        particle.update_best_pos(&mut temp_problem);
-
-        let pos = particle.pos().clone();
-
-        // Update best.
-        if new_global_best_pos.is_none()
-          || (temp_problem.f(&pos) < temp_problem.f(&new_global_best_pos.clone().unwrap()))
-        {
-          new_global_best_pos = Some(pos.clone());
-        }
-
-        // Update worst.
-        if new_global_worst_pos.is_none()
-          || (temp_problem.f(&pos) > temp_problem.f(&new_global_worst_pos.clone().unwrap()))
-        {
-          new_global_worst_pos = Some(pos.clone());
-        }
-
-        self.problem = temp_problem;
-      }
-      self.update_global_best_pos(new_global_best_pos.unwrap());
-      self.update_global_worst_pos(new_global_worst_pos.unwrap());
-
-      // Save the data for current iteration.
-      let gbest = self.problem.f(&self.global_best_pos());
-      let gworst = self.problem.f(&self.global_worst_pos());
-      let particles = self.particles.clone();
-      self.add_data(self.save, gbest, gworst, particles);
+  fn post_step(&mut self, _iter: usize, _iterations: usize, _gbest: f64, _gworst: f64) {
+    for particle in self.particles.iter_mut() {
+      particle.update_best_pos(&mut self.problem);
+    }
+  }
+
+  // The personal best starts over too, or it would pull the particle straight back.
+  fn reinitialize(&mut self, idx: usize, pos: Option<DVector<f64>>) {
+    let particle = &mut self.particles[idx];
+    match pos {
+      Some(pos) => particle.set_pos(pos),
+      None => Position::init(particle, &mut self.problem),
     }
+    BestPosition::init(particle);
+    Velocity::init(particle, &mut self.problem);
   }
 }
 
@@ -192,6 +181,10 @@ impl<T> Particles<T> for Pso<T> {
   fn particles_mut(&mut self) -> &mut Vec<T> {
     &mut self.particles
   }
+
+  fn particles_and_problem_mut(&mut self) -> (&mut Vec<T>, &mut Problem) {
+    (&mut self.particles, &mut self.problem)
+  }
 }
 
 impl<T> GlobalBestPos for Pso<T> {
@@ -218,7 +211,6 @@ impl<T> GlobalBestPos for Pso<T> {
   fn set_global_worst_pos(&mut self, pos: DVector<f64>) {
     self.global_worst_pos = Some(pos);
   }
-
 }
 
 impl<T> OptimizationProblem for Pso<T> {
@@ -238,10 +230,26 @@ impl<T: Clone> Data<T> for Pso<T> {
     &self.data
   }
 
+  fn saving(&self) -> bool {
+    self.save
+  }
+
   fn additional_data(&self) -> &Vec<Vec<Vec<(String, f64)>>> {
     &self.additional_data
   }
 
+  fn data_mut(&mut self) -> &mut Vec<(f64, f64, Option<Vec<T>>)> {
+    &mut self.data
+  }
+
+  fn additional_data_mut(&mut self) -> &mut Vec<Vec<Vec<(String, f64)>>> {
+    &mut self.additional_data
+  }
+
+  fn recorder(&mut self) -> &mut Recorder {
+    &mut self.recorder
+  }
+
   fn add_data_impl(&mut self, datum: (f64, f64, Option<Vec<T>>)) {
     self.data.push(datum);
   }
@@ -256,3 +264,64 @@ impl<T: Position + Velocity + Clone> DataExporter<T> for Pso<T> {
     &self.out_directory
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::particles::initializer::Init;
+  use crate::particles::pso::PsoParticle;
+  use crate::particles::traits::Edge;
+
+  fn pso(w: f64, vmax: Option<Vmax>) -> Pso<PsoParticle> {
+    let parameters = utils::param_hashmap_generator(vec![
+      ("particle_count", ParamValue::Int(20)),
+      ("w", ParamValue::Float(w)),
+      ("phi_p", ParamValue::Float(2.)),
+      ("phi_g", ParamValue::Float(2.)),
+      (
+        "behavior",
+        ParamValue::Behavior(Behavior {
+          edge: Edge::Pass,
+          vmax,
+          pos_init: Init::Uniform,
+          vel_init: None,
+        }),
+      ),
+    ]);
+    Pso::new(
+      "pso".to_owned(),
+      problems::sphere_100(5),
+      parameters,
+      PathBuf::new(),
+      false,
+    )
+  }
+
+  #[test]
+  fn clamps_velocities_by_default() {
+    let width = 200.;
+    let mut optimizer = pso(2., None);
+    optimizer.run(20);
+    assert!(optimizer.particles().iter().all(|p| p.vel().iter().all(|v| v.abs() <= width)));
+
+    // Without a limit, an inertia weight of 2 makes the swarm diverge.
+    let mut optimizer = pso(2., Some(Vmax::None));
+    optimizer.run(20);
+    assert!(optimizer.particles().iter().any(|p| p.vel().iter().any(|v| v.abs() > width)));
+  }
+
+  #[test]
+  fn reinitialized_particles_forget_their_personal_best() {
+    let mut optimizer = pso(0.7, None);
+    optimizer.run(10);
+    let pos = DVector::from_element(5, 50.);
+    optimizer.reinitialize(3, Some(pos.clone()));
+    let particle = &optimizer.particles()[3];
+    assert_eq!(particle.best_pos(), pos);
+    assert_eq!(particle.best_fitness(), None);
+
+    // The shifted sphere is 5 · 100² there, worse than any old best, and is still taken.
+    optimizer.post_step(10, 20, 0., 0.);
+    assert_eq!(optimizer.particles()[3].best_fitness(), Some(5. * 100f64.powi(2)));
+  }
+}
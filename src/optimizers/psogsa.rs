@@ -0,0 +1,404 @@
+use crate::optimizers::gsa::{gravitational_acceleration, gravitational_accelerations, Normalizer};
+use crate::optimizers::traits::{
+  Data, DataExporter, GlobalBestPos, Name, OptimizationProblem, Optimizer, ParamValue, Particles,
+};
+use crate::particles::boundary::Boundary;
+use crate::particles::swarm::{rows, scale_rows, stack, Swarm};
+use crate::particles::traits::{Behavior, BestPosition, Mass, Particle, Position, Velocity};
+use crate::problems;
+use crate::rand::Rng;
+use crate::recording::Recorder;
+use crate::utils;
+use nalgebra::DVector;
+use problems::Problem;
+use rand::rngs::StdRng;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+#[derive(Clone)]
+pub struct Psogsa<T> {
+  name: String,
+  problem: Problem,
+  particles: Vec<T>,
+  global_best_pos: Option<DVector<f64>>,
+  global_worst_pos: Option<DVector<f64>>,
+  g: f64,
+  data: Vec<(f64, f64, Option<Vec<T>>)>,
+  additional_data: Vec<Vec<Vec<(String, f64)>>>,
+  recorder: Recorder,
+  out_directory: PathBuf,
+  g0: f64,
+  alpha: f64,
+  w: f64,
+  c1: f64,
+  c2: f64,
+  save: bool,
+  // Seeds the random numbers of every particle at every iteration.
+  seed: u64,
+  normalizer: Normalizer,
+}
+
+impl<T: Particle + Position + Velocity + BestPosition + Mass + Clone> Optimizer<T> for Psogsa<T> {
+  fn new(
+    name: String,
+    problem: Problem,
+    parameters: HashMap<String, ParamValue>,
+    out_directory: PathBuf,
+    save: bool,
+  ) -> Psogsa<T> {
+    assert!(
+      parameters.contains_key("particle_count"),
+      "Key 'particle_count' not found."
+    );
+    let number_of_particles = match parameters["particle_count"] {
+      ParamValue::Int(val) => val as usize,
+      _ => {
+        eprintln!("Error: parameter 'particle_count' should be of type Param::Int.");
+        std::process::exit(1);
+      }
+    };
+
+    assert!(parameters.contains_key("g0"), "Key 'g0' not found.");
+    let g0 = match parameters["g0"] {
+      ParamValue::Float(val) => val,
+      _ => {
+        eprintln!("Error: parameter 'g0' should be of type Param::Float.");
+        std::process::exit(1);
+      }
+    };
+
+    assert!(parameters.contains_key("alpha"), "Key 'alpha' not found.");
+    let alpha = match parameters["alpha"] {
+      ParamValue::Float(val) => val,
+      _ => {
+        eprintln!("Error: parameter 'alpha' should be of type Param::Float.");
+        std::process::exit(1);
+      }
+    };
+
+    assert!(parameters.contains_key("w"), "Key 'w' not found.");
+    let w = match parameters["w"] {
+      ParamValue::Float(val) => val,
+      _ => {
+        eprintln!("Error: parameter 'w' should be of type Param::Float.");
+        std::process::exit(1);
+      }
+    };
+
+    assert!(parameters.contains_key("c1"), "Key 'c1' not found.");
+    let c1 = match parameters["c1"] {
+      ParamValue::Float(val) => val,
+      _ => {
+        eprintln!("Error: parameter 'c1' should be of type Param::Float.");
+        std::process::exit(1);
+      }
+    };
+
+    assert!(parameters.contains_key("c2"), "Key 'c2' not found.");
+    let c2 = match parameters["c2"] {
+      ParamValue::Float(val) => val,
+      _ => {
+        eprintln!("Error: parameter 'c2' should be of type Param::Float.");
+        std::process::exit(1);
+      }
+    };
+
+    assert!(parameters.contains_key("normalizer"), "Key 'normalizer' not found.");
+    let normalizer = match parameters["normalizer"] {
+      ParamValue::Normalizer(val) => val,
+      _ => {
+        eprintln!("Error: parameter 'normalizer' should be of type Param::Normalizer.");
+        std::process::exit(1);
+      }
+    };
+
+    let seed = match parameters.contains_key("seed") {
+      true => match parameters["seed"] {
+        ParamValue::Int(val) => val as u64,
+        _ => {
+          eprintln!("Error: parameter 'seed' should be of type Param::Int.");
+          std::process::exit(1);
+        }
+      },
+      false => rand::thread_rng().gen(),
+    };
+
+    assert!(parameters.contains_key("behavior"), "Key 'behavior' not found.");
+    let behavior = match parameters["behavior"] {
+      ParamValue::Behavior(val) => val,
+      _ => {
+        eprintln!("Error: parameter 'behavior' should be of type Param::Behavior.");
+        std::process::exit(1);
+      }
+    };
+
+    let mut psogsa = Psogsa {
+      name,
+      problem,
+      particles: Vec::new(),
+      global_best_pos: None,
+      global_worst_pos: None,
+      g: g0,
+      data: Vec::new(),
+      additional_data: Vec::new(),
+      recorder: Recorder::default(),
+      out_directory,
+      g0,
+      alpha,
+      w,
+      c1,
+      c2,
+      save,
+      seed,
+      normalizer,
+    };
+
+    psogsa.init(number_of_particles, behavior);
+    psogsa
+  }
+
+  fn init(&mut self, number_of_particles: usize, behavior: Behavior) {
+    let problem = &mut self.problem();
+    problem.sampler().reset(number_of_particles);
+    behavior.edge.setup(problem);
+    let mut particles: Vec<T> = Vec::new();
+    for _ in 0..number_of_particles {
+      particles.push(T::new(problem, behavior));
+    }
+
+    self.particles = particles;
+    self.update_best_and_worst();
+
+    // Record the fitness of the initial personal bests.
+    for particle in self.particles.iter_mut() {
+      particle.update_best_pos(&mut self.problem);
+    }
+
+    utils::create_directory(self.out_directory().to_path_buf(), true, false);
+  }
+
+  fn calculate_vel(&mut self, i: usize) -> DVector<f64> {
+    assert!(i < self.particles().len());
+    let influences = vec![true; self.particles().len()];
+    let mut rng = utils::particle_rng(self.seed, self.problem.iteration(), i);
+    let a = gravitational_acceleration(self.particles(), i, &influences, self.g, None, &mut rng);
+
+    let r1: f64 = rng.gen_range(0.0..1.0);
+    let r2: f64 = rng.gen_range(0.0..1.0);
+    self.w * self.particles()[i].vel()
+      + self.c1 * r1 * a
+      + self.c2 * r2 * (self.best_so_far_pos() - self.particles()[i].pos())
+  }
+
+  fn calculate_vels(&mut self, iter: usize, iterations: usize) -> Vec<DVector<f64>> {
+    self.g = self.g0 * (-self.alpha * iter as f64 / iterations as f64).exp();
+    let (g, n) = (self.g, self.particles().len());
+    self.recorder().set_metric("g", g);
+    // Every mass attracts the others.
+    self.recorder().set_metric("k", n as f64);
+
+    let fitness = self.evaluate();
+
+    let m = self.normalizer.mass(fitness);
+    for (mass, particle) in m.iter().zip(self.particles_mut().iter_mut()) {
+      particle.set_mass(*mass);
+    }
+
+    // Calculate vels.
+    let n = self.particles().len();
+    let swarm = Swarm::from_particles(self.particles());
+    let iteration = self.problem.iteration();
+    let mut rngs: Vec<StdRng> = (0..n).map(|i| utils::particle_rng(self.seed, iteration, i)).collect();
+    let mut a = gravitational_accelerations(&swarm, &m, &vec![true; n], self.g, None, &mut rngs, false);
+    let mut social = stack(&vec![self.best_so_far_pos(); n]) - &swarm.pos;
+    scale_rows(&mut a, &DVector::from_fn(n, |i, _| rngs[i].gen_range(0.0..1.0)));
+    scale_rows(&mut social, &DVector::from_fn(n, |i, _| rngs[i].gen_range(0.0..1.0)));
+    let vels = self.w * swarm.vel + self.c1 * a + self.c2 * social;
+    rows(&vels)
+  }
+
+  // The update does not use the personal bests, but they are kept for the exported particles.
+  fn post_step(&mut self, _iter: usize, _iterations: usize, _gbest: f64, _gworst: f64) {
+    for particle in self.particles.iter_mut() {
+      particle.update_best_pos(&mut self.problem);
+    }
+  }
+
+  fn reinitialize(&mut self, idx: usize, pos: Option<DVector<f64>>) {
+    let particle = &mut self.particles[idx];
+    match pos {
+      Some(pos) => particle.set_pos(pos),
+      None => Position::init(particle, &mut self.problem),
+    }
+    BestPosition::init(particle);
+    Velocity::init(particle, &mut self.problem);
+  }
+}
+
+impl<T> Psogsa<T> {
+  // The social term attracts to the best position found so far.
+  fn best_so_far_pos(&self) -> DVector<f64> {
+    self.recorder.best().map(|(_, pos)| pos.clone()).unwrap()
+  }
+}
+
+impl<T> Particles<T> for Psogsa<T> {
+  fn particles(&self) -> &Vec<T> {
+    &self.particles
+  }
+
+  fn particles_mut(&mut self) -> &mut Vec<T> {
+    &mut self.particles
+  }
+
+  fn particles_and_problem_mut(&mut self) -> (&mut Vec<T>, &mut Problem) {
+    (&mut self.particles, &mut self.problem)
+  }
+}
+
+impl<T> GlobalBestPos for Psogsa<T> {
+  fn global_best_pos(&self) -> DVector<f64> {
+    self.global_best_pos.clone().unwrap()
+  }
+
+  fn option_global_best_pos(&self) -> &Option<DVector<f64>> {
+    &self.global_best_pos
+  }
+
+  fn global_worst_pos(&self) -> DVector<f64> {
+    self.global_worst_pos.clone().unwrap()
+  }
+
+  fn option_global_worst_pos(&self) -> &Option<DVector<f64>> {
+    &self.global_worst_pos
+  }
+
+  fn set_global_best_pos(&mut self, pos: DVector<f64>) {
+    self.global_best_pos = Some(pos);
+  }
+
+  fn set_global_worst_pos(&mut self, pos: DVector<f64>) {
+    self.global_worst_pos = Some(pos);
+  }
+}
+
+impl<T> OptimizationProblem for Psogsa<T> {
+  fn problem(&mut self) -> &mut Problem {
+    &mut self.problem
+  }
+}
+
+impl<T> Name for Psogsa<T> {
+  fn name(&self) -> &String {
+    &self.name
+  }
+}
+
+impl<T: Clone> Data<T> for Psogsa<T> {
+  fn data(&self) -> &Vec<(f64, f64, Option<Vec<T>>)> {
+    &self.data
+  }
+
+  fn saving(&self) -> bool {
+    self.save
+  }
+
+  fn additional_data(&self) -> &Vec<Vec<Vec<(String, f64)>>> {
+    &self.additional_data
+  }
+
+  fn data_mut(&mut self) -> &mut Vec<(f64, f64, Option<Vec<T>>)> {
+    &mut self.data
+  }
+
+  fn additional_data_mut(&mut self) -> &mut Vec<Vec<Vec<(String, f64)>>> {
+    &mut self.additional_data
+  }
+
+  fn recorder(&mut self) -> &mut Recorder {
+    &mut self.recorder
+  }
+
+  fn add_data_impl(&mut self, datum: (f64, f64, Option<Vec<T>>)) {
+    self.data.push(datum);
+  }
+
+  fn add_additional_data_impl(&mut self, datum: Vec<Vec<(String, f64)>>) {
+    self.additional_data.push(datum);
+  }
+}
+
+impl<T: Position + Velocity + Mass + Clone> DataExporter<T> for Psogsa<T> {
+  fn out_directory(&self) -> &PathBuf {
+    &self.out_directory
+  }
+
+  fn particle_json(&mut self, particle: &T) -> Value {
+    json!({
+      "fitness": self.export_fitness(particle),
+      "vel": particle.vel().as_slice(),
+      "pos": particle.pos().as_slice(),
+      "mass": particle.mass(),
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::optimizers::testing;
+  use crate::particles::psogsa::PsogsaParticle;
+
+  fn psogsa() -> Psogsa<PsogsaParticle> {
+    let parameters = utils::param_hashmap_generator(vec![
+      ("particle_count", ParamValue::Int(20)),
+      ("g0", ParamValue::Float(1.)),
+      ("alpha", ParamValue::Float(20.)),
+      ("w", ParamValue::Float(0.5)),
+      ("c1", ParamValue::Float(0.5)),
+      ("c2", ParamValue::Float(1.5)),
+      ("normalizer", ParamValue::Normalizer(Normalizer::MinMax)),
+      testing::behavior(),
+      testing::seed(),
+    ]);
+    Psogsa::new(
+      "psogsa".to_owned(),
+      problems::sphere_100(5),
+      parameters,
+      PathBuf::new(),
+      false,
+    )
+  }
+
+  #[test]
+  fn improves_on_sphere() {
+    testing::assert_improves(&mut psogsa(), 100, 0.05);
+  }
+
+  #[test]
+  fn runs_with_the_same_seed_repeat() {
+    let (mut first, mut second) = (psogsa(), psogsa());
+    first.run(30);
+    second.run(30);
+    let best = |optimizer: &Psogsa<PsogsaParticle>| optimizer.data().iter().map(|datum| datum.0).collect::<Vec<f64>>();
+    assert_eq!(best(&first), best(&second));
+  }
+
+  #[test]
+  fn personal_bests_never_get_worse() {
+    let mut optimizer = psogsa();
+    let mut problem = optimizer.problem.fork();
+    let mut previous: Vec<f64> = optimizer.particles().iter().map(|p| p.best_fitness().unwrap()).collect();
+    for iteration in 0..20 {
+      optimizer.step(iteration, 20);
+      for (particle, before) in optimizer.particles().iter().zip(&previous) {
+        let best = particle.best_fitness().unwrap();
+        assert!(best <= *before);
+        assert!(best <= particle.cached_fitness().unwrap());
+        assert_eq!(problem.f_export(&particle.best_pos()), best);
+      }
+      previous = optimizer.particles().iter().map(|p| p.best_fitness().unwrap()).collect();
+    }
+  }
+}
@@ -0,0 +1,348 @@
+use crate::optimizers::traits::{
+  Data, DataExporter, GlobalBestPos, Name, OptimizationProblem, Optimizer, ParamValue, Particles,
+};
+use crate::particles::traits::{Behavior, Position, Velocity};
+use crate::problems;
+use crate::recording::Recorder;
+use nalgebra::DVector;
+use problems::Problem;
+use serde_json::{json, Map, Value};
+use std::{collections::HashMap, path::PathBuf};
+use strum_macros::EnumIter;
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, EnumIter, Debug, PartialEq)]
+pub enum Population {
+  // Keep the initial particle count.
+  Fixed,
+  // Move linearly from the initial to the final particle count, as in L-SHADE.
+  Linear,
+  // Remove the worst particle on improvement, add a random one otherwise.
+  Adaptive,
+}
+
+// Wraps any optimizer and changes the number of particles between iterations.
+#[derive(Clone)]
+pub struct Resizer<T, U> {
+  name: String,
+  inner: U,
+  population: Population,
+  initial_particle_count: usize,
+  final_particle_count: usize,
+  min_particle_count: usize,
+  max_particle_count: usize,
+  best: Option<f64>,
+  // Number of particles at every iteration.
+  particle_counts: Vec<usize>,
+  _marker: std::marker::PhantomData<T>,
+}
+
+impl<T: Position + Velocity + Clone, U: Optimizer<T>> Optimizer<T> for Resizer<T, U> {
+  fn new(
+    name: String,
+    problem: Problem,
+    parameters: HashMap<String, ParamValue>,
+    out_directory: PathBuf,
+    save: bool,
+  ) -> Resizer<T, U> {
+    assert!(parameters.contains_key("population"), "Key 'population' not found.");
+    let population = match parameters["population"] {
+      ParamValue::Population(val) => val,
+      _ => {
+        eprintln!("Error: parameter 'population' should be of type Param::Population.");
+        std::process::exit(1);
+      }
+    };
+
+    let inner = U::new(name.clone(), problem, parameters.clone(), out_directory, save);
+    let initial_particle_count = inner.particles().len();
+
+    let final_particle_count = match parameters.contains_key("final_particle_count") {
+      true => match parameters["final_particle_count"] {
+        ParamValue::Int(val) => val as usize,
+        _ => {
+          eprintln!("Error: parameter 'final_particle_count' should be of type Param::Int.");
+          std::process::exit(1);
+        }
+      },
+      false => 4,
+    };
+
+    let min_particle_count = match parameters.contains_key("min_particle_count") {
+      true => match parameters["min_particle_count"] {
+        ParamValue::Int(val) => val as usize,
+        _ => {
+          eprintln!("Error: parameter 'min_particle_count' should be of type Param::Int.");
+          std::process::exit(1);
+        }
+      },
+      false => 4,
+    };
+
+    let max_particle_count = match parameters.contains_key("max_particle_count") {
+      true => match parameters["max_particle_count"] {
+        ParamValue::Int(val) => val as usize,
+        _ => {
+          eprintln!("Error: parameter 'max_particle_count' should be of type Param::Int.");
+          std::process::exit(1);
+        }
+      },
+      false => 2 * initial_particle_count,
+    };
+
+    assert!(
+      final_particle_count >= 2 && min_particle_count >= 2,
+      "At least 2 particles are required."
+    );
+    assert!(
+      min_particle_count <= max_particle_count,
+      "'min_particle_count' should not exceed 'max_particle_count'."
+    );
+
+    Resizer {
+      name,
+      inner,
+      population,
+      initial_particle_count,
+      final_particle_count,
+      min_particle_count,
+      max_particle_count,
+      best: None,
+      particle_counts: Vec::new(),
+      _marker: std::marker::PhantomData,
+    }
+  }
+
+  fn init(&mut self, number_of_particles: usize, behavior: Behavior) {
+    self.inner.init(number_of_particles, behavior);
+  }
+
+  fn calculate_vel(&mut self, i: usize) -> DVector<f64> {
+    self.inner.calculate_vel(i)
+  }
+
+  fn step(&mut self, iter: usize, iterations: usize) {
+    self.inner.step(iter, iterations);
+    self.particle_counts.push(self.particles().len());
+
+    let gbest = self.data().last().unwrap().0;
+    let improved = self.best.is_none() || gbest < self.best.unwrap();
+    if improved {
+      self.best = Some(gbest);
+    }
+
+    let n = self.particles().len();
+    let count = match self.population {
+      Population::Fixed => n,
+      Population::Linear => {
+        let ratio = (iter + 1) as f64 / iterations as f64;
+        let initial = self.initial_particle_count as f64;
+        let target = self.final_particle_count as f64;
+        (initial + (target - initial) * ratio).round() as usize
+      }
+      Population::Adaptive => match improved {
+        true => n.saturating_sub(1).max(self.min_particle_count),
+        false => (n + 1).min(self.max_particle_count),
+      },
+    };
+    if count != n {
+      self.resize(count);
+    }
+  }
+
+  fn reinitialize(&mut self, idx: usize, pos: Option<DVector<f64>>) {
+    self.inner.reinitialize(idx, pos);
+  }
+
+  fn resize(&mut self, count: usize) {
+    self.inner.resize(count);
+  }
+
+  fn remove_particle(&mut self, idx: usize) {
+    self.inner.remove_particle(idx);
+  }
+
+  fn restart_count(&self) -> usize {
+    self.inner.restart_count()
+  }
+}
+
+impl<T, U: Particles<T>> Particles<T> for Resizer<T, U> {
+  fn particles(&self) -> &Vec<T> {
+    self.inner.particles()
+  }
+
+  fn particles_mut(&mut self) -> &mut Vec<T> {
+    self.inner.particles_mut()
+  }
+
+  fn particles_and_problem_mut(&mut self) -> (&mut Vec<T>, &mut Problem) {
+    self.inner.particles_and_problem_mut()
+  }
+}
+
+impl<T, U: GlobalBestPos> GlobalBestPos for Resizer<T, U> {
+  fn global_best_pos(&self) -> DVector<f64> {
+    self.inner.global_best_pos()
+  }
+
+  fn global_worst_pos(&self) -> DVector<f64> {
+    self.inner.global_worst_pos()
+  }
+
+  fn option_global_best_pos(&self) -> &Option<DVector<f64>> {
+    self.inner.option_global_best_pos()
+  }
+
+  fn option_global_worst_pos(&self) -> &Option<DVector<f64>> {
+    self.inner.option_global_worst_pos()
+  }
+
+  fn set_global_best_pos(&mut self, pos: DVector<f64>) {
+    self.inner.set_global_best_pos(pos);
+  }
+
+  fn set_global_worst_pos(&mut self, pos: DVector<f64>) {
+    self.inner.set_global_worst_pos(pos);
+  }
+}
+
+impl<T, U: OptimizationProblem> OptimizationProblem for Resizer<T, U> {
+  fn problem(&mut self) -> &mut Problem {
+    self.inner.problem()
+  }
+}
+
+impl<T, U> Name for Resizer<T, U> {
+  fn name(&self) -> &String {
+    &self.name
+  }
+}
+
+impl<T, U: Data<T>> Data<T> for Resizer<T, U> {
+  fn data(&self) -> &Vec<(f64, f64, Option<Vec<T>>)> {
+    self.inner.data()
+  }
+
+  fn saving(&self) -> bool {
+    self.inner.saving()
+  }
+
+  fn additional_data(&self) -> &Vec<Vec<Vec<(String, f64)>>> {
+    self.inner.additional_data()
+  }
+
+  fn data_mut(&mut self) -> &mut Vec<(f64, f64, Option<Vec<T>>)> {
+    self.inner.data_mut()
+  }
+
+  fn additional_data_mut(&mut self) -> &mut Vec<Vec<Vec<(String, f64)>>> {
+    self.inner.additional_data_mut()
+  }
+
+  fn recorder(&mut self) -> &mut Recorder {
+    self.inner.recorder()
+  }
+
+  fn add_data_impl(&mut self, datum: (f64, f64, Option<Vec<T>>)) {
+    self.inner.add_data_impl(datum);
+  }
+
+  fn add_additional_data_impl(&mut self, datum: Vec<Vec<(String, f64)>>) {
+    self.inner.add_additional_data_impl(datum);
+  }
+}
+
+// Exports go through the inner optimizer so that its own fields (e.g. mass) are kept.
+impl<T: Position + Velocity + Clone, U: Optimizer<T>> DataExporter<T> for Resizer<T, U> {
+  fn out_directory(&self) -> &PathBuf {
+    self.inner.out_directory()
+  }
+
+  fn save_data(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+    self.inner.save_data()
+  }
+
+  fn generate_data_json(&mut self) -> Result<String, Box<dyn std::error::Error>> {
+    self.inner.generate_data_json()
+  }
+
+  fn save_additional_data(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+    self.inner.save_additional_data()
+  }
+
+  fn additional_summary(&mut self) -> Map<String, Value> {
+    let mut summary = self.inner.additional_summary();
+    summary.insert("particle_count".to_owned(), json!(self.particle_counts));
+    summary
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::optimizers::pso::Pso;
+  use crate::particles::pso::PsoParticle;
+  use crate::particles::traits::BestPosition;
+  use crate::utils;
+
+  fn resizer(population: Population) -> Resizer<PsoParticle, Pso<PsoParticle>> {
+    let parameters = utils::param_hashmap_generator(vec![
+      ("particle_count", ParamValue::Int(20)),
+      ("w", ParamValue::Float(0.7)),
+      ("phi_p", ParamValue::Float(1.5)),
+      ("phi_g", ParamValue::Float(1.5)),
+      ("population", ParamValue::Population(population)),
+      ("final_particle_count", ParamValue::Int(5)),
+      ("min_particle_count", ParamValue::Int(10)),
+      ("max_particle_count", ParamValue::Int(30)),
+      ("behavior", utils::behavior_from_tiled(false)),
+    ]);
+    Resizer::new(
+      "resize".to_owned(),
+      problems::sphere_100(5),
+      parameters,
+      PathBuf::new(),
+      true,
+    )
+  }
+
+  // Number of particles of every iteration of data.json.
+  fn data_json_counts(optimizer: &mut Resizer<PsoParticle, Pso<PsoParticle>>) -> Vec<usize> {
+    let data: Value = serde_json::from_str(&optimizer.generate_data_json().unwrap()).unwrap();
+    data.as_array().unwrap().iter().map(|datum| datum["particles"].as_array().unwrap().len()).collect()
+  }
+
+  #[test]
+  fn linear_schedule_shrinks_to_the_final_count() {
+    let mut optimizer = resizer(Population::Linear);
+    optimizer.run(16);
+    let counts: Vec<usize> = (0..16).map(|iter| (20. - 15. * iter as f64 / 16.).round() as usize).collect();
+    assert_eq!(optimizer.additional_summary()["particle_count"], json!(counts));
+    assert_eq!(data_json_counts(&mut optimizer), counts);
+    assert_eq!(optimizer.particles().len(), 5);
+  }
+
+  #[test]
+  fn adaptive_schedule_stays_within_bounds() {
+    let mut optimizer = resizer(Population::Adaptive);
+    optimizer.run(40);
+    let counts: Vec<usize> = serde_json::from_value(optimizer.additional_summary()["particle_count"].clone()).unwrap();
+    assert_eq!(counts.len(), 40);
+    assert_eq!(counts[0], 20);
+    assert!(counts.windows(2).all(|w| w[0].abs_diff(w[1]) <= 1), "{:?}", counts);
+    assert!(counts.iter().all(|&n| (10..=30).contains(&n)), "{:?}", counts);
+    assert_eq!(data_json_counts(&mut optimizer), counts);
+  }
+
+  #[test]
+  fn new_particles_have_their_own_personal_best() {
+    let mut optimizer = resizer(Population::Fixed);
+    optimizer.run(5);
+    optimizer.resize(25);
+    for particle in &optimizer.particles()[20..] {
+      assert_eq!(&particle.best_pos(), particle.pos());
+      assert_eq!(particle.best_fitness(), None);
+    }
+  }
+}
@@ -0,0 +1,511 @@
+use crate::optimizers::traits::{
+  Data, DataExporter, GlobalBestPos, Name, OptimizationProblem, Optimizer, ParamValue, Particles,
+};
+use crate::particles::traits::{Behavior, Position, Velocity};
+use crate::problems;
+use crate::recording::Recorder;
+use crate::utils;
+use nalgebra::DVector;
+use problems::Problem;
+use serde_json::{json, Map, Value};
+use std::{collections::HashMap, mem, path::PathBuf};
+
+#[derive(Clone, Debug)]
+pub struct RestartEvent {
+  iteration: usize,
+  reason: String,
+  particle_count: usize,
+  reinitialized: usize,
+}
+
+// Wraps any optimizer and restarts it when the swarm stagnates or collapses.
+#[derive(Clone)]
+pub struct Restarter<T, U> {
+  name: String,
+  inner: U,
+  parameters: HashMap<String, ParamValue>,
+  global_best_pos: Option<DVector<f64>>,
+  global_worst_pos: Option<DVector<f64>>,
+  // The best solution found so far, kept across restarts.
+  elite: Option<(f64, DVector<f64>)>,
+  // Iterations without improving the elite before restarting.
+  stagnation: Option<usize>,
+  // Restart when the spread falls below this ratio of the initial spread.
+  spread: Option<f64>,
+  // Fraction of the worst particles to re-initialize. 1 restarts the whole optimizer.
+  fraction: f64,
+  // Population growth factor applied on every full restart (IPOP).
+  growth: f64,
+  particle_count: usize,
+  initial_spread: Option<f64>,
+  last_improvement: usize,
+  restart_iter: usize,
+  restarts: Vec<RestartEvent>,
+  // Number of particles at every iteration, across restarts.
+  particle_counts: Vec<usize>,
+  data: Vec<(f64, f64, Option<Vec<T>>)>,
+  additional_data: Vec<Vec<Vec<(String, f64)>>>,
+  out_directory: PathBuf,
+  save: bool,
+}
+
+impl<T: Position + Velocity + Clone, U: Optimizer<T>> Optimizer<T> for Restarter<T, U> {
+  fn new(
+    name: String,
+    problem: Problem,
+    parameters: HashMap<String, ParamValue>,
+    out_directory: PathBuf,
+    save: bool,
+  ) -> Restarter<T, U> {
+    let stagnation = match parameters.contains_key("restart_stagnation") {
+      true => match parameters["restart_stagnation"] {
+        ParamValue::Int(val) => Some(val as usize),
+        _ => {
+          eprintln!("Error: parameter 'restart_stagnation' should be of type Param::Int.");
+          std::process::exit(1);
+        }
+      },
+      false => None,
+    };
+
+    let spread = match parameters.contains_key("restart_spread") {
+      true => match parameters["restart_spread"] {
+        ParamValue::Float(val) => Some(val),
+        _ => {
+          eprintln!("Error: parameter 'restart_spread' should be of type Param::Float.");
+          std::process::exit(1);
+        }
+      },
+      false => None,
+    };
+
+    let fraction = match parameters.contains_key("restart_fraction") {
+      true => match parameters["restart_fraction"] {
+        ParamValue::Float(val) => val,
+        _ => {
+          eprintln!("Error: parameter 'restart_fraction' should be of type Param::Float.");
+          std::process::exit(1);
+        }
+      },
+      false => 1.,
+    };
+    assert!(
+      fraction > 0. && fraction <= 1.,
+      "'restart_fraction' should be in (0, 1]."
+    );
+
+    let growth = match parameters.contains_key("restart_growth") {
+      true => match parameters["restart_growth"] {
+        ParamValue::Float(val) => val,
+        _ => {
+          eprintln!("Error: parameter 'restart_growth' should be of type Param::Float.");
+          std::process::exit(1);
+        }
+      },
+      false => 1.,
+    };
+
+    let inner = U::new(name.clone(), problem, parameters.clone(), out_directory.clone(), save);
+    let particle_count = inner.particles().len();
+
+    Restarter {
+      name,
+      inner,
+      parameters,
+      global_best_pos: None,
+      global_worst_pos: None,
+      elite: None,
+      stagnation,
+      spread,
+      fraction,
+      growth,
+      particle_count,
+      initial_spread: None,
+      last_improvement: 0,
+      restart_iter: 0,
+      restarts: Vec::new(),
+      particle_counts: Vec::new(),
+      data: Vec::new(),
+      additional_data: Vec::new(),
+      out_directory,
+      save,
+    }
+  }
+
+  fn init(&mut self, number_of_particles: usize, behavior: Behavior) {
+    self.inner.init(number_of_particles, behavior);
+  }
+
+  fn calculate_vel(&mut self, i: usize) -> DVector<f64> {
+    self.inner.calculate_vel(i)
+  }
+
+  fn reinitialize(&mut self, idx: usize, pos: Option<DVector<f64>>) {
+    self.inner.reinitialize(idx, pos);
+  }
+
+  fn resize(&mut self, count: usize) {
+    self.inner.resize(count);
+  }
+
+  fn remove_particle(&mut self, idx: usize) {
+    self.inner.remove_particle(idx);
+  }
+
+  fn restart_count(&self) -> usize {
+    self.restarts.len()
+  }
+
+  fn step(&mut self, iter: usize, iterations: usize) {
+    self.inner.step(iter - self.restart_iter, iterations - self.restart_iter);
+
+    // Move the data of the inner optimizer over, so that it spans all restarts.
+    if let Some(datum) = self.inner.data_mut().pop() {
+      self.data.push(datum);
+    }
+    if let Some(datum) = self.inner.additional_data_mut().pop() {
+      self.additional_data.push(datum);
+    }
+    self.set_global_best_pos(self.inner.global_best_pos());
+    self.set_global_worst_pos(self.inner.global_worst_pos());
+    self.particle_counts.push(self.particles().len());
+
+    // Update elite.
+    let gbest = self.data.last().unwrap().0;
+    if self.elite.is_none() || gbest < self.elite.as_ref().unwrap().0 {
+      self.elite = Some((gbest, self.inner.global_best_pos()));
+      self.last_improvement = iter;
+    }
+
+    if iter + 1 == iterations {
+      return;
+    }
+
+    let mut reason = None;
+    if let Some(stagnation) = self.stagnation {
+      if iter - self.last_improvement >= stagnation {
+        reason = Some("stagnation");
+      }
+    }
+    if let Some(threshold) = self.spread {
+      let positions: Vec<DVector<f64>> = self.particles().iter().map(|p| p.pos().clone()).collect();
+      let spread = utils::average_pairwise_distance(&positions);
+      if self.initial_spread.is_none() {
+        self.initial_spread = Some(spread);
+      }
+      if reason.is_none() && spread / self.initial_spread.unwrap() < threshold {
+        reason = Some("spread");
+      }
+    }
+
+    if let Some(reason) = reason {
+      let reinitialized = match self.fraction < 1. {
+        true => self.partial_restart(),
+        false => self.full_restart(iter),
+      };
+      self.restarts.push(RestartEvent {
+        iteration: iter,
+        reason: reason.to_owned(),
+        particle_count: self.particles().len(),
+        reinitialized,
+      });
+      self.last_improvement = iter;
+    }
+  }
+}
+
+impl<T: Position + Velocity + Clone, U: Optimizer<T>> Restarter<T, U> {
+  // Re-creates the inner optimizer with a grown population and injects the elite.
+  fn full_restart(&mut self, iter: usize) -> usize {
+    self.particle_count = (self.particle_count as f64 * self.growth).round() as usize;
+    let mut parameters = self.parameters.clone();
+    parameters.insert(
+      "particle_count".to_owned(),
+      ParamValue::Int(self.particle_count as isize),
+    );
+
+    // Reuse the problem and the recorder so that the evaluation count, the metrics and
+    // the trajectory carry over. The old optimizer is dropped with its copy of the problem.
+    let problem = self.inner.problem().clone();
+    let recorder = mem::take(self.inner.recorder());
+    self.inner = U::new(
+      self.name.clone(),
+      problem,
+      parameters,
+      self.out_directory.clone(),
+      self.save,
+    );
+    let fresh = mem::replace(self.inner.recorder(), recorder);
+    if let Some((fitness, pos)) = fresh.best() {
+      self.inner.recorder().update_best(*fitness, pos);
+    }
+    let elite = self.elite.as_ref().unwrap().1.clone();
+    self.inner.reinitialize(0, Some(elite));
+
+    self.restart_iter = iter + 1;
+    self.initial_spread = None;
+    self.particles().len()
+  }
+
+  // Re-initializes the worst particles, always keeping the best one.
+  fn partial_restart(&mut self) -> usize {
+    let mut fitness: Vec<(f64, usize)> = self.inner.evaluate().into_iter().zip(0..).collect();
+    fitness.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    let n = fitness.len();
+    let count = ((n as f64 * self.fraction).ceil() as usize).min(n.saturating_sub(1));
+    for &(_, idx) in fitness.iter().take(count) {
+      self.inner.reinitialize(idx, None);
+    }
+    count
+  }
+}
+
+impl<T, U: Particles<T>> Particles<T> for Restarter<T, U> {
+  fn particles(&self) -> &Vec<T> {
+    self.inner.particles()
+  }
+
+  fn particles_mut(&mut self) -> &mut Vec<T> {
+    self.inner.particles_mut()
+  }
+
+  fn particles_and_problem_mut(&mut self) -> (&mut Vec<T>, &mut Problem) {
+    self.inner.particles_and_problem_mut()
+  }
+}
+
+impl<T, U: OptimizationProblem> GlobalBestPos for Restarter<T, U> {
+  fn global_best_pos(&self) -> DVector<f64> {
+    self.global_best_pos.clone().unwrap()
+  }
+
+  fn global_worst_pos(&self) -> DVector<f64> {
+    self.global_worst_pos.clone().unwrap()
+  }
+
+  fn option_global_best_pos(&self) -> &Option<DVector<f64>> {
+    &self.global_best_pos
+  }
+
+  fn option_global_worst_pos(&self) -> &Option<DVector<f64>> {
+    &self.global_worst_pos
+  }
+
+  fn set_global_best_pos(&mut self, pos: DVector<f64>) {
+    self.global_best_pos = Some(pos);
+  }
+
+  fn set_global_worst_pos(&mut self, pos: DVector<f64>) {
+    self.global_worst_pos = Some(pos);
+  }
+}
+
+impl<T, U: OptimizationProblem> OptimizationProblem for Restarter<T, U> {
+  fn problem(&mut self) -> &mut Problem {
+    self.inner.problem()
+  }
+}
+
+impl<T, U> Name for Restarter<T, U> {
+  fn name(&self) -> &String {
+    &self.name
+  }
+}
+
+impl<T: Clone, U: Data<T>> Data<T> for Restarter<T, U> {
+  fn data(&self) -> &Vec<(f64, f64, Option<Vec<T>>)> {
+    &self.data
+  }
+
+  fn saving(&self) -> bool {
+    self.save
+  }
+
+  fn additional_data(&self) -> &Vec<Vec<Vec<(String, f64)>>> {
+    &self.additional_data
+  }
+
+  fn data_mut(&mut self) -> &mut Vec<(f64, f64, Option<Vec<T>>)> {
+    &mut self.data
+  }
+
+  fn additional_data_mut(&mut self) -> &mut Vec<Vec<Vec<(String, f64)>>> {
+    &mut self.additional_data
+  }
+
+  // The inner optimizer records the iterations, and its recorder outlives restarts.
+  fn recorder(&mut self) -> &mut Recorder {
+    self.inner.recorder()
+  }
+
+  fn add_data_impl(&mut self, datum: (f64, f64, Option<Vec<T>>)) {
+    self.data.push(datum);
+  }
+
+  fn add_additional_data_impl(&mut self, datum: Vec<Vec<(String, f64)>>) {
+    self.additional_data.push(datum);
+  }
+}
+
+// Exports go through the inner optimizer so that its own fields (e.g. mass) are kept.
+impl<T: Position + Velocity + Clone, U: Optimizer<T>> DataExporter<T> for Restarter<T, U> {
+  fn out_directory(&self) -> &PathBuf {
+    &self.out_directory
+  }
+
+  fn save_data(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+    mem::swap(&mut self.data, self.inner.data_mut());
+    let result = self.inner.save_data();
+    mem::swap(&mut self.data, self.inner.data_mut());
+    result
+  }
+
+  fn generate_data_json(&mut self) -> Result<String, Box<dyn std::error::Error>> {
+    mem::swap(&mut self.data, self.inner.data_mut());
+    let result = self.inner.generate_data_json();
+    mem::swap(&mut self.data, self.inner.data_mut());
+    result
+  }
+
+  fn save_additional_data(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+    mem::swap(&mut self.additional_data, self.inner.additional_data_mut());
+    let result = self.inner.save_additional_data();
+    mem::swap(&mut self.additional_data, self.inner.additional_data_mut());
+    result
+  }
+
+  fn additional_summary(&mut self) -> Map<String, Value> {
+    let mut summary = self.inner.additional_summary();
+    let mut restarts = Vec::new();
+    for event in &self.restarts {
+      restarts.push(json!({
+        "iteration": event.iteration,
+        "reason": event.reason,
+        "particle_count": event.particle_count,
+        "reinitialized": event.reinitialized,
+      }));
+    }
+    let elite_fitness = self.elite.as_ref().map(|elite| elite.0);
+    summary.insert("particle_count".to_owned(), json!(self.particle_counts));
+    summary.insert("elite_fitness".to_owned(), json!(elite_fitness));
+    summary.insert("restarts".to_owned(), json!(restarts));
+    summary
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::optimizers::pso::Pso;
+  use crate::optimizers::testing;
+  use crate::particles::pso::PsoParticle;
+
+  fn restarter(particle_count: isize, restart: Vec<(&str, ParamValue)>) -> Restarter<PsoParticle, Pso<PsoParticle>> {
+    let mut parameters = vec![
+      ("particle_count", ParamValue::Int(particle_count)),
+      ("w", ParamValue::Float(0.7)),
+      ("phi_p", ParamValue::Float(1.5)),
+      ("phi_g", ParamValue::Float(1.5)),
+      testing::behavior(),
+    ];
+    parameters.extend(restart);
+    Restarter::new(
+      "restart".to_owned(),
+      problems::sphere_100(5),
+      utils::param_hashmap_generator(parameters),
+      PathBuf::new(),
+      false,
+    )
+  }
+
+  #[test]
+  fn restarts_when_the_spread_collapses() {
+    let mut optimizer = restarter(10, vec![("restart_spread", ParamValue::Float(0.5))]);
+    optimizer.step(0, 10);
+    assert!(optimizer.restarts.is_empty());
+    assert!(optimizer.initial_spread.is_some());
+
+    // Pretend that the swarm started out far wider than it is now.
+    optimizer.initial_spread = Some(1e9);
+    optimizer.step(1, 10);
+    assert_eq!(optimizer.restarts.len(), 1);
+    assert_eq!(optimizer.restarts[0].reason, "spread");
+    assert_eq!(optimizer.restarts[0].iteration, 1);
+    // The spread of the new swarm is measured again.
+    assert!(optimizer.initial_spread.is_none());
+  }
+
+  #[test]
+  fn full_restarts_grow_the_swarm() {
+    let mut optimizer = restarter(
+      4,
+      vec![
+        ("restart_stagnation", ParamValue::Int(0)),
+        ("restart_growth", ParamValue::Float(2.)),
+      ],
+    );
+    optimizer.run(4);
+    assert_eq!(optimizer.particle_counts, vec![4, 8, 16, 32]);
+    let counts: Vec<usize> = optimizer.restarts.iter().map(|event| event.particle_count).collect();
+    assert_eq!(counts, vec![8, 16, 32]);
+    assert!(optimizer.restarts.iter().all(|event| event.reinitialized == event.particle_count));
+  }
+
+  #[test]
+  fn full_restarts_keep_the_elite() {
+    let mut optimizer = restarter(10, vec![("restart_stagnation", ParamValue::Int(0))]);
+    let mut elite = f64::INFINITY;
+    for iter in 0..10 {
+      optimizer.step(iter, 10);
+      let (fitness, pos) = optimizer.elite.clone().unwrap();
+      assert!(fitness <= elite);
+      elite = fitness;
+      if iter < 9 {
+        assert_eq!(optimizer.particles()[0].pos(), &pos);
+      }
+    }
+    let best = optimizer.data().iter().map(|datum| datum.0).fold(f64::INFINITY, f64::min);
+    assert_eq!(elite, best);
+    assert_eq!(optimizer.recorder().best().unwrap().0, best);
+  }
+
+  #[test]
+  fn evaluations_carry_over_restarts() {
+    let mut optimizer = restarter(10, vec![("restart_stagnation", ParamValue::Int(0))]);
+    optimizer.run(5);
+    assert_eq!(optimizer.restart_count(), 4);
+    let evaluations = optimizer.problem().evaluation_progress().clone();
+    assert_eq!(evaluations.len(), 5);
+    assert!(evaluations.windows(2).all(|pair| pair[0] < pair[1]));
+    assert!(optimizer.problem().cnt() >= *evaluations.last().unwrap());
+  }
+
+  #[test]
+  fn summarizes_the_restarts_and_the_elite() {
+    let mut optimizer = restarter(
+      10,
+      vec![
+        ("restart_stagnation", ParamValue::Int(0)),
+        ("restart_fraction", ParamValue::Float(0.5)),
+      ],
+    );
+    optimizer.run(4);
+    let summary = optimizer.additional_summary();
+    let event = json!({
+      "reason": "stagnation",
+      "particle_count": 10,
+      "reinitialized": 5,
+    });
+    let restarts = summary["restarts"].as_array().unwrap();
+    assert_eq!(restarts.len(), 3);
+    for (iter, restart) in restarts.iter().enumerate() {
+      let mut expected = event.clone();
+      expected["iteration"] = json!(iter);
+      assert_eq!(restart, &expected);
+    }
+    let best = optimizer.data().iter().map(|datum| datum.0).fold(f64::INFINITY, f64::min);
+    assert_eq!(summary["elite_fitness"], json!(best));
+    assert_eq!(summary["particle_count"], json!([10, 10, 10, 10]));
+  }
+}
@@ -2,17 +2,17 @@ use crate::optimizers::gsa::Normalizer;
 use crate::optimizers::traits::{
   Data, DataExporter, GlobalBestPos, Name, OptimizationProblem, Optimizer, ParamValue, Particles,
 };
+use crate::particles::boundary::Boundary;
 use crate::particles::traits::{Behavior, Mass, Particle, Position, Velocity};
 use crate::problems;
 use rand_distr::{Distribution, Normal};
 // use crate::rand::Rng;
+use crate::recording::Recorder;
 use crate::utils;
 use nalgebra::DVector;
 use problems::Problem;
-use serde_json::json;
+use serde_json::{json, Value};
 use std::collections::HashMap;
-use std::fs;
-use std::mem;
 use std::path::PathBuf;
 
 type VelsAndAdditionalData = (Vec<DVector<f64>>, Vec<Vec<(String, f64)>>);
@@ -25,8 +25,10 @@ pub struct Rgsa<T> {
   global_best_pos: Option<DVector<f64>>,
   global_worst_pos: Option<DVector<f64>>,
   g: f64,
+  initial_spread: Option<f64>,
   data: Vec<(f64, f64, Option<Vec<T>>)>,
   additional_data: Vec<Vec<Vec<(String, f64)>>>,
+  recorder: Recorder,
   out_directory: PathBuf,
   _g0: f64,
   alpha: f64,
@@ -97,8 +99,10 @@ impl<T: Particle + Position + Velocity + Mass + Clone> Optimizer<T> for Rgsa<T>
       global_best_pos: None,
       global_worst_pos: None,
       g: g0,
+      initial_spread: None,
       data: Vec::new(),
       additional_data: Vec::new(),
+      recorder: Recorder::default(),
       out_directory,
       _g0: g0,
       alpha,
@@ -112,25 +116,15 @@ impl<T: Particle + Position + Velocity + Mass + Clone> Optimizer<T> for Rgsa<T>
 
   fn init(&mut self, number_of_particles: usize, behavior: Behavior) {
     let problem = &mut self.problem();
+    problem.sampler().reset(number_of_particles);
+    behavior.edge.setup(problem);
     let mut particles: Vec<T> = Vec::new();
     for _ in 0..number_of_particles {
       particles.push(T::new(problem, behavior));
     }
 
-    let mut global_best_pos = None;
-    let mut global_worst_pos = None;
-    for particle in particles.clone() {
-      if global_best_pos.is_none() || problem.f(particle.pos()) < problem.f(global_best_pos.as_ref().unwrap()) {
-        global_best_pos = Some(particle.pos().clone());
-      }
-      if global_worst_pos.is_none() || problem.f(particle.pos()) > problem.f(global_worst_pos.as_ref().unwrap()) {
-        global_worst_pos = Some(particle.pos().clone());
-      }
-    }
-
     self.particles = particles;
-    self.set_global_best_pos(global_best_pos.unwrap());
-    self.set_global_worst_pos(global_worst_pos.unwrap());
+    self.update_best_and_worst();
 
     utils::create_directory(self.out_directory().to_path_buf(), true, false);
   }
@@ -139,111 +133,48 @@ impl<T: Particle + Position + Velocity + Mass + Clone> Optimizer<T> for Rgsa<T>
     panic!("deprecated");
   }
 
-  fn run(&mut self, iterations: usize) {
+  fn calculate_vels(&mut self, iter: usize, iterations: usize) -> Vec<DVector<f64>> {
     let n = self.particles().len();
 
-    let mut initial_spread = None;
-
-    for iter in 0..iterations {
-      let mut distances = Vec::new();
-      for i in 0..n {
-        for j in 0..n {
-          if i == j {
-            continue;
-          }
-          let distance = (self.particles()[i].pos() - self.particles()[j].pos()).norm();
-          distances.push(distance);
-        }
-      }
-      let use_avg = true;
-      let spread = match use_avg {
-        true => distances.iter().sum::<f64>() / distances.len() as f64,
-        false => utils::calculate_std(&distances),
-      };
-      if initial_spread.is_none() {
-        initial_spread = Some(spread);
-      }
-      let _spread_ratio = spread / initial_spread.unwrap();
-
-      let ratio = (-self.alpha * iter as f64 / iterations as f64).exp();
-
-      self.g = (-self.alpha * iter as f64 / iterations as f64).exp();
+    let positions: Vec<DVector<f64>> = self.particles().iter().map(|p| p.pos().clone()).collect();
+    // Measured before the move, and recorded so that `record_metrics` does not measure
+    // the swarm a second time.
+    let (diameter, spread) = utils::pairwise_distances(&positions);
+    self.recorder().set_metric("diameter", diameter);
+    self.recorder().set_metric("spread", spread);
+    if self.initial_spread.is_none() {
+      self.initial_spread = Some(spread);
+    }
+    let spread_ratio = spread / self.initial_spread.unwrap();
+    self.recorder().set_metric("spread_ratio", spread_ratio);
 
-      let mut fitness = Vec::new();
-      for idx in 0..n {
-        let pos = self.particles()[idx].pos().clone();
-        fitness.push(self.problem().f(&pos));
-      }
+    let ratio = (-self.alpha * iter as f64 / iterations as f64).exp();
 
-      let m = match self.normalizer {
-        Normalizer::MinMax => utils::original_gsa_mass(fitness),
-        Normalizer::ZScore => utils::z_mass(fitness),
-        Normalizer::Robust => utils::robust_mass(fitness),
-        Normalizer::Rank => utils::rank_mass(fitness),
-        Normalizer::Sigmoid2 => utils::sigmoid2_mass(fitness),
-        Normalizer::Sigmoid4 => utils::sigmoid4_mass(fitness),
-      };
-
-      for (mass, particle) in m.iter().zip(self.particles_mut().iter_mut()) {
-        particle.set_mass(*mass);
-      }
+    self.g = (-self.alpha * iter as f64 / iterations as f64).exp();
+    let g = self.g;
+    self.recorder().set_metric("g", g);
+    // Every mass attracts the others.
+    self.recorder().set_metric("k", n as f64);
 
-      // Calculate vels.
-      let mut x: Vec<DVector<f64>> = Vec::new();
-      let mut v = Vec::new();
-      for idx in 0..n {
-        x.push(self.particles()[idx].pos().clone());
-        v.push(self.particles()[idx].vel().clone());
-      }
+    let fitness = self.evaluate();
 
-      let (vels, additional_data) = calculate_vels(
-        x.clone(),
-        m.clone(),
-        self.g,
-        iter as f64 / iterations as f64,
-        ratio,
-      );
-
-      // Clear memory.
-      self.problem().clear_memo();
-
-      // Update the position, best and worst.
-      let mut new_global_best_pos = None;
-      let mut new_global_worst_pos = None;
-      // for (i, m_i) in 0.. {
-      for (i, vel) in vels.iter().enumerate().take(n) {
-        let mut temp_problem = mem::take(&mut self.problem);
-        let particle = &mut self.particles_mut()[i];
-        particle.update_vel(vel.clone(), &mut temp_problem);
-        particle.move_pos(&mut temp_problem);
-        let pos = particle.pos().clone();
-
-        // Update best.
-        if new_global_best_pos.is_none()
-          || (temp_problem.f(&pos) < temp_problem.f(&new_global_best_pos.clone().unwrap()))
-        {
-          new_global_best_pos = Some(pos.clone());
-        }
-
-        // Update worst.
-        if new_global_worst_pos.is_none()
-          || (temp_problem.f(&pos) > temp_problem.f(&new_global_worst_pos.clone().unwrap()))
-        {
-          new_global_worst_pos = Some(pos.clone());
-        }
-        self.problem = temp_problem;
-      }
-      self.update_global_best_pos(new_global_best_pos.clone().unwrap());
-      self.update_global_worst_pos(new_global_worst_pos.unwrap());
+    let m = self.normalizer.mass(fitness);
 
-      // Save the data for current iteration.
-      let gbest = self.problem.f(&self.global_best_pos());
-      let gworst = self.problem.f(&self.global_worst_pos());
+    for (mass, particle) in m.iter().zip(self.particles_mut().iter_mut()) {
+      particle.set_mass(*mass);
+    }
 
-      let particles = self.particles.clone();
-      self.add_data(self.save, gbest, gworst, particles);
-      self.add_additional_data(self.save, additional_data);
+    // Calculate vels.
+    let mut x: Vec<DVector<f64>> = Vec::new();
+    let mut v = Vec::new();
+    for idx in 0..n {
+      x.push(self.particles()[idx].pos().clone());
+      v.push(self.particles()[idx].vel().clone());
     }
+
+    let (vels, additional_data) = calculate_vels(x.clone(), m.clone(), self.g, iter as f64 / iterations as f64, ratio);
+    self.add_additional_data(self.save, additional_data);
+    vels
   }
 }
 
@@ -310,6 +241,10 @@ impl<T> Particles<T> for Rgsa<T> {
   fn particles_mut(&mut self) -> &mut Vec<T> {
     &mut self.particles
   }
+
+  fn particles_and_problem_mut(&mut self) -> (&mut Vec<T>, &mut Problem) {
+    (&mut self.particles, &mut self.problem)
+  }
 }
 
 impl<T> GlobalBestPos for Rgsa<T> {
@@ -355,10 +290,26 @@ impl<T: Clone> Data<T> for Rgsa<T> {
     &self.data
   }
 
+  fn saving(&self) -> bool {
+    self.save
+  }
+
   fn additional_data(&self) -> &Vec<Vec<Vec<(String, f64)>>> {
     &self.additional_data
   }
 
+  fn data_mut(&mut self) -> &mut Vec<(f64, f64, Option<Vec<T>>)> {
+    &mut self.data
+  }
+
+  fn additional_data_mut(&mut self) -> &mut Vec<Vec<Vec<(String, f64)>>> {
+    &mut self.additional_data
+  }
+
+  fn recorder(&mut self) -> &mut Recorder {
+    &mut self.recorder
+  }
+
   fn add_data_impl(&mut self, datum: (f64, f64, Option<Vec<T>>)) {
     self.data.push(datum);
   }
@@ -373,32 +324,13 @@ impl<T: Position + Velocity + Mass + Clone> DataExporter<T> for Rgsa<T> {
     &self.out_directory
   }
 
-  fn save_data(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-    // Serialize it to a JSON string
-    let mut vec_data = Vec::new();
-    for t in 0..self.data().len() {
-      let mut iter_data = Vec::new();
-      let datum = self.data()[t].2.clone().unwrap();
-      for particle_datum in &datum {
-        let pos = particle_datum.pos().clone();
-        iter_data.push(json!({
-          "fitness": self.problem().f_no_memo(&pos),
-          "vel": particle_datum.vel().as_slice(),
-          "pos": particle_datum.pos().as_slice(),
-          "mass": particle_datum.mass(),
-        }));
-      }
-      vec_data.push(json!({
-        "global_best_fitness": self.data()[t].0,
-        "global_worst_fitness": self.data()[t].1,
-        "particles": iter_data
-      }));
-    }
-
-    let serialized = serde_json::to_string(&json!(vec_data))?;
-
-    fs::write(self.out_directory().join("data.json"), serialized)?;
-    Ok(())
+  fn particle_json(&mut self, particle: &T) -> Value {
+    json!({
+      "fitness": self.export_fitness(particle),
+      "vel": particle.vel().as_slice(),
+      "pos": particle.pos().as_slice(),
+      "mass": particle.mass(),
+    })
   }
 }
 
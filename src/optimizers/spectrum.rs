@@ -0,0 +1,204 @@
+use crate::optimizers::observer::{Observer, Snapshot};
+use crate::particles::traits::Position;
+use nalgebra::{DMatrix, DVector, SymmetricEigen};
+use serde_json::{json, Map, Value};
+
+// Degree of the polynomial that unfolds the eigenvalues, as in the visualizer.
+const UNFOLDING_DEGREE: usize = 6;
+
+// Eigen-spectrum of the covariance of the standardized positions at every iteration,
+// compared with random matrix theory as `visualizer/rmt.py` does from data.json.
+#[allow(dead_code)]
+pub struct Spectrum {
+  // Number of largest eigenvalues kept per iteration.
+  pub largest: usize,
+  largest_eigenvalues: Vec<Vec<f64>>,
+  // Fraction of eigenvalues outside the Marchenko-Pastur bulk.
+  outside_bulk: Vec<f64>,
+  // Nearest-neighbour spacings of the unfolded eigenvalues.
+  spacing_mean: Vec<f64>,
+  spacing_variance: Vec<f64>,
+  // Mean ratio of consecutive spacings, which needs no unfolding. About 0.386 for
+  // uncorrelated levels and 0.531 for the Gaussian orthogonal ensemble.
+  spacing_ratio: Vec<f64>,
+}
+
+impl Default for Spectrum {
+  fn default() -> Self {
+    Spectrum {
+      largest: 5,
+      largest_eigenvalues: Vec::new(),
+      outside_bulk: Vec::new(),
+      spacing_mean: Vec::new(),
+      spacing_variance: Vec::new(),
+      spacing_ratio: Vec::new(),
+    }
+  }
+}
+
+impl<T: Position> Observer<T> for Spectrum {
+  fn on_iteration_end(&mut self, snapshot: &Snapshot<T>) {
+    let n = snapshot.particles.len();
+    if n < 2 {
+      return;
+    }
+    let dim = snapshot.particles[0].pos().len();
+    let data = DMatrix::from_fn(n, dim, |i, j| snapshot.particles[i].pos()[j]);
+    // Centering the n positions leaves at most n - 1 nonzero eigenvalues.
+    let eigenvalues = eigenvalues(&covariance(data), (n - 1).min(dim));
+
+    let q = dim as f64 / n as f64;
+    let (lambda_minus, lambda_plus) = ((1. - q.sqrt()).powi(2), (1. + q.sqrt()).powi(2));
+    let outside = eigenvalues.iter().filter(|&&x| x > lambda_plus || x < lambda_minus).count();
+    self.outside_bulk.push(outside as f64 / eigenvalues.len() as f64);
+    self.largest_eigenvalues.push(eigenvalues.iter().rev().take(self.largest).copied().collect());
+
+    let spacings: Vec<f64> = unfold(&eigenvalues).windows(2).map(|w| w[1] - w[0]).collect();
+    let mean = spacings.iter().sum::<f64>() / spacings.len() as f64;
+    let variance = spacings.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / spacings.len() as f64;
+    self.spacing_mean.push(mean);
+    self.spacing_variance.push(variance);
+    self.spacing_ratio.push(spacing_ratio(&eigenvalues));
+  }
+
+  fn summary(&self) -> Map<String, Value> {
+    let mut summary = Map::new();
+    summary.insert(
+      "spectrum_largest_eigenvalues".to_owned(),
+      json!(self.largest_eigenvalues),
+    );
+    summary.insert("spectrum_outside_bulk".to_owned(), json!(self.outside_bulk));
+    summary.insert("spectrum_spacing_mean".to_owned(), json!(self.spacing_mean));
+    summary.insert("spectrum_spacing_variance".to_owned(), json!(self.spacing_variance));
+    summary.insert("spectrum_spacing_ratio".to_owned(), json!(self.spacing_ratio));
+    summary
+  }
+}
+
+// Covariance of the dimensions, once every column is standardized.
+fn covariance(mut data: DMatrix<f64>) -> DMatrix<f64> {
+  let n = data.nrows() as f64;
+  for mut column in data.column_iter_mut() {
+    let mean = column.mean();
+    column.add_scalar_mut(-mean);
+    let std = (column.norm_squared() / n).sqrt();
+    column /= std + 1e-12;
+  }
+  data.transpose() * &data / n
+}
+
+// The `rank` largest eigenvalues, in ascending order.
+fn eigenvalues(covariance: &DMatrix<f64>, rank: usize) -> Vec<f64> {
+  let mut eigenvalues: Vec<f64> = SymmetricEigen::new(covariance.clone()).eigenvalues.iter().copied().collect();
+  eigenvalues.sort_by(|a, b| a.total_cmp(b));
+  eigenvalues.split_off(eigenvalues.len() - rank)
+}
+
+// Maps the eigenvalues to a smoothed count of the levels below them, fitting the level
+// indices with a polynomial, so that the mean spacing is about one.
+fn unfold(eigenvalues: &[f64]) -> Vec<f64> {
+  let m = eigenvalues.len();
+  let (min, max) = (eigenvalues[0], eigenvalues[m - 1]);
+  if m < 3 || max <= min {
+    return eigenvalues.to_vec();
+  }
+  let degree = UNFOLDING_DEGREE.min(m - 2);
+  // Rescaled to [-1, 1], which keeps the fit well conditioned.
+  let x: Vec<f64> = eigenvalues.iter().map(|e| 2. * (e - min) / (max - min) - 1.).collect();
+  let vandermonde = DMatrix::from_fn(m, degree + 1, |i, j| x[i].powi(j as i32));
+  let levels = DVector::from_fn(m, |i, _| (i + 1) as f64);
+  match vandermonde.clone().svd(true, true).solve(&levels, 1e-12) {
+    Ok(coefficients) => (vandermonde * coefficients).iter().copied().collect(),
+    Err(_) => eigenvalues.to_vec(),
+  }
+}
+
+// Mean of min(s_i, s_i+1) / max(s_i, s_i+1) over consecutive raw spacings.
+fn spacing_ratio(eigenvalues: &[f64]) -> f64 {
+  let spacings: Vec<f64> = eigenvalues.windows(2).map(|w| w[1] - w[0]).collect();
+  let ratios: Vec<f64> =
+    spacings.windows(2).filter(|s| s[0].max(s[1]) > 0.).map(|s| s[0].min(s[1]) / s[0].max(s[1])).collect();
+  ratios.iter().sum::<f64>() / ratios.len() as f64
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::particles::initializer::Init;
+  use crate::particles::pso::PsoParticle;
+  use crate::particles::traits::{Behavior, Edge, Particle};
+  use crate::problems;
+
+  fn assert_close(actual: &[f64], expected: &[f64]) {
+    assert_eq!(actual.len(), expected.len(), "{:?} != {:?}", actual, expected);
+    for (a, e) in actual.iter().zip(expected) {
+      assert!((a - e).abs() < 1e-9, "{:?} != {:?}", actual, expected);
+    }
+  }
+
+  // Particles at the given positions.
+  fn particles(positions: &[Vec<f64>]) -> Vec<PsoParticle> {
+    let mut problem = problems::sphere_100(positions[0].len());
+    let behavior = Behavior {
+      edge: Edge::Pass,
+      vmax: None,
+      pos_init: Init::Uniform,
+      vel_init: None,
+    };
+    positions
+      .iter()
+      .map(|pos| {
+        let mut particle = PsoParticle::new(&mut problem, behavior);
+        particle.set_pos(DVector::from_vec(pos.clone()));
+        particle
+      })
+      .collect()
+  }
+
+  #[test]
+  fn eigenvalues_of_a_known_covariance() {
+    // Two perfectly correlated dimensions and an independent one.
+    let data = DMatrix::from_row_slice(4, 3, &[1., 1., 1., -1., -1., 1., 1., 1., -1., -1., -1., -1.]);
+    let covariance = covariance(data);
+    let expected = DMatrix::from_row_slice(3, 3, &[1., 1., 0., 1., 1., 0., 0., 0., 1.]);
+    assert_close(covariance.as_slice(), expected.as_slice());
+    assert_close(&eigenvalues(&covariance, 3), &[0., 1., 2.]);
+    assert_close(&eigenvalues(&covariance, 2), &[1., 2.]);
+    // Evenly spaced levels.
+    assert_close(&[spacing_ratio(&eigenvalues(&covariance, 3))], &[1.]);
+  }
+
+  #[test]
+  fn spacing_ratio_of_known_levels() {
+    assert_close(&[spacing_ratio(&[0., 1., 2., 3.])], &[1.]);
+    assert_close(&[spacing_ratio(&[1., 2., 4., 8.])], &[0.5]);
+  }
+
+  #[test]
+  fn keeps_the_nonzero_eigenvalues_of_a_small_swarm() {
+    // Three particles in five dimensions, whose centered columns are a = (1, -1, 0) or
+    // b = (1, 1, -2). The standardized covariance is made of a 3×3 and a 2×2 block of
+    // ones, with eigenvalues 3 and 2, and zeros that are not part of the spectrum.
+    let positions = vec![
+      vec![1., 1., 1., 1., 1.],
+      vec![-1., -1., -1., 1., 1.],
+      vec![0., 0., 0., -2., -2.],
+    ];
+    let particles = particles(&positions);
+    let mut spectrum = Spectrum::default();
+    let snapshot = Snapshot {
+      iter: 0,
+      particles: &particles,
+      gbest: 0.,
+      gworst: 0.,
+      best_so_far: 0.,
+      evaluations: 0,
+    };
+    Observer::<PsoParticle>::on_iteration_end(&mut spectrum, &snapshot);
+
+    assert_eq!(spectrum.largest_eigenvalues.len(), 1);
+    assert_close(&spectrum.largest_eigenvalues[0], &[3., 2.]);
+    // With q = 5/3, the Marchenko-Pastur bulk spans about [0.085, 5.25].
+    assert_eq!(spectrum.outside_bulk, vec![0.]);
+  }
+}
@@ -0,0 +1,45 @@
+use crate::optimizers::traits::{Optimizer, ParamValue};
+use crate::particles::initializer::Init;
+use crate::particles::traits::{Behavior, Edge, Position, Velocity};
+
+// Sobol positions with the fixed shift of the default sampler and zero velocities,
+// so that a run with a given seed repeats exactly.
+pub fn behavior() -> (&'static str, ParamValue) {
+  (
+    "behavior",
+    ParamValue::Behavior(Behavior {
+      edge: Edge::Pass,
+      vmax: None,
+      pos_init: Init::Sobol,
+      vel_init: Some(Init::Zero),
+    }),
+  )
+}
+
+pub fn seed() -> (&'static str, ParamValue) {
+  ("seed", ParamValue::Int(7))
+}
+
+// Runs the optimizer and checks that the best found falls below `ratio` times the best
+// initial fitness.
+pub fn assert_improves<T: Position + Velocity + Clone, U: Optimizer<T>>(
+  optimizer: &mut U,
+  iterations: usize,
+  ratio: f64,
+) {
+  let initial = optimizer.recorder().best().unwrap().0;
+  optimizer.run(iterations);
+  let best: Vec<f64> = optimizer.data().iter().map(|datum| datum.0).collect();
+  assert_eq!(best.len(), iterations);
+  assert!(best.iter().all(|f| f.is_finite()));
+
+  let best_so_far = optimizer.recorder().best().unwrap().0;
+  assert_eq!(best_so_far, best.iter().copied().fold(initial, f64::min));
+  assert!(
+    best_so_far < ratio * initial,
+    "{} is not below {} · {}",
+    best_so_far,
+    ratio,
+    initial
+  );
+}
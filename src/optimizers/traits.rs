@@ -1,13 +1,20 @@
+use crate::iohprofiler;
+use crate::optimizers::cmaes::Restart;
 use crate::optimizers::gsa::Normalizer;
+use crate::optimizers::observer::{Observer, Snapshot};
+use crate::optimizers::resize::Population;
 use crate::particles::traits::{Behavior, Position, Velocity};
 use crate::problems;
+use crate::recording::{Format, Policy, Recorder};
+use crate::utils;
 use nalgebra::DVector;
 use problems::Problem;
 use serde::ser::{Serialize, Serializer};
-use serde_json::{json, Map};
+use serde_json::{json, Map, Value};
 use std::collections::HashMap;
 use std::fmt;
 use std::fs;
+use std::mem;
 use std::path::PathBuf;
 
 #[allow(dead_code)]
@@ -18,6 +25,13 @@ pub enum ParamValue {
   Normalizer(Normalizer),
   Bool(bool),
   Behavior(Behavior),
+  Restart(Restart),
+  Population(Population),
+  Format(Format),
+  Recording(Policy),
+  // Parameters tracked by the IOHprofiler logs, e.g. g or w.
+  IohProfiler(Vec<String>),
+  Path(PathBuf),
 }
 
 impl fmt::Display for ParamValue {
@@ -50,6 +64,23 @@ impl Serialize for ParamValue {
       },
       ParamValue::Bool(v) => serializer.serialize_bool(v),
       ParamValue::Behavior(b) => b.serialize(serializer),
+      ParamValue::Restart(value) => match value {
+        Restart::None => serializer.serialize_str("None"),
+        Restart::Ipop => serializer.serialize_str("Ipop"),
+        Restart::Bipop => serializer.serialize_str("Bipop"),
+      },
+      ParamValue::Population(value) => match value {
+        Population::Fixed => serializer.serialize_str("Fixed"),
+        Population::Linear => serializer.serialize_str("Linear"),
+        Population::Adaptive => serializer.serialize_str("Adaptive"),
+      },
+      ParamValue::Format(value) => match value {
+        Format::Json => serializer.serialize_str("Json"),
+        Format::Npy => serializer.serialize_str("Npy"),
+      },
+      ParamValue::Recording(ref policy) => policy.serialize(serializer),
+      ParamValue::IohProfiler(ref tracked) => tracked.serialize(serializer),
+      ParamValue::Path(ref path) => path.serialize(serializer),
     }
   }
 }
@@ -69,12 +100,224 @@ pub trait Optimizer<U: Position + Velocity + Clone>:
 
   fn init(&mut self, number_of_particles: usize, behavior: Behavior);
   fn calculate_vel(&mut self, i: usize) -> DVector<f64>;
-  fn run(&mut self, iterations: usize);
+
+  // New velocity of every particle for this iteration.
+  fn calculate_vels(&mut self, _iter: usize, _iterations: usize) -> Vec<DVector<f64>> {
+    (0..self.particles().len()).map(|idx| self.calculate_vel(idx)).collect()
+  }
+
+  // Called once the particles moved and the global best and worst were updated, with
+  // their fitness, before the iteration is recorded.
+  fn post_step(&mut self, _iter: usize, _iterations: usize, _gbest: f64, _gworst: f64) {}
+
+  // Optimizers only provide the velocities and the post-step hook, and this does the
+  // rest of the iteration.
+  fn step(&mut self, iter: usize, iterations: usize) {
+    let vels = self.calculate_vels(iter, iterations);
+    self.move_particles(vels);
+    let (gbest, gworst) = self.update_best_and_worst();
+    self.post_step(iter, iterations, gbest, gworst);
+    self.record(gbest, gworst);
+  }
+
+  fn should_stop(&mut self) -> bool {
+    self.problem().exhausted()
+  }
+
+  // Number of restarts so far, for optimizers that restart themselves.
+  fn restart_count(&self) -> usize {
+    0
+  }
+
+  fn run(&mut self, iterations: usize) {
+    self.run_observed(iterations, &mut []);
+  }
+
+  fn run_observed(&mut self, iterations: usize, observers: &mut [Box<dyn Observer<U>>]) {
+    for observer in observers.iter_mut() {
+      observer.on_init(self.particles());
+    }
+    let mut best_so_far = f64::INFINITY;
+    for iter in 0..iterations {
+      if self.should_stop() {
+        break;
+      }
+      let restarts = self.restart_count();
+      self.step(iter, iterations);
+      if observers.is_empty() {
+        continue;
+      }
+
+      let (gbest, gworst) = {
+        let datum = self.data().last().unwrap();
+        (datum.0, datum.1)
+      };
+      let evaluations = self.problem().cnt();
+      if self.restart_count() > restarts {
+        for observer in observers.iter_mut() {
+          observer.on_restart(iter, self.particles());
+        }
+      }
+      if gbest < best_so_far {
+        best_so_far = gbest;
+        let pos = self.global_best_pos();
+        for observer in observers.iter_mut() {
+          observer.on_improvement(iter, gbest, &pos);
+        }
+      }
+      let snapshot = Snapshot {
+        iter,
+        particles: self.particles(),
+        gbest,
+        gworst,
+        best_so_far,
+        evaluations,
+      };
+      for observer in observers.iter_mut() {
+        observer.on_iteration_end(&snapshot);
+      }
+    }
+    for observer in observers.iter_mut() {
+      observer.on_finish(self.particles());
+    }
+  }
+
+  // Applies the velocities, with the velocity and boundary policies of each particle.
+  fn move_particles(&mut self, vels: Vec<DVector<f64>>) {
+    let (particles, problem) = self.particles_and_problem_mut();
+    for (particle, vel) in particles.iter_mut().zip(vels) {
+      particle.update_vel(vel, problem);
+      particle.move_pos(problem);
+    }
+  }
+
+  // Closes the iteration with the given best and worst fitness. Saved particles go to
+  // the trajectory stream of the recorder if there is one, and are kept otherwise.
+  fn record(&mut self, gbest: f64, gworst: f64) {
+    let particles = mem::take(self.particles_mut());
+    if self.recorder().metrics().is_some() {
+      self.record_metrics(&particles);
+    }
+    let streamed = self.saving() && self.recorder().trajectory().is_some();
+    self.add_data(self.saving() && !streamed, gbest, gworst, &particles);
+    if streamed && self.recorder().trajectory().unwrap().advance() {
+      let record = self.iteration_json(gbest, gworst, &particles);
+      if let Err(e) = self.recorder().trajectory().unwrap().append(&record) {
+        eprintln!("Failed to write the trajectory: {}", e);
+      }
+    }
+    *self.particles_mut() = particles;
+  }
+
+  // Statistics of the swarm at the end of the iteration, for summary.json.
+  fn record_metrics(&mut self, particles: &[U]) {
+    let mut fitness: Vec<f64> = particles.iter().filter_map(|p| p.cached_fitness()).collect();
+    fitness.sort_by(|a, b| a.total_cmp(b));
+    let vel_norms: Vec<f64> = particles.iter().map(|p| p.vel().norm()).collect();
+
+    let recorder = self.recorder();
+    if !fitness.is_empty() {
+      let n = fitness.len();
+      recorder.set_metric("mean_fitness", fitness.iter().sum::<f64>() / n as f64);
+      recorder.set_metric("median_fitness", (fitness[(n - 1) / 2] + fitness[n / 2]) / 2.);
+    }
+    if !vel_norms.is_empty() {
+      recorder.set_metric(
+        "mean_velocity_norm",
+        vel_norms.iter().sum::<f64>() / vel_norms.len() as f64,
+      );
+    }
+    // Optimizers that measure the swarm themselves, e.g. MGSA, already set the spread.
+    if recorder.metrics().is_some_and(|metrics| metrics.get("spread").is_none()) {
+      let positions: Vec<DVector<f64>> = particles.iter().map(|p| p.pos().clone()).collect();
+      let (diameter, spread) = utils::pairwise_distances(&positions);
+      recorder.set_metric("diameter", diameter);
+      recorder.set_metric("spread", spread);
+    }
+  }
+
+  // Fitness of every particle. Those that moved are evaluated in a single batch.
+  fn evaluate(&mut self) -> Vec<f64> {
+    let moved: Vec<usize> =
+      (0..self.particles().len()).filter(|&idx| self.particles()[idx].cached_fitness().is_none()).collect();
+    let positions: Vec<DVector<f64>> = moved.iter().map(|&idx| self.particles()[idx].pos().clone()).collect();
+    let fitness = self.problem().f_batch(&positions);
+    for (&idx, f) in moved.iter().zip(fitness) {
+      self.particles_mut()[idx].set_cached_fitness(Some(f));
+    }
+    self.particles().iter().map(|p| p.cached_fitness().unwrap()).collect()
+  }
+
+  // Fitness of a single particle, evaluated only if it moved.
+  fn particle_fitness(&mut self, idx: usize) -> f64 {
+    let (particles, problem) = self.particles_and_problem_mut();
+    particles[idx].fitness(problem)
+  }
+
+  // Sets the global best and worst positions to those of the current particles, and
+  // returns their fitness. The recorder keeps the best found so far across iterations.
+  fn update_best_and_worst(&mut self) -> (f64, f64) {
+    let fitness = self.evaluate();
+    let (mut best, mut worst) = (0, 0);
+    for (i, f) in fitness.iter().enumerate() {
+      if *f < fitness[best] {
+        best = i;
+      }
+      if *f > fitness[worst] {
+        worst = i;
+      }
+    }
+    let best_pos = self.particles()[best].pos().clone();
+    self.recorder().update_best(fitness[best], &best_pos);
+    self.update_global_best_pos(best_pos);
+    self.update_global_worst_pos(self.particles()[worst].pos().clone());
+    (fitness[best], fitness[worst])
+  }
+
+  // Re-initializes a single particle, either randomly or at the given position.
+  fn reinitialize(&mut self, idx: usize, pos: Option<DVector<f64>>) {
+    let (particles, problem) = self.particles_and_problem_mut();
+    let particle = &mut particles[idx];
+    match pos {
+      Some(pos) => particle.set_pos(pos),
+      None => Position::init(particle, problem),
+    }
+    Velocity::init(particle, problem);
+  }
+
+  // Grows the swarm with random particles or shrinks it by removing the worst ones.
+  fn resize(&mut self, count: usize) {
+    let n = self.particles().len();
+    if count < n {
+      let mut fitness: Vec<(f64, usize)> = self.evaluate().into_iter().zip(0..n).collect();
+      fitness.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+      // Remove from the back so that the remaining indices stay valid.
+      let mut worst: Vec<usize> = fitness.iter().take(n - count).map(|&(_, idx)| idx).collect();
+      worst.sort_unstable_by(|a, b| b.cmp(a));
+      for idx in worst {
+        self.remove_particle(idx);
+      }
+    }
+    // New particles start from a copy, so `reinitialize` has to reset every per-particle
+    // state, e.g. the personal best.
+    for idx in n..count {
+      let particle = self.particles()[0].clone();
+      self.particles_mut().push(particle);
+      self.reinitialize(idx, None);
+    }
+  }
+
+  fn remove_particle(&mut self, idx: usize) {
+    self.particles_mut().remove(idx);
+  }
 }
 
 pub trait Particles<T> {
   fn particles(&self) -> &Vec<T>;
   fn particles_mut(&mut self) -> &mut Vec<T>;
+  // Both at once, for the particle methods that evaluate or sample.
+  fn particles_and_problem_mut(&mut self) -> (&mut Vec<T>, &mut Problem);
 }
 
 pub trait GlobalBestPos: OptimizationProblem {
@@ -86,6 +329,8 @@ pub trait GlobalBestPos: OptimizationProblem {
   fn option_global_worst_pos(&self) -> &Option<DVector<f64>>;
   fn set_global_best_pos(&mut self, pos: DVector<f64>);
   fn set_global_worst_pos(&mut self, pos: DVector<f64>);
+  // The global best is that of the current iteration. The best found so far is kept
+  // by the recorder of the run.
   fn update_global_best_pos(&mut self, pos: DVector<f64>) {
     // let gb = self.global_best_pos().clone();
     // if self.problem().f(&pos) < self.problem().f(&gb) {
@@ -110,11 +355,23 @@ pub trait OptimizationProblem {
 
 pub trait Data<T>: OptimizationProblem + GlobalBestPos {
   fn data(&self) -> &Vec<(f64, f64, Option<Vec<T>>)>;
+  // Whether the particles of every iteration are kept.
+  fn saving(&self) -> bool;
   #[allow(dead_code)]
   fn additional_data(&self) -> &Vec<Vec<Vec<(String, f64)>>>;
-  fn add_data(&mut self, save: bool, gbest: f64, gworst: f64, particles: Vec<T>) {
+  fn data_mut(&mut self) -> &mut Vec<(f64, f64, Option<Vec<T>>)>;
+  fn additional_data_mut(&mut self) -> &mut Vec<Vec<Vec<(String, f64)>>>;
+  // Trajectory and metrics of the run.
+  fn recorder(&mut self) -> &mut Recorder;
+  // The particles are only copied when they are saved.
+  fn add_data(&mut self, save: bool, gbest: f64, gworst: f64, particles: &[T])
+  where
+    T: Clone,
+  {
+    self.problem().next_iteration();
+    self.recorder().next_iteration();
     if save {
-      self.add_data_impl((gbest, gworst, Some(particles)));
+      self.add_data_impl((gbest, gworst, Some(particles.to_vec())));
     } else {
       self.add_data_impl((gbest, gworst, None));
     }
@@ -131,61 +388,49 @@ pub trait Data<T>: OptimizationProblem + GlobalBestPos {
 
 pub trait DataExporter<T: Position + Velocity + Clone>: Data<T> + Name + OptimizationProblem {
   fn out_directory(&self) -> &PathBuf;
-  #[allow(dead_code)]
-  fn save_data(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-    // Serialize it to a JSON string
-    let mut vec_data = Vec::new();
-    for t in 0..self.data().len() {
-      let mut iter_data = Vec::new();
-      let datum = self.data()[t].2.clone().unwrap();
-      for particle_datum in &datum {
-        let pos = particle_datum.pos().clone();
-        iter_data.push(json!({
-          "fitness": self.problem().f_no_memo(&pos),
-          "vel": particle_datum.vel().as_slice(),
-          "pos": particle_datum.pos().as_slice(),
-        }));
-      }
-      vec_data.push(json!({
-        "global_best_fitness": self.data()[t].0,
-        "global_worst_fitness": self.data()[t].1,
-        "particles": iter_data
-      }));
+  // Fitness of an exported particle, re-evaluated only if it was not cached.
+  fn export_fitness(&mut self, particle: &T) -> f64 {
+    match particle.cached_fitness() {
+      Some(fitness) => fitness,
+      None => self.problem().f_export(particle.pos()),
     }
+  }
 
-    let serialized = serde_json::to_string(&json!(vec_data))?;
+  // One particle of data.json. Optimizers add their own fields, e.g. the mass.
+  fn particle_json(&mut self, particle: &T) -> Value {
+    json!({
+      "fitness": self.export_fitness(particle),
+      "vel": particle.vel().as_slice(),
+      "pos": particle.pos().as_slice(),
+    })
+  }
 
-    fs::write(self.out_directory().join("data.json"), serialized)?;
-    Ok(())
+  // One iteration of data.json.
+  fn iteration_json(&mut self, gbest: f64, gworst: f64, particles: &[T]) -> Value {
+    let particles: Vec<Value> = particles.iter().map(|particle| self.particle_json(particle)).collect();
+    json!({
+      "global_best_fitness": gbest,
+      "global_worst_fitness": gworst,
+      "particles": particles,
+    })
   }
 
+  // data.json of the iterations kept in memory, i.e. when the run was not streamed.
   fn generate_data_json(&mut self) -> Result<String, Box<dyn std::error::Error>> {
     let mut vec_data = Vec::new();
-
     for t in 0..self.data().len() {
-      let mut iter_data = Vec::new();
-      // unwrap is for demonstration; handle errors as needed
+      let (gbest, gworst) = (self.data()[t].0, self.data()[t].1);
       let datum = self.data()[t].2.clone().unwrap();
-
-      for particle_datum in &datum {
-        let pos = particle_datum.pos().clone();
-        iter_data.push(json!({
-            "fitness": self.problem().f_no_memo(&pos),
-            "vel": particle_datum.vel().as_slice(),
-            "pos": particle_datum.pos().as_slice(),
-        }));
-      }
-
-      vec_data.push(json!({
-          "global_best_fitness": self.data()[t].0,
-          "global_worst_fitness": self.data()[t].1,
-          "particles": iter_data
-      }));
+      vec_data.push(self.iteration_json(gbest, gworst, &datum));
     }
+    Ok(serde_json::to_string(&vec_data)?)
+  }
 
-    // Convert to a JSON string
-    let serialized = serde_json::to_string(&json!(vec_data))?;
-    Ok(serialized)
+  #[allow(dead_code)]
+  fn save_data(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+    let serialized = self.generate_data_json()?;
+    fs::write(self.out_directory().join("data.json"), serialized)?;
+    Ok(())
   }
 
   #[allow(dead_code)]
@@ -218,6 +463,7 @@ pub trait DataExporter<T: Position + Velocity + Clone>: Data<T> + Name + Optimiz
       "problem": {
         "name": self.problem().name(),
         "dim": self.problem().dim(),
+        "optimum": self.problem().optimum(),
     },
       "method": {
         "name": self.name(),
@@ -228,18 +474,87 @@ pub trait DataExporter<T: Position + Velocity + Clone>: Data<T> + Name + Optimiz
     Ok(())
   }
 
-  fn save_summary(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+  // The run in the IOHprofiler format, a line per iteration with the evaluations, the
+  // global best fitness of the iteration and the best so far. Tracked parameters are
+  // metrics, e.g. g, or constant parameters, e.g. w, and NaN otherwise.
+  fn iohprofiler_run(&mut self, tracked: &[String], parameters: &HashMap<String, ParamValue>) -> iohprofiler::Run {
+    let metrics = match self.recorder().metrics() {
+      Some(metrics) => metrics.to_json(),
+      None => Map::new(),
+    };
+    let columns: Vec<Vec<f64>> = tracked
+      .iter()
+      .map(|name| match (metrics.get(name), parameters.get(name)) {
+        (Some(Value::Array(values)), _) => values.iter().map(|v| v.as_f64().unwrap_or(f64::NAN)).collect(),
+        (_, Some(ParamValue::Float(value))) => vec![*value; self.data().len()],
+        (_, Some(ParamValue::Int(value))) => vec![*value as f64; self.data().len()],
+        _ => vec![f64::NAN; self.data().len()],
+      })
+      .collect();
+
+    let mut dat = format!(
+      "evaluations raw_y best_y{}\n",
+      tracked.iter().map(|name| format!(" {}", name)).collect::<String>()
+    );
+    let (mut best, mut best_evaluations) = (f64::INFINITY, 0);
+    let evaluations = self.problem().evaluation_progress().clone();
+    for (t, &count) in evaluations.iter().enumerate().take(self.data().len()) {
+      let fitness = self.data()[t].0;
+      if fitness < best {
+        (best, best_evaluations) = (fitness, count);
+      }
+      dat.push_str(&format!("{} {} {}", count, fitness, best));
+      for column in columns.iter() {
+        dat.push_str(&format!(" {}", column.get(t).copied().unwrap_or(f64::NAN)));
+      }
+      dat.push('\n');
+    }
+    let json = json!({
+      "instance": iohprofiler::instance(self.problem().name()),
+      "evals": self.problem().cnt(),
+      "best": {
+        "evals": best_evaluations,
+        "y": best,
+      },
+    });
+    iohprofiler::Run { dat, json }
+  }
+
+  // Extra entries for summary.json, e.g. from wrappers.
+  fn additional_summary(&mut self) -> Map<String, Value> {
+    Map::new()
+  }
+
+  // `observed` holds the entries of the observers of the run.
+  fn save_summary(&mut self, observed: Map<String, Value>) -> Result<(), Box<dyn std::error::Error>> {
     let mut global_best_progress = Vec::new();
     let mut global_worst_progress = Vec::new();
     for t in 0..self.data().len() {
       global_best_progress.push(self.data()[t].0);
       global_worst_progress.push(self.data()[t].1);
     }
-    let serialized = serde_json::to_string(&json!({
-      "global_best_fitness": global_best_progress,
-      "global_worst_fitness": global_worst_progress,
-      "evaluation_count": self.problem().cnt(),
-    }))?;
+    let mut summary = self.additional_summary();
+    summary.insert("global_best_fitness".to_owned(), json!(global_best_progress));
+    summary.insert("global_worst_fitness".to_owned(), json!(global_worst_progress));
+    if let Some(metrics) = self.recorder().metrics() {
+      summary.extend(metrics.to_json());
+    }
+    if let Some((fitness, pos)) = self.recorder().best().cloned() {
+      summary.insert("best_fitness".to_owned(), json!(fitness));
+      summary.insert("best_position".to_owned(), json!(pos.as_slice()));
+    }
+    summary.extend(observed);
+    summary.insert("evaluation_count".to_owned(), json!(self.problem().cnt()));
+    summary.insert(
+      "evaluation_count_progress".to_owned(),
+      json!(self.problem().evaluation_progress()),
+    );
+    summary.insert("export_evaluation_count".to_owned(), json!(self.problem().export_cnt()));
+    summary.insert(
+      "out_of_bounds".to_owned(),
+      json!(self.problem().out_of_bounds_progress()),
+    );
+    let serialized = serde_json::to_string(&summary)?;
     fs::write(self.out_directory().join("summary.json"), serialized)?;
     Ok(())
   }
@@ -0,0 +1,319 @@
+use crate::optimizers::traits::{
+  Data, DataExporter, GlobalBestPos, Name, OptimizationProblem, Optimizer, ParamValue, Particles,
+};
+use crate::particles::boundary::Boundary;
+use crate::particles::traits::{Behavior, Particle, Position, Velocity};
+use crate::problems;
+use crate::rand::Rng;
+use crate::recording::Recorder;
+use crate::utils;
+use nalgebra::DVector;
+use problems::Problem;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::{collections::HashMap, path::PathBuf};
+
+#[derive(Clone)]
+pub struct Woa<T> {
+  name: String,
+  problem: Problem,
+  particles: Vec<T>,
+  global_best_pos: Option<DVector<f64>>,
+  global_worst_pos: Option<DVector<f64>>,
+  // The best whale found so far, which the others encircle.
+  prey: Option<(f64, DVector<f64>)>,
+  a: f64,
+  b: f64,
+  // Random numbers of the run, seeded with the `seed` parameter.
+  rng: StdRng,
+  data: Vec<(f64, f64, Option<Vec<T>>)>,
+  additional_data: Vec<Vec<Vec<(String, f64)>>>,
+  recorder: Recorder,
+  out_directory: PathBuf,
+  save: bool,
+}
+
+impl<T: Particle + Position + Velocity + Clone> Optimizer<T> for Woa<T> {
+  fn new(
+    name: String,
+    problem: Problem,
+    parameters: HashMap<String, ParamValue>,
+    out_directory: PathBuf,
+    save: bool,
+  ) -> Woa<T> {
+    assert!(
+      parameters.contains_key("particle_count"),
+      "Key 'particle_count' not found."
+    );
+    let number_of_particles = match parameters["particle_count"] {
+      ParamValue::Int(val) => val as usize,
+      _ => {
+        eprintln!("Error: parameter 'particle_count' should be of type Param::Int.");
+        std::process::exit(1);
+      }
+    };
+
+    assert!(parameters.contains_key("b"), "Key 'b' not found.");
+    let b = match parameters["b"] {
+      ParamValue::Float(val) => val,
+      _ => {
+        eprintln!("Error: parameter 'b' should be of type Param::Float.");
+        std::process::exit(1);
+      }
+    };
+
+    let seed = match parameters.contains_key("seed") {
+      true => match parameters["seed"] {
+        ParamValue::Int(val) => val as u64,
+        _ => {
+          eprintln!("Error: parameter 'seed' should be of type Param::Int.");
+          std::process::exit(1);
+        }
+      },
+      false => rand::thread_rng().gen(),
+    };
+
+    assert!(parameters.contains_key("behavior"), "Key 'behavior' not found.");
+    let behavior = match parameters["behavior"] {
+      ParamValue::Behavior(val) => val,
+      _ => {
+        eprintln!("Error: parameter 'behavior' should be of type Param::Behavior.");
+        std::process::exit(1);
+      }
+    };
+
+    let mut woa = Woa {
+      name,
+      problem,
+      particles: Vec::new(),
+      global_best_pos: None,
+      global_worst_pos: None,
+      prey: None,
+      a: 2.,
+      b,
+      rng: StdRng::seed_from_u64(seed),
+      data: Vec::new(),
+      additional_data: Vec::new(),
+      recorder: Recorder::default(),
+      out_directory,
+      save,
+    };
+
+    woa.init(number_of_particles, behavior);
+    woa
+  }
+
+  fn init(&mut self, number_of_particles: usize, behavior: Behavior) {
+    let problem = &mut self.problem();
+    problem.sampler().reset(number_of_particles);
+    behavior.edge.setup(problem);
+    let mut particles: Vec<T> = Vec::new();
+    for _ in 0..number_of_particles {
+      particles.push(T::new(problem, behavior));
+    }
+
+    self.particles = particles;
+    self.update_best_and_worst();
+    self.update_prey();
+
+    utils::create_directory(self.out_directory().to_path_buf(), true, false);
+  }
+
+  fn calculate_vel(&mut self, i: usize) -> DVector<f64> {
+    assert!(i < self.particles().len());
+    let x = self.particles()[i].pos().clone();
+    let prey = self.prey.clone().unwrap().1;
+
+    let p: f64 = self.rng.gen_range(0.0..1.0);
+    let l: f64 = self.rng.gen_range(-1.0..1.0);
+    let j = self.rng.gen_range(0..self.particles.len());
+    let x_rand = self.particles[j].pos().clone();
+
+    // A and C are drawn per dimension, as the random vectors of the original paper. With
+    // scalars the swarm moves along a single direction and collapses onto the prey.
+    let mut new_pos = DVector::from_element(x.len(), 0.);
+    for d in 0..x.len() {
+      let big_a = 2. * self.a * self.rng.gen_range(0.0..1.0) - self.a;
+      let big_c = 2. * self.rng.gen_range(0.0..1.0);
+      new_pos[d] = match (p < 0.5, big_a.abs() < 1.) {
+        // Encircle the prey.
+        (true, true) => prey[d] - big_a * (big_c * prey[d] - x[d]).abs(),
+        // Search around a random whale.
+        (true, false) => x_rand[d] - big_a * (big_c * x_rand[d] - x[d]).abs(),
+        // Bubble-net spiral around the prey.
+        (false, _) => (prey[d] - x[d]).abs() * (self.b * l).exp() * (2. * std::f64::consts::PI * l).cos() + prey[d],
+      };
+    }
+
+    new_pos - x
+  }
+
+  fn calculate_vels(&mut self, iter: usize, iterations: usize) -> Vec<DVector<f64>> {
+    // `a` decreases linearly from 2 to 0.
+    self.a = 2. - 2. * iter as f64 / iterations as f64;
+    (0..self.particles().len()).map(|idx| self.calculate_vel(idx)).collect()
+  }
+
+  fn post_step(&mut self, _iter: usize, _iterations: usize, _gbest: f64, _gworst: f64) {
+    self.update_prey();
+  }
+}
+
+impl<T: Particle + Position + Velocity + Clone> Woa<T> {
+  fn update_prey(&mut self) {
+    let fitness = self.evaluate();
+    for (particle, f) in self.particles.iter().zip(fitness) {
+      if self.prey.is_none() || f < self.prey.as_ref().unwrap().0 {
+        self.prey = Some((f, particle.pos().clone()));
+      }
+    }
+  }
+}
+
+impl<T> Particles<T> for Woa<T> {
+  fn particles(&self) -> &Vec<T> {
+    &self.particles
+  }
+
+  fn particles_mut(&mut self) -> &mut Vec<T> {
+    &mut self.particles
+  }
+
+  fn particles_and_problem_mut(&mut self) -> (&mut Vec<T>, &mut Problem) {
+    (&mut self.particles, &mut self.problem)
+  }
+}
+
+impl<T> GlobalBestPos for Woa<T> {
+  fn global_best_pos(&self) -> DVector<f64> {
+    self.global_best_pos.clone().unwrap()
+  }
+
+  fn global_worst_pos(&self) -> DVector<f64> {
+    self.global_worst_pos.clone().unwrap()
+  }
+
+  fn option_global_best_pos(&self) -> &Option<DVector<f64>> {
+    &self.global_best_pos
+  }
+
+  fn option_global_worst_pos(&self) -> &Option<DVector<f64>> {
+    &self.global_worst_pos
+  }
+
+  fn set_global_best_pos(&mut self, pos: DVector<f64>) {
+    self.global_best_pos = Some(pos);
+  }
+
+  fn set_global_worst_pos(&mut self, pos: DVector<f64>) {
+    self.global_worst_pos = Some(pos);
+  }
+}
+
+impl<T> OptimizationProblem for Woa<T> {
+  fn problem(&mut self) -> &mut Problem {
+    &mut self.problem
+  }
+}
+
+impl<T> Name for Woa<T> {
+  fn name(&self) -> &String {
+    &self.name
+  }
+}
+
+impl<T: Clone> Data<T> for Woa<T> {
+  fn data(&self) -> &Vec<(f64, f64, Option<Vec<T>>)> {
+    &self.data
+  }
+
+  fn saving(&self) -> bool {
+    self.save
+  }
+
+  fn additional_data(&self) -> &Vec<Vec<Vec<(String, f64)>>> {
+    &self.additional_data
+  }
+
+  fn data_mut(&mut self) -> &mut Vec<(f64, f64, Option<Vec<T>>)> {
+    &mut self.data
+  }
+
+  fn additional_data_mut(&mut self) -> &mut Vec<Vec<Vec<(String, f64)>>> {
+    &mut self.additional_data
+  }
+
+  fn recorder(&mut self) -> &mut Recorder {
+    &mut self.recorder
+  }
+
+  fn add_data_impl(&mut self, datum: (f64, f64, Option<Vec<T>>)) {
+    self.data.push(datum);
+  }
+
+  fn add_additional_data_impl(&mut self, datum: Vec<Vec<(String, f64)>>) {
+    self.additional_data.push(datum);
+  }
+}
+
+impl<T: Position + Velocity + Clone> DataExporter<T> for Woa<T> {
+  fn out_directory(&self) -> &PathBuf {
+    &self.out_directory
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::optimizers::testing;
+  use crate::particles::basic::BasicParticle;
+
+  fn woa() -> Woa<BasicParticle> {
+    let parameters = utils::param_hashmap_generator(vec![
+      ("particle_count", ParamValue::Int(20)),
+      ("b", ParamValue::Float(1.)),
+      testing::behavior(),
+      testing::seed(),
+    ]);
+    Woa::new(
+      "woa".to_owned(),
+      problems::sphere_100(5),
+      parameters,
+      PathBuf::new(),
+      false,
+    )
+  }
+
+  #[test]
+  fn improves_on_sphere() {
+    testing::assert_improves(&mut woa(), 100, 0.05);
+  }
+
+  #[test]
+  fn whales_either_encircle_the_prey_or_spiral_around_it() {
+    // With `a` at 0, A is 0 and encircling lands on the prey, while the spiral scales the
+    // distance to the prey by the same e^(bl)·cos(2πl) in every dimension.
+    let mut optimizer = woa();
+    optimizer.a = 0.;
+    let prey = optimizer.prey.clone().unwrap().1;
+    let (mut encircled, mut spiraled) = (0, 0);
+    for i in 0..optimizer.particles().len() {
+      let x = optimizer.particles()[i].pos().clone();
+      if x == prey {
+        continue;
+      }
+      let new_pos = &x + optimizer.calculate_vel(i);
+      if new_pos == prey {
+        encircled += 1;
+        continue;
+      }
+      spiraled += 1;
+      let factor = (new_pos[0] - prey[0]) / (prey[0] - x[0]).abs();
+      assert!(factor.abs() <= 1f64.exp());
+      for d in 0..x.len() {
+        assert!((new_pos[d] - prey[d] - factor * (prey[d] - x[d]).abs()).abs() < 1e-9);
+      }
+    }
+    assert!(encircled > 0 && spiraled > 0);
+  }
+}
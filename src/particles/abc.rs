@@ -0,0 +1,79 @@
+extern crate nalgebra as na;
+use crate::particles::traits::{Behavior, BehaviorTrait, Particle, Position, Trial, Velocity};
+use crate::problems;
+use nalgebra::DVector;
+use problems::Problem;
+
+#[allow(dead_code)]
+#[derive(Clone)]
+pub struct AbcParticle {
+  pos: DVector<f64>,
+  fitness: Option<f64>,
+  vel: DVector<f64>,
+  trial: usize,
+  behavior: Behavior,
+}
+
+impl Particle for AbcParticle {
+  fn new(problem: &mut Problem, behavior: Behavior) -> AbcParticle {
+    let mut particle = AbcParticle {
+      pos: DVector::from_element(problem.dim(), 0.),
+      fitness: None,
+      vel: DVector::from_element(problem.dim(), 0.),
+      trial: 0,
+      behavior,
+    };
+    Position::init(&mut particle, problem);
+    Velocity::init(&mut particle, problem);
+    particle
+  }
+}
+
+impl Position for AbcParticle {
+  fn pos(&self) -> &DVector<f64> {
+    &self.pos
+  }
+
+  fn set_pos(&mut self, pos: DVector<f64>) {
+    self.pos = pos;
+    self.fitness = None;
+  }
+
+  fn cached_fitness(&self) -> Option<f64> {
+    self.fitness
+  }
+
+  fn set_cached_fitness(&mut self, fitness: Option<f64>) {
+    self.fitness = fitness;
+  }
+}
+
+impl Velocity for AbcParticle {
+  fn default_vel(&self, problem: &Problem) -> DVector<f64> {
+    DVector::from_element(problem.dim(), 0.)
+  }
+
+  fn vel(&self) -> &DVector<f64> {
+    &self.vel
+  }
+
+  fn set_vel(&mut self, vel: DVector<f64>) {
+    self.vel = vel;
+  }
+}
+
+impl Trial for AbcParticle {
+  fn trial(&self) -> usize {
+    self.trial
+  }
+
+  fn set_trial(&mut self, trial: usize) {
+    self.trial = trial;
+  }
+}
+
+impl BehaviorTrait for AbcParticle {
+  fn behavior(&self) -> Behavior {
+    self.behavior
+  }
+}
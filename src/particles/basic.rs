@@ -0,0 +1,6 @@
+use crate::particles::cmaes::CmaesParticle;
+
+// A particle without any per-particle state besides its position and velocity,
+// which is all CMA-ES keeps too.
+#[allow(dead_code)]
+pub type BasicParticle = CmaesParticle;
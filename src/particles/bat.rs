@@ -0,0 +1,89 @@
+extern crate nalgebra as na;
+use crate::particles::traits::{Behavior, BehaviorTrait, Echolocation, Particle, Position, Velocity};
+use crate::problems;
+use nalgebra::DVector;
+use problems::Problem;
+
+#[allow(dead_code)]
+#[derive(Clone)]
+pub struct BatParticle {
+  pos: DVector<f64>,
+  fitness: Option<f64>,
+  vel: DVector<f64>,
+  loudness: f64,
+  pulse_rate: f64,
+  behavior: Behavior,
+}
+
+impl Particle for BatParticle {
+  fn new(problem: &mut Problem, behavior: Behavior) -> BatParticle {
+    let mut particle = BatParticle {
+      pos: DVector::from_element(problem.dim(), 0.),
+      fitness: None,
+      vel: DVector::from_element(problem.dim(), 0.),
+      loudness: 0.,
+      pulse_rate: 0.,
+      behavior,
+    };
+    Position::init(&mut particle, problem);
+    Velocity::init(&mut particle, problem);
+    particle
+  }
+}
+
+impl Position for BatParticle {
+  fn pos(&self) -> &DVector<f64> {
+    &self.pos
+  }
+
+  fn set_pos(&mut self, pos: DVector<f64>) {
+    self.pos = pos;
+    self.fitness = None;
+  }
+
+  fn cached_fitness(&self) -> Option<f64> {
+    self.fitness
+  }
+
+  fn set_cached_fitness(&mut self, fitness: Option<f64>) {
+    self.fitness = fitness;
+  }
+}
+
+impl Velocity for BatParticle {
+  fn default_vel(&self, problem: &Problem) -> DVector<f64> {
+    DVector::from_element(problem.dim(), 0.)
+  }
+
+  fn vel(&self) -> &DVector<f64> {
+    &self.vel
+  }
+
+  fn set_vel(&mut self, vel: DVector<f64>) {
+    self.vel = vel;
+  }
+}
+
+impl Echolocation for BatParticle {
+  fn loudness(&self) -> f64 {
+    self.loudness
+  }
+
+  fn set_loudness(&mut self, loudness: f64) {
+    self.loudness = loudness;
+  }
+
+  fn pulse_rate(&self) -> f64 {
+    self.pulse_rate
+  }
+
+  fn set_pulse_rate(&mut self, pulse_rate: f64) {
+    self.pulse_rate = pulse_rate;
+  }
+}
+
+impl BehaviorTrait for BatParticle {
+  fn behavior(&self) -> Behavior {
+    self.behavior
+  }
+}
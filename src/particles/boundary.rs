@@ -0,0 +1,243 @@
+extern crate nalgebra as na;
+use crate::particles::traits::Edge;
+use crate::problems;
+use nalgebra::DVector;
+use problems::Problem;
+use rand::Rng;
+
+// Moves a particle by its velocity while keeping it (or its fitness) within the domain.
+// Returns the new position, and the new velocity if the strategy changes it.
+pub trait Boundary {
+  fn apply(
+    &self,
+    pos: &DVector<f64>,
+    vel: &DVector<f64>,
+    problem: &mut Problem,
+  ) -> (DVector<f64>, Option<DVector<f64>>);
+
+  // Prepares the problem for the strategy, once before the particles are created.
+  fn setup(&self, _problem: &mut Problem) {}
+}
+
+// Dispatches to the strategy of the edge without allocating it, as the particles
+// move at every iteration.
+impl Boundary for Edge {
+  fn apply(
+    &self,
+    pos: &DVector<f64>,
+    vel: &DVector<f64>,
+    problem: &mut Problem,
+  ) -> (DVector<f64>, Option<DVector<f64>>) {
+    match *self {
+      Edge::Reflect => Reflect { damping: 1. }.apply(pos, vel, problem),
+      Edge::Pass => Pass.apply(pos, vel, problem),
+      Edge::Cycle => Cycle.apply(pos, vel, problem),
+      Edge::Absorb => Absorb.apply(pos, vel, problem),
+      Edge::Random => Random.apply(pos, vel, problem),
+      Edge::Midpoint => Midpoint.apply(pos, vel, problem),
+      Edge::DampedReflect(damping) => Reflect { damping }.apply(pos, vel, problem),
+      Edge::Hyperbolic => Hyperbolic.apply(pos, vel, problem),
+      Edge::Infinity => Infinity.apply(pos, vel, problem),
+    }
+  }
+
+  fn setup(&self, problem: &mut Problem) {
+    match *self {
+      Edge::Reflect => Reflect { damping: 1. }.setup(problem),
+      Edge::Pass => Pass.setup(problem),
+      Edge::Cycle => Cycle.setup(problem),
+      Edge::Absorb => Absorb.setup(problem),
+      Edge::Random => Random.setup(problem),
+      Edge::Midpoint => Midpoint.setup(problem),
+      Edge::DampedReflect(damping) => Reflect { damping }.setup(problem),
+      Edge::Hyperbolic => Hyperbolic.setup(problem),
+      Edge::Infinity => Infinity.setup(problem),
+    }
+  }
+}
+
+// Mirrors the particle on the wall and flips the velocity, scaled by `damping`.
+pub struct Reflect {
+  damping: f64,
+}
+
+impl Boundary for Reflect {
+  fn apply(
+    &self,
+    pos: &DVector<f64>,
+    vel: &DVector<f64>,
+    problem: &mut Problem,
+  ) -> (DVector<f64>, Option<DVector<f64>>) {
+    let (lo, up) = problem.domain();
+    let mut new_pos = pos.clone();
+    let mut new_vel = vel.clone();
+
+    // Check wall.
+    for (i, e) in new_pos.iter_mut().enumerate() {
+      if pos[i] + vel[i] < lo {
+        *e = 2. * lo - vel[i] - pos[i];
+        new_vel[i] *= -self.damping;
+      } else if pos[i] + vel[i] > up {
+        *e = 2. * up - vel[i] - pos[i];
+        new_vel[i] *= -self.damping;
+      } else {
+        *e = pos[i] + vel[i];
+      }
+    }
+
+    // Set new velocity, as it may have hit a wall
+    (new_pos, Some(new_vel))
+  }
+}
+
+// Lets the particle leave the domain.
+pub struct Pass;
+
+impl Boundary for Pass {
+  fn apply(
+    &self,
+    pos: &DVector<f64>,
+    vel: &DVector<f64>,
+    _problem: &mut Problem,
+  ) -> (DVector<f64>, Option<DVector<f64>>) {
+    (pos + vel, None)
+  }
+}
+
+// Wraps the particle around to the opposite side of the domain.
+pub struct Cycle;
+
+impl Boundary for Cycle {
+  fn apply(
+    &self,
+    pos: &DVector<f64>,
+    vel: &DVector<f64>,
+    problem: &mut Problem,
+  ) -> (DVector<f64>, Option<DVector<f64>>) {
+    let (lo, up) = problem.domain();
+    let mut new_pos = pos + vel;
+
+    // Check wall.
+    let width = up - lo;
+    for e in new_pos.iter_mut() {
+      while *e < lo {
+        *e += width;
+      }
+      while *e > up {
+        *e -= width;
+      }
+    }
+    (new_pos, None)
+  }
+}
+
+// Stops the particle on the wall and zeroes the velocity component.
+pub struct Absorb;
+
+impl Boundary for Absorb {
+  fn apply(
+    &self,
+    pos: &DVector<f64>,
+    vel: &DVector<f64>,
+    problem: &mut Problem,
+  ) -> (DVector<f64>, Option<DVector<f64>>) {
+    let (lo, up) = problem.domain();
+    let mut new_pos = pos + vel;
+    let mut new_vel = vel.clone();
+    for (i, e) in new_pos.iter_mut().enumerate() {
+      if *e < lo || *e > up {
+        *e = e.clamp(lo, up);
+        new_vel[i] = 0.;
+      }
+    }
+    (new_pos, Some(new_vel))
+  }
+}
+
+// Re-samples the violating components uniformly within the domain.
+pub struct Random;
+
+impl Boundary for Random {
+  fn apply(
+    &self,
+    pos: &DVector<f64>,
+    vel: &DVector<f64>,
+    problem: &mut Problem,
+  ) -> (DVector<f64>, Option<DVector<f64>>) {
+    let mut rng = rand::thread_rng();
+    let (lo, up) = problem.domain();
+    let mut new_pos = pos + vel;
+    for e in new_pos.iter_mut() {
+      if *e < lo || *e > up {
+        *e = rng.gen_range(lo..up);
+      }
+    }
+    (new_pos, None)
+  }
+}
+
+// Moves the violating components halfway between the old position and the wall.
+pub struct Midpoint;
+
+impl Boundary for Midpoint {
+  fn apply(
+    &self,
+    pos: &DVector<f64>,
+    vel: &DVector<f64>,
+    problem: &mut Problem,
+  ) -> (DVector<f64>, Option<DVector<f64>>) {
+    let (lo, up) = problem.domain();
+    let mut new_pos = pos + vel;
+    for (i, e) in new_pos.iter_mut().enumerate() {
+      if *e < lo {
+        *e = (pos[i] + lo) / 2.;
+      } else if *e > up {
+        *e = (pos[i] + up) / 2.;
+      }
+    }
+    (new_pos, None)
+  }
+}
+
+// Shrinks the velocity as the particle approaches the wall, so it never leaves the domain.
+pub struct Hyperbolic;
+
+impl Boundary for Hyperbolic {
+  fn apply(
+    &self,
+    pos: &DVector<f64>,
+    vel: &DVector<f64>,
+    problem: &mut Problem,
+  ) -> (DVector<f64>, Option<DVector<f64>>) {
+    let (lo, up) = problem.domain();
+    let mut new_vel = vel.clone();
+    for (i, v) in new_vel.iter_mut().enumerate() {
+      if *v > 0. {
+        *v /= 1. + *v / (up - pos[i]).max(f64::EPSILON);
+      } else if *v < 0. {
+        *v /= 1. - *v / (pos[i] - lo).max(f64::EPSILON);
+      }
+    }
+    (pos + &new_vel, Some(new_vel))
+  }
+}
+
+// Lets the particle leave the domain, but its fitness there is not evaluated.
+pub struct Infinity;
+
+impl Boundary for Infinity {
+  fn apply(
+    &self,
+    pos: &DVector<f64>,
+    vel: &DVector<f64>,
+    _problem: &mut Problem,
+  ) -> (DVector<f64>, Option<DVector<f64>>) {
+    (pos + vel, None)
+  }
+
+  // Infeasible particles are left out of the mass normalizers, which only see finite
+  // fitness values.
+  fn setup(&self, problem: &mut Problem) {
+    problem.set_infeasible_fitness(Some(f64::INFINITY));
+  }
+}
@@ -7,6 +7,7 @@ use problems::Problem;
 #[derive(Clone)]
 pub struct GsaParticle {
   pos: DVector<f64>,
+  fitness: Option<f64>,
   vel: DVector<f64>,
   mass: f64,
   behavior: Behavior,
@@ -16,6 +17,7 @@ impl Particle for GsaParticle {
   fn new(problem: &mut Problem, behavior: Behavior) -> GsaParticle {
     let mut particle = GsaParticle {
       pos: DVector::from_element(problem.dim(), 0.),
+      fitness: None,
       vel: DVector::from_element(problem.dim(), 0.),
       mass: 0.,
       behavior,
@@ -33,12 +35,21 @@ impl Position for GsaParticle {
 
   fn set_pos(&mut self, pos: DVector<f64>) {
     self.pos = pos;
+    self.fitness = None;
+  }
+
+  fn cached_fitness(&self) -> Option<f64> {
+    self.fitness
+  }
+
+  fn set_cached_fitness(&mut self, fitness: Option<f64>) {
+    self.fitness = fitness;
   }
 }
 
 impl Velocity for GsaParticle {
-  fn init(&mut self, problem: &mut Problem) {
-    self.update_vel(DVector::from_element(problem.dim(), 0.), problem);
+  fn default_vel(&self, problem: &Problem) -> DVector<f64> {
+    DVector::from_element(problem.dim(), 0.)
   }
 
   fn vel(&self) -> &DVector<f64> {
@@ -0,0 +1,505 @@
+extern crate nalgebra as na;
+use crate::problems;
+use crate::utils;
+use nalgebra::DVector;
+use problems::Problem;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_distr::{Distribution, Normal};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+// How initial positions and velocities are sampled.
+#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Init {
+  Uniform,
+  // Centered on the domain, with a quarter of the width as standard deviation, and
+  // truncated to the domain.
+  Gaussian,
+  LatinHypercube,
+  // Low-discrepancy sequences, randomly shifted with the seed of the sampler.
+  Sobol,
+  Halton,
+  // Keep the better of a uniform sample and its opposite. Positions only.
+  Opposition,
+  // Rows given to `Sampler::set_initial_positions`/`set_initial_velocities`, e.g. from
+  // the files of the `initial_positions`/`initial_velocities` parameters. Every row is
+  // used once, so particles added after they run out (e.g. by restarts) are uniform.
+  File,
+  Zero,
+}
+
+#[derive(Clone, Default)]
+struct Stream {
+  drawn: usize,
+  latin: Vec<DVector<f64>>,
+  loaded: Option<Arc<Vec<DVector<f64>>>>,
+  // Loaded rows used so far. Unlike `drawn`, it does not start over with a new population.
+  used: usize,
+}
+
+// Population-level state of the initializers, kept in the problem.
+#[derive(Clone)]
+pub struct Sampler {
+  population: usize,
+  seed: u64,
+  // Draws of the Gaussian and Latin hypercube initializers.
+  rng: StdRng,
+  pos: Stream,
+  vel: Stream,
+}
+
+impl Default for Sampler {
+  fn default() -> Self {
+    Sampler {
+      population: 0,
+      seed: 0,
+      rng: stream_rng(0, 2),
+      pos: Stream::default(),
+      vel: Stream::default(),
+    }
+  }
+}
+
+impl Sampler {
+  // Called before a population is created, so that sequences start over.
+  pub fn reset(&mut self, population: usize) {
+    self.population = population;
+    for stream in [&mut self.pos, &mut self.vel] {
+      stream.drawn = 0;
+      stream.latin = Vec::new();
+    }
+  }
+
+  // Seeds the shift of the low-discrepancy sequences and the Gaussian and Latin hypercube
+  // draws, e.g. with the seed of the attempt, so that attempts do not start from the
+  // same points.
+  pub fn set_seed(&mut self, seed: u64) {
+    self.seed = seed;
+    self.rng = stream_rng(seed, 2);
+  }
+
+  // Shift of every dimension of a stream, the same for the whole population.
+  fn shift(&self, stream: u64, dim: usize) -> DVector<f64> {
+    let mut rng = stream_rng(self.seed, stream);
+    DVector::from_fn(dim, |_, _| rng.gen_range(0.0..1.0))
+  }
+
+  pub fn set_initial_positions(&mut self, positions: Vec<DVector<f64>>) {
+    self.pos.loaded = Some(Arc::new(positions));
+  }
+
+  pub fn set_initial_velocities(&mut self, velocities: Vec<DVector<f64>>) {
+    self.vel.loaded = Some(Arc::new(velocities));
+  }
+}
+
+fn stream_rng(seed: u64, stream: u64) -> StdRng {
+  StdRng::seed_from_u64(seed ^ stream.wrapping_mul(0x9e37_79b9_7f4a_7c15))
+}
+
+impl Init {
+  // The fitness is given when the initializer had to evaluate the position.
+  pub fn sample_pos(&self, problem: &mut Problem) -> (DVector<f64>, Option<f64>) {
+    let (lo, up) = problem.domain();
+    let dim = problem.dim();
+    if *self == Init::Opposition {
+      let pos = utils::random_init_pos(problem);
+      let opposite = pos.map(|x| lo + up - x);
+      let (f_opposite, f_pos) = (problem.f(&opposite), problem.f(&pos));
+      return match f_opposite < f_pos {
+        true => (opposite, Some(f_opposite)),
+        false => (pos, Some(f_pos)),
+      };
+    }
+    let sampler = problem.sampler();
+    let shift = sampler.shift(0, dim);
+    let pos = sample(
+      *self,
+      &mut sampler.pos,
+      &mut sampler.rng,
+      sampler.population,
+      &shift,
+      lo,
+      up,
+    );
+    (pos, None)
+  }
+
+  pub fn sample_vel(&self, problem: &mut Problem) -> DVector<f64> {
+    let width = problem.domain().1 - problem.domain().0;
+    let dim = problem.dim();
+    assert!(
+      *self != Init::Opposition,
+      "Init::Opposition is not supported for velocities."
+    );
+    let sampler = problem.sampler();
+    let shift = sampler.shift(1, dim);
+    sample(
+      *self,
+      &mut sampler.vel,
+      &mut sampler.rng,
+      sampler.population,
+      &shift,
+      -width,
+      width,
+    )
+  }
+}
+
+fn sample(
+  init: Init,
+  stream: &mut Stream,
+  rng: &mut StdRng,
+  population: usize,
+  shift: &DVector<f64>,
+  lo: f64,
+  up: f64,
+) -> DVector<f64> {
+  let dim = shift.len();
+  let idx = stream.drawn;
+  stream.drawn += 1;
+  let uniform = || utils::uniform_distribution(&DVector::from_element(dim, 0.), &DVector::from_element(dim, 1.));
+  let unit = match init {
+    Init::Uniform | Init::Opposition => uniform(),
+    Init::Gaussian => {
+      // Samples outside of the domain are drawn again rather than clamped onto its walls.
+      let normal = Normal::new(0.5, 0.25).unwrap();
+      DVector::from_fn(dim, |_, _| loop {
+        let x: f64 = normal.sample(rng);
+        if (0. ..=1.).contains(&x) {
+          break x;
+        }
+      })
+    }
+    Init::LatinHypercube => {
+      // Particles beyond the population size (e.g. after resizing) start a new hypercube.
+      if idx.is_multiple_of(population.max(1)) {
+        stream.latin = latin_hypercube(population.max(1), dim, rng);
+      }
+      stream.latin[idx % population.max(1)].clone()
+    }
+    // The first point of both sequences is the corner of the domain, so it is skipped.
+    // The shift (modulo 1) keeps their uniformity while changing the points.
+    Init::Sobol => (sobol(idx + 1, dim) + shift).map(|u| u.fract()),
+    Init::Halton => (halton(idx + 1, dim) + shift).map(|u| u.fract()),
+    Init::File => {
+      let loaded = stream.loaded.as_ref().expect("No initial vectors were given to the problem.");
+      match loaded.get(stream.used) {
+        Some(vector) => {
+          assert!(
+            vector.len() == dim,
+            "Initial vector {} should have {} elements.",
+            stream.used,
+            dim
+          );
+          stream.used += 1;
+          return vector.clone();
+        }
+        None => uniform(),
+      }
+    }
+    Init::Zero => return DVector::from_element(dim, 0.),
+  };
+  unit.map(|u| lo + (up - lo) * u)
+}
+
+fn latin_hypercube(n: usize, dim: usize, rng: &mut StdRng) -> Vec<DVector<f64>> {
+  let mut points = vec![DVector::from_element(dim, 0.); n];
+  for d in 0..dim {
+    let mut strata: Vec<usize> = (0..n).collect();
+    strata.shuffle(rng);
+    for (point, stratum) in points.iter_mut().zip(strata) {
+      point[d] = (stratum as f64 + rng.gen_range(0.0..1.0)) / n as f64;
+    }
+  }
+  points
+}
+
+fn halton(idx: usize, dim: usize) -> DVector<f64> {
+  let primes = first_primes(dim);
+  DVector::from_iterator(
+    dim,
+    primes.iter().map(|&base| {
+      // Radical inverse of idx in the given base.
+      let (mut i, mut f, mut result) = (idx, 1., 0.);
+      while i > 0 {
+        f /= base as f64;
+        result += f * (i % base) as f64;
+        i /= base;
+      }
+      result
+    }),
+  )
+}
+
+fn first_primes(n: usize) -> Vec<usize> {
+  let mut primes = Vec::new();
+  let mut candidate = 2;
+  while primes.len() < n {
+    if primes.iter().all(|p| candidate % p != 0) {
+      primes.push(candidate);
+    }
+    candidate += 1;
+  }
+  primes
+}
+
+const SOBOL_BITS: usize = 32;
+
+// Degree s, coefficients a and initial direction numbers m of the dimensions after the
+// first, from the new-joe-kuo-6.21201 table of Joe and Kuo.
+const JOE_KUO: [(usize, u64, &[u64]); 20] = [
+  (1, 0, &[1]),
+  (2, 1, &[1, 3]),
+  (3, 1, &[1, 3, 1]),
+  (3, 2, &[1, 1, 1]),
+  (4, 1, &[1, 1, 3, 3]),
+  (4, 4, &[1, 3, 5, 13]),
+  (5, 2, &[1, 1, 5, 5, 17]),
+  (5, 4, &[1, 1, 5, 5, 5]),
+  (5, 7, &[1, 1, 7, 11, 19]),
+  (5, 11, &[1, 1, 5, 1, 1]),
+  (5, 13, &[1, 1, 1, 3, 11]),
+  (5, 14, &[1, 3, 5, 5, 31]),
+  (6, 1, &[1, 3, 3, 9, 7, 49]),
+  (6, 13, &[1, 1, 1, 15, 21, 21]),
+  (6, 16, &[1, 3, 1, 13, 27, 49]),
+  (6, 19, &[1, 1, 1, 15, 7, 5]),
+  (6, 22, &[1, 3, 1, 15, 13, 25]),
+  (6, 25, &[1, 1, 5, 5, 19, 61]),
+  (7, 1, &[1, 3, 7, 11, 23, 15, 103]),
+  (7, 4, &[1, 3, 7, 13, 13, 15, 69]),
+];
+
+// Sobol point via the Gray code construction, with the direction numbers of Joe and Kuo.
+// Dimensions beyond the table use the next primitive polynomials, with fixed odd
+// initial values drawn from a seeded generator.
+fn sobol(idx: usize, dim: usize) -> DVector<f64> {
+  let gray = idx ^ (idx >> 1);
+  let polynomials = primitive_polynomials(dim.saturating_sub(1));
+  let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+  DVector::from_iterator(
+    dim,
+    (0..dim).map(|d| {
+      let directions = match d {
+        0 => (1..=SOBOL_BITS).map(|k| 1u64 << (SOBOL_BITS - k)).collect(),
+        _ if d <= JOE_KUO.len() => {
+          let (degree, a, m) = JOE_KUO[d - 1];
+          sobol_directions((1 << degree) | (a << 1) | 1, m)
+        }
+        _ => {
+          let degree = 63 - polynomials[d - 1].leading_zeros() as usize;
+          sobol_directions(polynomials[d - 1], &random_initial_values(degree, &mut seed))
+        }
+      };
+      let mut x = 0u64;
+      for (k, v) in directions.iter().enumerate() {
+        if (gray >> k) & 1 == 1 {
+          x ^= v;
+        }
+      }
+      x as f64 / (1u64 << SOBOL_BITS) as f64
+    }),
+  )
+}
+
+// Any odd m_k < 2^k is a valid initial direction number.
+fn random_initial_values(degree: usize, seed: &mut u64) -> Vec<u64> {
+  (1..=degree)
+    .map(|k| {
+      *seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+      ((*seed >> 33) % (1 << (k - 1))) * 2 + 1
+    })
+    .collect()
+}
+
+// Direction numbers of a dimension from its primitive polynomial and initial values.
+fn sobol_directions(polynomial: u64, initial: &[u64]) -> Vec<u64> {
+  let degree = 63 - polynomial.leading_zeros() as usize;
+  let mut m: Vec<u64> = initial.to_vec();
+  for k in degree + 1..=SOBOL_BITS {
+    let mut value = m[k - 1 - degree] ^ (m[k - 1 - degree] << degree);
+    for j in 1..degree {
+      if (polynomial >> (degree - j)) & 1 == 1 {
+        value ^= m[k - 1 - j] << j;
+      }
+    }
+    m.push(value);
+  }
+  m.iter().enumerate().map(|(k, m_k)| m_k << (SOBOL_BITS - k - 1)).collect()
+}
+
+// The first n primitive polynomials over GF(2), as bit masks ordered by degree.
+fn primitive_polynomials(n: usize) -> Vec<u64> {
+  let mut polynomials = Vec::new();
+  let mut degree = 1;
+  while polynomials.len() < n {
+    for polynomial in (1u64 << degree)..(1u64 << (degree + 1)) {
+      if polynomials.len() == n {
+        break;
+      }
+      if polynomial & 1 == 1 && is_primitive(polynomial, degree) {
+        polynomials.push(polynomial);
+      }
+    }
+    degree += 1;
+  }
+  polynomials
+}
+
+fn is_primitive(polynomial: u64, degree: usize) -> bool {
+  let order = (1u64 << degree) - 1;
+  if pow_mod(2, order, polynomial, degree) != 1 {
+    return false;
+  }
+  // x must not have a smaller order, i.e. one of order / q for a prime factor q.
+  let mut rest = order;
+  let mut q = 2;
+  while rest > 1 {
+    if rest.is_multiple_of(q) {
+      if pow_mod(2, order / q, polynomial, degree) == 1 {
+        return false;
+      }
+      while rest.is_multiple_of(q) {
+        rest /= q;
+      }
+    }
+    q += 1;
+  }
+  true
+}
+
+// base^exp modulo the polynomial, with polynomials over GF(2) as bit masks.
+fn pow_mod(base: u64, mut exp: u64, polynomial: u64, degree: usize) -> u64 {
+  let mul_mod = |a: u64, b: u64| {
+    let mut result = 0u64;
+    let mut a = a;
+    let mut b = b;
+    while b > 0 {
+      if b & 1 == 1 {
+        result ^= a;
+      }
+      b >>= 1;
+      a <<= 1;
+      if (a >> degree) & 1 == 1 {
+        a ^= polynomial;
+      }
+    }
+    result
+  };
+  let mut result = 1u64;
+  let mut base = base;
+  if (base >> degree) & 1 == 1 {
+    base ^= polynomial;
+  }
+  while exp > 0 {
+    if exp & 1 == 1 {
+      result = mul_mod(result, base);
+    }
+    base = mul_mod(base, base);
+    exp >>= 1;
+  }
+  result
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn joe_kuo_table_is_valid() {
+    let polynomials = primitive_polynomials(JOE_KUO.len());
+    for ((degree, a, m), polynomial) in JOE_KUO.iter().zip(polynomials) {
+      assert_eq!((1 << degree) | (a << 1) | 1, polynomial);
+      assert_eq!(m.len(), *degree);
+      for (k, m_k) in m.iter().enumerate() {
+        assert!(m_k % 2 == 1 && *m_k < 1 << (k + 1));
+      }
+    }
+  }
+
+  #[test]
+  fn sobol_starts_with_known_points() {
+    let expected = [
+      [0., 0., 0.],
+      [0.5, 0.5, 0.5],
+      [0.75, 0.25, 0.25],
+      [0.25, 0.75, 0.75],
+      [0.375, 0.375, 0.625],
+      [0.875, 0.875, 0.125],
+      [0.625, 0.125, 0.875],
+      [0.125, 0.625, 0.375],
+    ];
+    for (idx, point) in expected.iter().enumerate() {
+      assert_eq!(sobol(idx, 3), DVector::from_row_slice(point));
+    }
+  }
+
+  #[test]
+  fn sobol_projections_are_stratified() {
+    let n = 64;
+    let dim = JOE_KUO.len() + 3;
+    let points: Vec<DVector<f64>> = (0..n).map(|idx| sobol(idx, dim)).collect();
+    for d in 0..dim {
+      let mut strata: Vec<usize> = points.iter().map(|p| (p[d] * n as f64) as usize).collect();
+      strata.sort_unstable();
+      assert_eq!(strata, (0..n).collect::<Vec<usize>>(), "dimension {}", d);
+    }
+  }
+
+  #[test]
+  fn gaussian_stays_in_the_domain() {
+    let mut problem = problems::sphere_100(5);
+    for _ in 0..1000 {
+      let (pos, _) = Init::Gaussian.sample_pos(&mut problem);
+      assert!(problem.contains(&pos));
+    }
+  }
+
+  #[test]
+  fn opposition_keeps_the_fitness_of_the_better_point() {
+    let mut problem = problems::sphere_100(5);
+    for evaluations in (2..=20).step_by(2) {
+      let (pos, fitness) = Init::Opposition.sample_pos(&mut problem);
+      assert_eq!(problem.cnt(), evaluations);
+      let opposite = pos.map(|x| -x);
+      assert_eq!(fitness, Some(problem.f_export(&pos)));
+      assert!(fitness.unwrap() <= problem.f_export(&opposite));
+    }
+  }
+
+  #[test]
+  fn loaded_vectors_are_used_once() {
+    let loaded = vec![DVector::from_element(5, 1.), DVector::from_element(5, 2.)];
+    let mut problem = problems::sphere_100(5);
+    problem.sampler().set_initial_positions(loaded.clone());
+    problem.sampler().reset(2);
+    assert_eq!(Init::File.sample_pos(&mut problem).0, loaded[0]);
+    assert_eq!(Init::File.sample_pos(&mut problem).0, loaded[1]);
+
+    // A grown or restarted population does not start over with the same rows.
+    problem.sampler().reset(3);
+    for _ in 0..3 {
+      let (pos, _) = Init::File.sample_pos(&mut problem);
+      assert!(problem.contains(&pos));
+      assert!(!loaded.contains(&pos));
+    }
+  }
+
+  #[test]
+  fn samples_repeat_with_the_seed() {
+    let first = |init: Init, seed: u64| {
+      let mut problem = problems::sphere_100(5);
+      problem.sampler().set_seed(seed);
+      problem.sampler().reset(10);
+      init.sample_pos(&mut problem).0
+    };
+    for init in [Init::Sobol, Init::Halton, Init::Gaussian, Init::LatinHypercube] {
+      assert_eq!(first(init, 1), first(init, 1));
+      assert_ne!(first(init, 1), first(init, 2));
+    }
+  }
+}
@@ -1,67 +1,4 @@
-extern crate nalgebra as na;
-use crate::particles::traits::{Behavior, BehaviorTrait, Mass, Particle, Position, Velocity};
-use crate::problems;
-use nalgebra::DVector;
-use problems::Problem;
+use crate::particles::gsa::GsaParticle;
 
-#[derive(Clone)]
-pub struct MgsaParticle {
-  pos: DVector<f64>,
-  vel: DVector<f64>,
-  mass: f64,
-  behavior: Behavior,
-}
-
-impl Particle for MgsaParticle {
-  fn new(problem: &mut Problem, behavior: Behavior) -> MgsaParticle {
-    let mut particle = MgsaParticle {
-      pos: DVector::from_element(problem.dim(), 0.),
-      vel: DVector::from_element(problem.dim(), 0.),
-      mass: 0.,
-      behavior,
-    };
-    Position::init(&mut particle, problem);
-    Velocity::init(&mut particle, problem);
-    particle
-  }
-}
-
-impl Position for MgsaParticle {
-  fn pos(&self) -> &DVector<f64> {
-    &self.pos
-  }
-
-  fn set_pos(&mut self, pos: DVector<f64>) {
-    self.pos = pos;
-  }
-}
-
-impl Velocity for MgsaParticle {
-  fn init(&mut self, problem: &mut Problem) {
-    self.update_vel(DVector::from_element(problem.dim(), 0.), problem);
-  }
-
-  fn vel(&self) -> &DVector<f64> {
-    &self.vel
-  }
-
-  fn set_vel(&mut self, vel: DVector<f64>) {
-    self.vel = vel;
-  }
-}
-
-impl Mass for MgsaParticle {
-  fn set_mass(&mut self, mass: f64) {
-    self.mass = mass;
-  }
-
-  fn mass(&self) -> f64 {
-    self.mass
-  }
-}
-
-impl BehaviorTrait for MgsaParticle {
-  fn behavior(&self) -> Behavior {
-    self.behavior
-  }
-}
+// MGSA particles carry the same state as GSA ones.
+pub type MgsaParticle = GsaParticle;
@@ -1,5 +1,13 @@
+pub mod abc;
+pub mod basic;
+pub mod bat;
+pub mod boundary;
+pub mod cmaes;
 pub mod gsa;
+pub mod initializer;
 pub mod mgsa;
 pub mod pso;
-pub mod traits;
+pub mod psogsa;
 pub mod rgsa;
+pub mod swarm;
+pub mod traits;
@@ -7,8 +7,10 @@ use problems::Problem;
 #[derive(Clone)]
 pub struct PsoParticle {
   pos: DVector<f64>,
+  fitness: Option<f64>,
   vel: DVector<f64>,
   best_pos: Option<DVector<f64>>,
+  best_fitness: Option<f64>,
   behavior: Behavior,
 }
 
@@ -16,8 +18,10 @@ impl Particle for PsoParticle {
   fn new(problem: &mut Problem, behavior: Behavior) -> PsoParticle {
     let mut particle = PsoParticle {
       pos: DVector::from_element(problem.dim(), 0.),
+      fitness: None,
       vel: DVector::from_element(problem.dim(), 0.),
       best_pos: None,
+      best_fitness: None,
       behavior,
     };
     Position::init(&mut particle, problem);
@@ -34,6 +38,15 @@ impl Position for PsoParticle {
 
   fn set_pos(&mut self, pos: DVector<f64>) {
     self.pos = pos;
+    self.fitness = None;
+  }
+
+  fn cached_fitness(&self) -> Option<f64> {
+    self.fitness
+  }
+
+  fn set_cached_fitness(&mut self, fitness: Option<f64>) {
+    self.fitness = fitness;
   }
 }
 
@@ -49,6 +62,14 @@ impl BestPosition for PsoParticle {
   fn set_best_pos(&mut self, pos: DVector<f64>) {
     self.best_pos = Some(pos);
   }
+
+  fn best_fitness(&self) -> Option<f64> {
+    self.best_fitness
+  }
+
+  fn set_best_fitness(&mut self, fitness: Option<f64>) {
+    self.best_fitness = fitness;
+  }
 }
 
 impl Velocity for PsoParticle {
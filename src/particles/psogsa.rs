@@ -0,0 +1,105 @@
+extern crate nalgebra as na;
+use crate::particles::traits::{Behavior, BehaviorTrait, BestPosition, Mass, Particle, Position, Velocity};
+use crate::problems;
+use nalgebra::DVector;
+use problems::Problem;
+
+#[derive(Clone)]
+pub struct PsogsaParticle {
+  pos: DVector<f64>,
+  fitness: Option<f64>,
+  vel: DVector<f64>,
+  best_pos: Option<DVector<f64>>,
+  best_fitness: Option<f64>,
+  mass: f64,
+  behavior: Behavior,
+}
+
+impl Particle for PsogsaParticle {
+  fn new(problem: &mut Problem, behavior: Behavior) -> PsogsaParticle {
+    let mut particle = PsogsaParticle {
+      pos: DVector::from_element(problem.dim(), 0.),
+      fitness: None,
+      vel: DVector::from_element(problem.dim(), 0.),
+      best_pos: None,
+      best_fitness: None,
+      mass: 0.,
+      behavior,
+    };
+    Position::init(&mut particle, problem);
+    BestPosition::init(&mut particle);
+    Velocity::init(&mut particle, problem);
+    particle
+  }
+}
+
+impl Position for PsogsaParticle {
+  fn pos(&self) -> &DVector<f64> {
+    &self.pos
+  }
+
+  fn set_pos(&mut self, pos: DVector<f64>) {
+    self.pos = pos;
+    self.fitness = None;
+  }
+
+  fn cached_fitness(&self) -> Option<f64> {
+    self.fitness
+  }
+
+  fn set_cached_fitness(&mut self, fitness: Option<f64>) {
+    self.fitness = fitness;
+  }
+}
+
+impl BestPosition for PsogsaParticle {
+  fn best_pos(&self) -> DVector<f64> {
+    self.best_pos.clone().unwrap()
+  }
+
+  fn option_best_pos(&self) -> &Option<DVector<f64>> {
+    &self.best_pos
+  }
+
+  fn set_best_pos(&mut self, pos: DVector<f64>) {
+    self.best_pos = Some(pos);
+  }
+
+  fn best_fitness(&self) -> Option<f64> {
+    self.best_fitness
+  }
+
+  fn set_best_fitness(&mut self, fitness: Option<f64>) {
+    self.best_fitness = fitness;
+  }
+}
+
+impl Velocity for PsogsaParticle {
+  fn default_vel(&self, problem: &Problem) -> DVector<f64> {
+    DVector::from_element(problem.dim(), 0.)
+  }
+
+  fn vel(&self) -> &DVector<f64> {
+    &self.vel
+  }
+
+  fn set_vel(&mut self, vel: DVector<f64>) {
+    self.vel = vel;
+  }
+}
+
+impl Mass for PsogsaParticle {
+  fn set_mass(&mut self, mass: f64) {
+    self.mass = mass;
+  }
+
+  fn mass(&self) -> f64 {
+    self.mass
+  }
+}
+
+impl BehaviorTrait for PsogsaParticle {
+  fn behavior(&self) -> Behavior {
+    self.behavior
+  }
+}
@@ -0,0 +1,77 @@
+extern crate nalgebra as na;
+use crate::particles::traits::Velocity;
+use nalgebra::{DMatrix, DVector};
+use std::borrow::Borrow;
+
+// Snapshot of the positions and velocities of the particles, one row per particle,
+// for the GSA forces and PSO velocities computed with matrix operations. It is taken
+// again at every iteration, at O(N·D) next to the O(N²·D) of the forces, and never
+// written back: the particles hold the state of the swarm.
+#[derive(Clone, Debug)]
+pub struct Swarm {
+  pub pos: DMatrix<f64>,
+  pub vel: DMatrix<f64>,
+}
+
+impl Swarm {
+  pub fn from_particles<T: Velocity>(particles: &[T]) -> Swarm {
+    let positions: Vec<&DVector<f64>> = particles.iter().map(|p| p.pos()).collect();
+    let velocities: Vec<&DVector<f64>> = particles.iter().map(|p| p.vel()).collect();
+    Swarm {
+      pos: stack(&positions),
+      vel: stack(&velocities),
+    }
+  }
+
+  // Euclidean distances from the `rows` particles to the `columns` particles. With
+  // `tiled_width`, the domain wraps around and the closest image is used.
+  pub fn distances(&self, rows: &[usize], columns: &[usize], tiled_width: Option<f64>) -> DMatrix<f64> {
+    let mut distances = DMatrix::<f64>::zeros(rows.len(), columns.len());
+    for x in self.pos.column_iter() {
+      for (c, &j) in columns.iter().enumerate() {
+        for (r, &i) in rows.iter().enumerate() {
+          let d = match tiled_width {
+            Some(width) => closest_image(x[j] - x[i], width),
+            None => x[j] - x[i],
+          };
+          distances[(r, c)] += d * d;
+        }
+      }
+    }
+    distances.map(f64::sqrt)
+  }
+}
+
+// Stacks vectors of equal length as the rows of a matrix.
+pub fn stack<V: Borrow<DVector<f64>>>(vectors: &[V]) -> DMatrix<f64> {
+  let dim = vectors.first().map_or(0, |v| v.borrow().len());
+  let mut matrix = DMatrix::zeros(vectors.len(), dim);
+  for (i, v) in vectors.iter().enumerate() {
+    matrix.row_mut(i).tr_copy_from(v.borrow());
+  }
+  matrix
+}
+
+// Multiplies every row of the matrix by the matching factor.
+pub fn scale_rows(matrix: &mut DMatrix<f64>, factors: &DVector<f64>) {
+  for mut column in matrix.column_iter_mut() {
+    column.component_mul_assign(factors);
+  }
+}
+
+// Displacement `r` along one axis, moved to the closest image when the domain
+// wraps around with the given width.
+pub fn closest_image(r: f64, width: f64) -> f64 {
+  if (r - width).abs() < r.abs() {
+    r - width
+  } else if (r + width).abs() < r.abs() {
+    r + width
+  } else {
+    r
+  }
+}
+
+// Splits a matrix into its rows, as column vectors.
+pub fn rows(matrix: &DMatrix<f64>) -> Vec<DVector<f64>> {
+  matrix.row_iter().map(|row| row.transpose()).collect()
+}
@@ -1,4 +1,6 @@
 extern crate nalgebra as na;
+use crate::particles::boundary::Boundary;
+use crate::particles::initializer::Init;
 use crate::problems;
 use crate::utils;
 use nalgebra::DVector;
@@ -8,7 +10,46 @@ use serde::{Deserialize, Serialize};
 #[derive(Clone, Copy, Serialize, Deserialize)]
 pub struct Behavior {
   pub edge: Edge,
-  pub vmax: bool,
+  // None keeps the default of the optimizer: `Component(1.)` for PSO, no limit otherwise.
+  pub vmax: Option<Vmax>,
+  pub pos_init: Init,
+  // None keeps the default of the particle type.
+  pub vel_init: Option<Init>,
+}
+
+// Velocity limits, as fractions of the domain width.
+#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Vmax {
+  None,
+  // Limit the norm of the whole velocity vector.
+  Norm(f64),
+  // Limit every component separately.
+  Component(f64),
+  // Per-component limit that decays linearly over the given number of iterations.
+  Decay { from: f64, to: f64, iterations: usize },
+}
+
+impl Vmax {
+  pub fn clamp(&self, vel: DVector<f64>, problem: &Problem) -> DVector<f64> {
+    let width = problem.domain().1 - problem.domain().0;
+    match *self {
+      Vmax::None => vel,
+      Vmax::Norm(k) => {
+        if vel.norm() > k * width {
+          vel.clone() / vel.norm() * k * width
+        } else {
+          vel
+        }
+      }
+      Vmax::Component(k) => vel.map(|e| e.clamp(-k * width, k * width)),
+      Vmax::Decay { from, to, iterations } => {
+        let ratio = (problem.iteration() as f64 / iterations as f64).min(1.);
+        let k = from + (to - from) * ratio;
+        vel.map(|e| e.clamp(-k * width, k * width))
+      }
+    }
+  }
 }
 
 #[allow(dead_code)]
@@ -17,96 +58,100 @@ pub enum Edge {
   Reflect,
   Pass,
   Cycle,
+  Absorb,
+  Random,
+  Midpoint,
+  // Reflect, with the velocity scaled by the given factor.
+  DampedReflect(f64),
+  Hyperbolic,
+  Infinity,
 }
 
 pub trait Particle {
   fn new(problem: &mut Problem, behavior: Behavior) -> Self;
 }
 
-pub trait Position {
+pub trait Position: BehaviorTrait {
   fn init(&mut self, problem: &mut Problem) {
-    self.set_pos(utils::random_init_pos(problem));
+    let (pos, fitness) = self.behavior().pos_init.sample_pos(problem);
+    self.set_pos(pos);
+    self.set_cached_fitness(fitness);
   }
 
   fn pos(&self) -> &DVector<f64>;
+  // Also clears the cached fitness.
   fn set_pos(&mut self, pos: DVector<f64>);
+
+  // Objective value at the current position, if it was evaluated since the last move.
+  fn cached_fitness(&self) -> Option<f64>;
+  fn set_cached_fitness(&mut self, fitness: Option<f64>);
+  fn fitness(&mut self, problem: &mut Problem) -> f64 {
+    match self.cached_fitness() {
+      Some(fitness) => fitness,
+      None => {
+        let fitness = problem.f(self.pos());
+        self.set_cached_fitness(Some(fitness));
+        fitness
+      }
+    }
+  }
 }
 
 pub trait BestPosition: Position {
+  // The fitness of the initial best is unknown, so the first update always takes the
+  // current position.
   fn init(&mut self) {
     self.set_best_pos(self.pos().clone());
+    self.set_best_fitness(None);
   }
 
   fn best_pos(&self) -> DVector<f64>;
+  #[allow(dead_code)]
   fn option_best_pos(&self) -> &Option<DVector<f64>>;
   fn set_best_pos(&mut self, pos: DVector<f64>);
+  fn best_fitness(&self) -> Option<f64>;
+  fn set_best_fitness(&mut self, fitness: Option<f64>);
 
   fn update_best_pos(&mut self, problem: &mut Problem) {
-    // This function returns whether the personal best was updated.
-    if self.option_best_pos().is_none() || problem.f(self.pos()) < problem.f(&self.best_pos()) {
+    let fitness = self.fitness(problem);
+    if self.best_fitness().is_none_or(|best| fitness < best) {
       self.set_best_pos(self.pos().clone());
+      self.set_best_fitness(Some(fitness));
     }
   }
 }
 
 pub trait Velocity: Position + BehaviorTrait {
   fn init(&mut self, problem: &mut Problem) {
-    self.update_vel(utils::random_init_vel(problem), problem);
+    let vel = match self.behavior().vel_init {
+      Some(init) => init.sample_vel(problem),
+      None => self.default_vel(problem),
+    };
+    self.update_vel(vel, problem);
+  }
+
+  // Initial velocity when `Behavior::vel_init` is not set.
+  fn default_vel(&self, problem: &Problem) -> DVector<f64> {
+    utils::random_init_vel(problem)
   }
 
   fn vel(&self) -> &DVector<f64>;
   fn set_vel(&mut self, vel: DVector<f64>);
   fn update_vel(&mut self, vel: DVector<f64>, problem: &mut Problem) {
-    let vmax = problem.domain().1 - problem.domain().0;
-    if self.behavior().vmax && vel.norm() > vmax {
-      self.set_vel(vel.clone() / vel.norm() * vmax);
-    } else {
-      self.set_vel(vel);
-    }
+    let vmax = self.behavior().vmax.unwrap_or(Vmax::None);
+    self.set_vel(vmax.clamp(vel, problem));
   }
 
   fn move_pos(&mut self, problem: &mut Problem) {
-    match self.behavior().edge {
-      Edge::Reflect => {
-        let mut new_pos = self.pos().clone();
-        let mut new_vel = self.vel().clone();
-
-        // Check wall.
-        for (i, e) in new_pos.iter_mut().enumerate() {
-          if self.pos()[i] + self.vel()[i] < problem.domain().0 {
-            *e = 2. * problem.domain().0 - self.vel()[i] - self.pos()[i];
-            new_vel[i] = -new_vel[i];
-          } else if self.pos()[i] + self.vel()[i] > problem.domain().1 {
-            *e = 2. * problem.domain().1 - self.vel()[i] - self.pos()[i];
-            new_vel[i] = -new_vel[i];
-          } else {
-            *e = self.pos()[i] + self.vel()[i];
-          }
-        }
-        self.set_pos(new_pos);
-
-        // Set new velocity, as it may have hit a wall
-        self.update_vel(new_vel, problem);
-      }
-      Edge::Pass => {
-        self.set_pos(self.pos().clone() + self.vel().clone());
-      }
-      Edge::Cycle => {
-        let mut new_pos = self.pos().clone() + self.vel().clone();
-
-        // Check wall.
-        let width = problem.domain().1 - problem.domain().0;
-        for e in new_pos.iter_mut() {
-          while *e < problem.domain().0 {
-            *e += width;
-          }
-          while *e > problem.domain().1 {
-            *e -= width;
-          }
-        }
+    let (lo, up) = problem.domain();
+    if (self.pos() + self.vel()).iter().any(|&e| e < lo || e > up) {
+      problem.add_out_of_bounds();
+    }
 
-        self.set_pos(new_pos);
-      }
+    let (new_pos, new_vel) = self.behavior().edge.apply(self.pos(), self.vel(), problem);
+    self.set_pos(new_pos);
+    if let Some(new_vel) = new_vel {
+      self.update_vel(new_vel, problem);
     }
   }
 }
@@ -116,6 +161,19 @@ pub trait Mass: BehaviorTrait {
   fn set_mass(&mut self, mass: f64);
 }
 
+pub trait Echolocation: BehaviorTrait {
+  fn loudness(&self) -> f64;
+  fn set_loudness(&mut self, loudness: f64);
+  fn pulse_rate(&self) -> f64;
+  fn set_pulse_rate(&mut self, pulse_rate: f64);
+}
+
+pub trait Trial: BehaviorTrait {
+  // Number of consecutive updates that failed to improve the particle.
+  fn trial(&self) -> usize;
+  fn set_trial(&mut self, trial: usize);
+}
+
 pub trait BehaviorTrait {
   fn behavior(&self) -> Behavior;
 }
@@ -1,15 +1,31 @@
 extern crate nalgebra as na;
+use crate::bbob::Bbob;
 use crate::functions;
+use crate::particles::initializer::Sampler;
+use rayon::prelude::*;
 use std::collections::hash_map::DefaultHasher;
 use std::sync::Arc;
 use std::{
-  collections::HashMap,
+  collections::{BTreeMap, HashMap, HashSet},
   hash::{Hash, Hasher},
 };
 
 use nalgebra::DVector;
 
 type OptimizationFunction = Arc<dyn Fn(&DVector<f64>) -> f64 + Sync + Send>;
+type BatchFunction = Arc<dyn Fn(&[DVector<f64>]) -> Vec<f64> + Sync + Send>;
+
+// How `Problem::f_batch` evaluates the vectors that are not memoized yet.
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Evaluation {
+  Serial,
+  // Not for the CEC functions, whose C code keeps global state between calls.
+  Parallel,
+  // One call to the batch function of the problem, e.g. a single FFI call. Falls
+  // back to `Serial` for problems without one.
+  Native,
+}
 
 #[derive(Debug, Clone)]
 struct HashableDVectorF64ForMemo(DVector<f64>);
@@ -38,150 +54,385 @@ impl PartialEq for HashableDVectorF64ForMemo {
 
 impl Eq for HashableDVectorF64ForMemo {}
 
+// Bounded cache of objective values that evicts the least recently used entry.
 #[derive(Clone)]
-pub struct Problem {
-  #[allow(dead_code)]
+struct Memo {
+  capacity: usize,
+  tick: u64,
+  entries: HashMap<HashableDVectorF64ForMemo, (f64, u64)>,
+  order: BTreeMap<u64, HashableDVectorF64ForMemo>,
+}
+
+impl Memo {
+  fn new(capacity: usize) -> Memo {
+    Memo {
+      capacity,
+      tick: 0,
+      entries: HashMap::new(),
+      order: BTreeMap::new(),
+    }
+  }
+
+  fn get(&mut self, key: &HashableDVectorF64ForMemo) -> Option<f64> {
+    let (value, last_used) = self.entries.get_mut(key)?;
+    self.order.remove(last_used);
+    self.tick += 1;
+    *last_used = self.tick;
+    self.order.insert(self.tick, key.clone());
+    Some(*value)
+  }
+
+  fn insert(&mut self, key: HashableDVectorF64ForMemo, value: f64) {
+    if self.capacity == 0 || self.get(&key).is_some() {
+      return;
+    }
+    if self.entries.len() == self.capacity {
+      if let Some((_, oldest)) = self.order.pop_first() {
+        self.entries.remove(&oldest);
+      }
+    }
+    self.tick += 1;
+    self.order.insert(self.tick, key.clone());
+    self.entries.insert(key, (value, self.tick));
+  }
+}
+
+// Immutable definition of a problem. It is shared by every run through an `Arc`.
+pub struct ProblemDef {
   name: String,
   f: OptimizationFunction,
+  batch_f: Option<BatchFunction>,
   domain: (f64, f64),
   dim: usize,
-  cnt: usize,
-  memo: HashMap<HashableDVectorF64ForMemo, f64>,
+  // Known minimum of the objective, if any.
+  optimum: Option<f64>,
 }
 
-impl Problem {
+impl ProblemDef {
   pub fn new(name: String, f: OptimizationFunction, domain: (f64, f64), dim: usize) -> Self {
     Self {
       name,
       f,
+      batch_f: None,
       domain,
       dim,
+      optimum: None,
+    }
+  }
+
+  pub fn set_batch_function(&mut self, batch_f: BatchFunction) {
+    self.batch_f = Some(batch_f);
+  }
+
+  pub fn set_optimum(&mut self, optimum: f64) {
+    self.optimum = Some(optimum);
+  }
+}
+
+// Evaluator of a shared problem definition, with the mutable state of one run.
+#[derive(Clone)]
+pub struct Problem {
+  def: Arc<ProblemDef>,
+  evaluation: Evaluation,
+  // Calls of the objective function made by the optimizer.
+  cnt: usize,
+  // Calls made to export data, which are not part of the optimization.
+  export_cnt: usize,
+  memo: Option<Memo>,
+  // Fitness returned outside the domain without evaluating, if set.
+  infeasible_fitness: Option<f64>,
+  out_of_bounds: usize,
+  out_of_bounds_progress: Vec<usize>,
+  // Evaluations made by the end of every iteration.
+  evaluation_progress: Vec<usize>,
+  sampler: Sampler,
+  // Maximum number of evaluations of the run, if set.
+  budget: Option<usize>,
+}
+
+impl Problem {
+  pub fn new(def: Arc<ProblemDef>) -> Self {
+    Self {
+      def,
+      evaluation: Evaluation::Serial,
       cnt: 0,
-      memo: HashMap::new(),
+      export_cnt: 0,
+      memo: None,
+      infeasible_fitness: None,
+      out_of_bounds: 0,
+      out_of_bounds_progress: Vec::new(),
+      evaluation_progress: Vec::new(),
+      sampler: Sampler::default(),
+      budget: None,
+    }
+  }
+
+  // Evaluator for a new run of the same problem, with the same settings but none
+  // of the counters and cached values.
+  pub fn fork(&self) -> Problem {
+    Problem {
+      evaluation: self.evaluation,
+      memo: self.memo.as_ref().map(|memo| Memo::new(memo.capacity)),
+      infeasible_fitness: self.infeasible_fitness,
+      sampler: self.sampler.clone(),
+      budget: self.budget,
+      ..Problem::new(Arc::clone(&self.def))
     }
   }
 
+  #[allow(dead_code)]
+  pub fn def(&self) -> &Arc<ProblemDef> {
+    &self.def
+  }
+
   #[allow(dead_code)]
   pub fn name(&self) -> &String {
-    &self.name
+    &self.def.name
   }
 
   pub fn f(&mut self, x: &DVector<f64>) -> f64 {
-    let hash = &HashableDVectorF64ForMemo(x.clone());
-    if self.memo.contains_key(hash) {
-      return self.memo[hash];
+    if let Some(fitness) = self.infeasible_fitness {
+      if !self.contains(x) {
+        return fitness;
+      }
     }
-    let ans = (self.f)(x);
+    let hash = HashableDVectorF64ForMemo(x.clone());
+    if let Some(fitness) = self.memo.as_mut().and_then(|memo| memo.get(&hash)) {
+      return fitness;
+    }
+    let ans = (self.def.f)(x);
     self.cnt += 1;
-    self.memo.insert(hash.clone(), ans);
+    if let Some(memo) = self.memo.as_mut() {
+      memo.insert(hash, ans);
+    }
     ans
   }
 
-  pub fn f_no_memo(&mut self, x: &DVector<f64>) -> f64 {
-    (self.f)(x)
+  // Same as calling `f` on every vector, but the new ones are evaluated together
+  // as set by `set_evaluation`. Repeated vectors are only evaluated once.
+  pub fn f_batch(&mut self, xs: &[DVector<f64>]) -> Vec<f64> {
+    let mut known: HashMap<HashableDVectorF64ForMemo, f64> = HashMap::new();
+    let mut pending = Vec::new();
+    let mut seen = HashSet::new();
+    for x in xs {
+      if self.infeasible_fitness.is_some() && !self.contains(x) {
+        continue;
+      }
+      let hash = HashableDVectorF64ForMemo(x.clone());
+      if let Some(fitness) = self.memo.as_mut().and_then(|memo| memo.get(&hash)) {
+        known.insert(hash, fitness);
+      } else if seen.insert(hash.clone()) {
+        pending.push(hash);
+      }
+    }
+
+    let f = &self.def.f;
+    let values: Vec<f64> = match (self.evaluation, &self.def.batch_f) {
+      (Evaluation::Parallel, _) => pending.par_iter().map(|hash| f(&hash.0)).collect(),
+      (Evaluation::Native, Some(batch_f)) if !pending.is_empty() => {
+        let vectors: Vec<DVector<f64>> = pending.iter().map(|hash| hash.0.clone()).collect();
+        batch_f(&vectors)
+      }
+      _ => pending.iter().map(|hash| f(&hash.0)).collect(),
+    };
+    assert!(
+      values.len() == pending.len(),
+      "The batch function returned {} values for {} vectors.",
+      values.len(),
+      pending.len()
+    );
+    self.cnt += pending.len();
+    for (hash, value) in pending.into_iter().zip(values) {
+      if let Some(memo) = self.memo.as_mut() {
+        memo.insert(hash.clone(), value);
+      }
+      known.insert(hash, value);
+    }
+
+    xs.iter()
+      .map(|x| match known.get(&HashableDVectorF64ForMemo(x.clone())) {
+        Some(&fitness) => fitness,
+        None => self.infeasible_fitness.unwrap(),
+      })
+      .collect()
   }
 
-  pub fn clear_memo(&mut self) {
-    self.memo.clear();
+  // Evaluation for exported data, counted apart from the optimization.
+  pub fn f_export(&mut self, x: &DVector<f64>) -> f64 {
+    self.export_cnt += 1;
+    (self.def.f)(x)
   }
 
   pub fn domain(&self) -> (f64, f64) {
-    self.domain
+    self.def.domain
   }
 
   pub fn dim(&self) -> usize {
-    self.dim
+    self.def.dim
+  }
+
+  pub fn optimum(&self) -> Option<f64> {
+    self.def.optimum
   }
 
   pub fn cnt(&self) -> usize {
     self.cnt
   }
+
+  pub fn export_cnt(&self) -> usize {
+    self.export_cnt
+  }
+
+  // Caches up to `capacity` objective values, so that revisited points are not
+  // evaluated again. Disabled by default.
+  #[allow(dead_code)]
+  pub fn set_memo_capacity(&mut self, capacity: Option<usize>) {
+    self.memo = capacity.map(Memo::new);
+  }
+
+  pub fn contains(&self, x: &DVector<f64>) -> bool {
+    x.iter().all(|&e| self.def.domain.0 <= e && e <= self.def.domain.1)
+  }
+
+  pub fn set_infeasible_fitness(&mut self, fitness: Option<f64>) {
+    self.infeasible_fitness = fitness;
+  }
+
+  pub fn add_out_of_bounds(&mut self) {
+    self.out_of_bounds += 1;
+  }
+
+  // Closes the current iteration, recording its out-of-bounds count.
+  pub fn next_iteration(&mut self) {
+    self.out_of_bounds_progress.push(self.out_of_bounds);
+    self.out_of_bounds = 0;
+    self.evaluation_progress.push(self.cnt);
+  }
+
+  // Number of iterations closed so far.
+  pub fn iteration(&self) -> usize {
+    self.out_of_bounds_progress.len()
+  }
+
+  pub fn out_of_bounds_progress(&self) -> &Vec<usize> {
+    &self.out_of_bounds_progress
+  }
+
+  pub fn evaluation_progress(&self) -> &Vec<usize> {
+    &self.evaluation_progress
+  }
+
+  #[allow(dead_code)]
+  pub fn set_evaluation(&mut self, evaluation: Evaluation) {
+    self.evaluation = evaluation;
+  }
+
+  pub fn sampler(&mut self) -> &mut Sampler {
+    &mut self.sampler
+  }
+
+  #[allow(dead_code)]
+  pub fn set_budget(&mut self, budget: Option<usize>) {
+    self.budget = budget;
+  }
+
+  // Whether the run has used up its evaluation budget.
+  pub fn exhausted(&self) -> bool {
+    self.budget.is_some_and(|budget| self.cnt >= budget)
+  }
 }
 
-impl Default for Problem {
-  fn default() -> Problem {
-    Problem::new("DEFAULT".to_owned(), Arc::new(functions::sphere), (-100., 100.), 0)
+impl From<ProblemDef> for Problem {
+  fn from(def: ProblemDef) -> Problem {
+    Problem::new(Arc::new(def))
   }
 }
 
 #[allow(dead_code)]
 pub fn f1(dim: usize) -> Problem {
-  Problem::new("Sphere".to_owned(), Arc::new(functions::sphere), (-1., 1.), dim)
+  ProblemDef::new("Sphere".to_owned(), Arc::new(functions::sphere), (-1., 1.), dim).into()
 }
 
 #[allow(dead_code)]
 pub fn f1_skewed(dim: usize) -> Problem {
-  Problem::new(
+  ProblemDef::new(
     "Skewed Sphere".to_owned(),
     Arc::new(functions::skewed_sphere),
     (-1., 1.),
     dim,
   )
+  .into()
 }
 
 #[allow(dead_code)]
 pub fn sphere_100(dim: usize) -> Problem {
-  Problem::new("Sphere100".to_owned(), Arc::new(functions::sphere), (-100., 100.), dim)
+  ProblemDef::new("Sphere100".to_owned(), Arc::new(functions::sphere), (-100., 100.), dim).into()
 }
 
 #[allow(dead_code)]
 pub fn rosenbrock_30(dim: usize) -> Problem {
-  Problem::new(
+  ProblemDef::new(
     "Rosenbrock30".to_owned(),
     Arc::new(functions::rosenbrock),
     (-30., 30.),
     dim,
   )
+  .into()
 }
 
 #[allow(dead_code)]
 pub fn griewank_600(dim: usize) -> Problem {
-  Problem::new(
+  ProblemDef::new(
     "Griewank600".to_owned(),
     Arc::new(functions::griewank),
     (-600., 600.),
     dim,
   )
+  .into()
 }
 
 #[allow(dead_code)]
 pub fn rastrigin_5_12(dim: usize) -> Problem {
-  Problem::new(
+  ProblemDef::new(
     "Rastrigin5_12".to_owned(),
     Arc::new(functions::rastrigin),
     (-5.12, 5.12),
     dim,
   )
+  .into()
 }
 
 #[allow(dead_code)]
 pub fn rastrigin_100(dim: usize) -> Problem {
-  Problem::new(
+  ProblemDef::new(
     "Rastrigin100".to_owned(),
     Arc::new(functions::rastrigin),
     (-100., 100.),
     dim,
   )
+  .into()
 }
 
 #[allow(dead_code)]
 pub fn hyper_ellipsoid_100(dim: usize) -> Problem {
-  Problem::new(
+  ProblemDef::new(
     "HyperEllipsoid100".to_owned(),
     Arc::new(functions::hyper_ellipsoid),
     (-100., 100.),
     dim,
   )
+  .into()
 }
 
 #[allow(dead_code)]
 pub fn f3(dim: usize) -> Problem {
-  Problem::new(
+  ProblemDef::new(
     "Rastrigin".to_owned(),
     Arc::new(functions::rastrigin),
     (-5.12, 5.12),
     dim,
   )
+  .into()
 }
 
 #[allow(dead_code)]
@@ -195,10 +446,32 @@ pub fn cec17(func_num: usize, dim: usize) -> Problem {
     "CEC2017 contains 30 functions from F1 to F30 except for F2."
   );
   assert!(func_num != 2, "CEC2017 F2 has been deprecated.");
-  Problem::new(
+  let mut def = ProblemDef::new(
     format!("CEC2017_F{:02}", func_num),
     Arc::new(move |x: &DVector<f64>| functions::cec17_impl(x, func_num)),
     (-100., 100.),
     dim,
-  )
+  );
+  def.set_batch_function(Arc::new(move |xs: &[DVector<f64>]| {
+    functions::cec17_batch_impl(xs, func_num)
+  }));
+  // F_i is shifted so that its minimum is 100 * i.
+  def.set_optimum(100. * func_num as f64);
+  let mut problem: Problem = def.into();
+  problem.set_evaluation(Evaluation::Native);
+  problem
+}
+
+#[allow(dead_code)]
+pub fn bbob(func_num: usize, instance: usize, dim: usize) -> Problem {
+  let function = Bbob::new(func_num, instance, dim);
+  let optimum = function.fopt();
+  let mut def = ProblemDef::new(
+    format!("BBOB_F{:02}_I{:02}", func_num, instance),
+    Arc::new(move |x: &DVector<f64>| function.evaluate(x)),
+    (-5., 5.),
+    dim,
+  );
+  def.set_optimum(optimum);
+  def.into()
 }
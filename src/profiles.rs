@@ -0,0 +1,300 @@
+use crate::compare::{self, ByProblem};
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Tolerances of the convergence test of the performance and data profiles.
+const TAUS: [f64; 4] = [1e-1, 1e-3, 1e-5, 1e-7];
+
+// Distances to the optimum of the ECDF targets, from 1e2 down to 1e-8, as in COCO.
+fn precisions() -> Vec<f64> {
+  (0..=50).map(|i| 10f64.powf(2. - 0.2 * i as f64)).collect()
+}
+
+// Best fitness so far of an attempt, against the evaluations made.
+struct Run {
+  evaluations: Vec<f64>,
+  best: Vec<f64>,
+}
+
+impl Run {
+  // Older summaries only have the total evaluation count, which is then assumed to be
+  // spread evenly over the iterations.
+  fn load(attempt: &Path) -> Result<Run, Box<dyn std::error::Error>> {
+    let summary = compare::read_json(&attempt.join("summary.json"))?;
+    let best = compare::best_so_far(&summary);
+    let evaluations: Vec<f64> = match summary["evaluation_count_progress"].as_array() {
+      Some(progress) => progress.iter().filter_map(|e| e.as_f64()).collect(),
+      None => {
+        let total = summary["evaluation_count"].as_f64().unwrap_or(0.);
+        (1..=best.len()).map(|t| total * t as f64 / best.len() as f64).collect()
+      }
+    };
+    Ok(Run { evaluations, best })
+  }
+
+  // Evaluations made when the best fitness first reached `target`, if it did. Iterations
+  // past the recorded evaluation counts cannot be timed, so they count as not reached.
+  fn runtime(&self, target: f64) -> Option<f64> {
+    self.best.iter().position(|&f| f <= target).and_then(|t| self.evaluations.get(t).copied())
+  }
+
+  fn first(&self) -> f64 {
+    self.best.first().copied().unwrap_or(f64::INFINITY)
+  }
+
+  fn last(&self) -> f64 {
+    self.best.last().copied().unwrap_or(f64::INFINITY)
+  }
+}
+
+// Anytime comparison of the optimizers of a test, written as CSV to `data/<test>/<dim>`:
+// the ECDF of the runtimes to the targets of every problem (ecdf.csv), and the
+// performance and data profiles over the problems (performance_profile.csv and
+// data_profile.csv). Runtimes are counted in evaluations.
+pub fn profiles(test_name: &str, dim: usize) -> Result<(), Box<dyn std::error::Error>> {
+  let directory = PathBuf::from(format!("data/{}/{}", test_name, dim));
+  let mut runs = ByProblem::<Vec<Run>>::new();
+  // Optimum of every problem, or the best fitness found by any attempt if unknown.
+  let mut optima = std::collections::BTreeMap::new();
+  for (problem, by_optimizer) in compare::attempts(&directory)? {
+    let mut optimum = f64::INFINITY;
+    for (optimizer, attempts) in by_optimizer {
+      for attempt in attempts.iter() {
+        let run = Run::load(attempt)?;
+        let config = compare::read_json(&attempt.join("config.json")).unwrap_or(Value::Null);
+        optimum = optimum.min(config["problem"]["optimum"].as_f64().unwrap_or(run.last()));
+        runs.entry(problem.clone()).or_default().entry(optimizer.clone()).or_default().push(run);
+      }
+    }
+    optima.insert(problem, optimum);
+  }
+  let mut optimizers: Vec<String> = runs.values().flat_map(|by_optimizer| by_optimizer.keys().cloned()).collect();
+  optimizers.sort();
+  optimizers.dedup();
+
+  fs::write(directory.join("ecdf.csv"), ecdf(&runs, &optima, &optimizers))?;
+  let (performance, data) = performance_and_data_profiles(&runs, &optimizers, dim);
+  fs::write(directory.join("performance_profile.csv"), performance)?;
+  fs::write(directory.join("data_profile.csv"), data)?;
+  println!("Profiles written to {}.", directory.display());
+  Ok(())
+}
+
+// Budgets from 1 to `max`, spaced logarithmically.
+fn log_grid(max: f64, per_decade: f64) -> Vec<f64> {
+  let steps = (per_decade * max.max(1.).log10()).ceil() as usize;
+  (0..=steps).map(|j| 10f64.powf(j as f64 / per_decade)).collect()
+}
+
+// Fraction of the (problem, attempt, target) triples of every optimizer solved within
+// every budget.
+fn ecdf(runs: &ByProblem<Vec<Run>>, optima: &std::collections::BTreeMap<String, f64>, optimizers: &[String]) -> String {
+  let max = runs
+    .values()
+    .flat_map(|r| r.values())
+    .flatten()
+    .filter_map(|run| run.evaluations.last())
+    .fold(1., |a: f64, &b| a.max(b));
+  let budgets = log_grid(max, 10.);
+  let mut csv = "optimizer,evaluations,fraction\n".to_owned();
+  for optimizer in optimizers.iter() {
+    let mut runtimes = Vec::new();
+    for (problem, by_optimizer) in runs.iter() {
+      for run in by_optimizer.get(optimizer).into_iter().flatten() {
+        for precision in precisions() {
+          runtimes.push(run.runtime(optima[problem] + precision).unwrap_or(f64::INFINITY));
+        }
+      }
+    }
+    for budget in budgets.iter() {
+      let solved = runtimes.iter().filter(|&&runtime| runtime <= *budget).count();
+      csv.push_str(&format!(
+        "{},{},{}\n",
+        optimizer,
+        budget,
+        solved as f64 / runtimes.len().max(1) as f64
+      ));
+    }
+  }
+  csv
+}
+
+// Dolan-Moré performance profiles and Moré-Wild data profiles. Every attempt index of a
+// problem that all optimizers ran is an instance, and an optimizer solves it once
+//   f <= f_L + tau (f_0 - f_L),
+// with f_L the best final fitness of any optimizer and f_0 the worst initial one.
+fn performance_and_data_profiles(runs: &ByProblem<Vec<Run>>, optimizers: &[String], dim: usize) -> (String, String) {
+  let mut performance = "tau,optimizer,ratio,fraction\n".to_owned();
+  let mut data = "tau,optimizer,simplex_gradients,fraction\n".to_owned();
+  for tau in TAUS {
+    // Runtimes of every optimizer on every instance.
+    let mut runtimes: Vec<Vec<f64>> = Vec::new();
+    for by_optimizer in runs.values() {
+      if !optimizers.iter().all(|name| by_optimizer.contains_key(name)) {
+        continue;
+      }
+      let attempts = optimizers.iter().map(|name| by_optimizer[name].len()).min().unwrap_or(0);
+      let instances = (0..attempts).map(|a| optimizers.iter().map(|name| &by_optimizer[name][a]).collect::<Vec<_>>());
+      for instance in instances {
+        let f_l = instance.iter().map(|run| run.last()).fold(f64::INFINITY, f64::min);
+        let f_0 = instance.iter().map(|run| run.first()).fold(f64::NEG_INFINITY, f64::max);
+        let target = f_l + tau * (f_0 - f_l);
+        runtimes.push(instance.iter().map(|run| run.runtime(target).unwrap_or(f64::INFINITY)).collect());
+      }
+    }
+    let instances = runtimes.len().max(1) as f64;
+
+    let ratios: Vec<Vec<f64>> = runtimes
+      .iter()
+      .map(|instance| {
+        let fastest = instance.iter().copied().fold(f64::INFINITY, f64::min);
+        instance.iter().map(|t| t / fastest).collect()
+      })
+      .collect();
+    let max_ratio = ratios.iter().flatten().filter(|r| r.is_finite()).fold(1., |a: f64, &b| a.max(b));
+    let steps = (4. * max_ratio.log2()).ceil() as usize;
+    for (s, optimizer) in optimizers.iter().enumerate() {
+      for alpha in (0..=steps).map(|j| 2f64.powf(j as f64 / 4.)) {
+        let solved = ratios.iter().filter(|instance| instance[s] <= alpha).count();
+        performance.push_str(&format!(
+          "{},{},{},{}\n",
+          tau,
+          optimizer,
+          alpha,
+          solved as f64 / instances
+        ));
+      }
+    }
+
+    // Budgets in simplex gradients, i.e. groups of dim + 1 evaluations.
+    let gradients: Vec<Vec<f64>> =
+      runtimes.iter().map(|instance| instance.iter().map(|t| t / (dim + 1) as f64).collect()).collect();
+    let max = gradients.iter().flatten().filter(|k| k.is_finite()).fold(1., |a: f64, &b| a.max(b));
+    for (s, optimizer) in optimizers.iter().enumerate() {
+      for kappa in log_grid(max, 10.) {
+        let solved = gradients.iter().filter(|instance| instance[s] <= kappa).count();
+        data.push_str(&format!(
+          "{},{},{},{}\n",
+          tau,
+          optimizer,
+          kappa,
+          solved as f64 / instances
+        ));
+      }
+    }
+  }
+  (performance, data)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::collections::BTreeMap;
+
+  fn run(evaluations: &[f64], best: &[f64]) -> Run {
+    Run {
+      evaluations: evaluations.to_vec(),
+      best: best.to_vec(),
+    }
+  }
+
+  fn close(a: f64, b: f64) -> bool {
+    (a - b).abs() < 1e-12
+  }
+
+  // Rows of a CSV with its two last columns parsed, after the header.
+  fn rows(csv: &str) -> Vec<(Vec<String>, f64, f64)> {
+    csv
+      .lines()
+      .skip(1)
+      .map(|line| {
+        let fields: Vec<&str> = line.split(',').collect();
+        let n = fields.len();
+        let keys = fields[..n - 2].iter().map(|f| f.to_string()).collect();
+        (keys, fields[n - 2].parse().unwrap(), fields[n - 1].parse().unwrap())
+      })
+      .collect()
+  }
+
+  // Fractions of the rows with the given keys, in order.
+  fn fractions(rows: &[(Vec<String>, f64, f64)], keys: &[&str]) -> Vec<f64> {
+    rows.iter().filter(|(k, _, _)| k.iter().map(String::as_str).eq(keys.iter().copied())).map(|r| r.2).collect()
+  }
+
+  fn by_problem(runs: Vec<(&str, Run)>) -> ByProblem<Vec<Run>> {
+    let mut by_optimizer = BTreeMap::new();
+    for (optimizer, run) in runs {
+      by_optimizer.insert(optimizer.to_owned(), vec![run]);
+    }
+    BTreeMap::from([("p".to_owned(), by_optimizer)])
+  }
+
+  #[test]
+  fn log_grid_spans_the_decades() {
+    let grid = log_grid(100., 2.);
+    let expected = [1., 10f64.sqrt(), 10., 10f64.powf(1.5), 100.];
+    assert_eq!(grid.len(), expected.len());
+    assert!(grid.iter().zip(expected).all(|(a, b)| close(*a, b)));
+    assert_eq!(log_grid(0.5, 10.), vec![1.]);
+  }
+
+  #[test]
+  fn runtime_stops_at_the_recorded_evaluations() {
+    let truncated = run(&[10., 20.], &[5., 3., 1.]);
+    assert_eq!(truncated.runtime(3.), Some(20.));
+    assert_eq!(truncated.runtime(1.), None);
+    assert_eq!(truncated.runtime(0.), None);
+  }
+
+  #[test]
+  fn ecdf_counts_the_solved_targets() {
+    // The 51 targets are 10^2, 10^1.8, ..., 10^-8 above the optimum 0. `a` reaches the
+    // 2 targets above 50 after 10 evaluations, the 9 down to 1 after 20 and the other
+    // 40 after 30. `b` only reaches 100, after 10 evaluations.
+    let runs = by_problem(vec![
+      ("a", run(&[10., 20., 30.], &[50., 1., 1e-9])),
+      ("b", run(&[10., 20., 30.], &[100., 100., 100.])),
+    ]);
+    let optima = BTreeMap::from([("p".to_owned(), 0.)]);
+    let rows = rows(&ecdf(&runs, &optima, &["a".to_owned(), "b".to_owned()]));
+
+    // Budgets 10^(j / 10) up to 10^1.5, just over the 30 evaluations.
+    let mut a = vec![0.; 10];
+    a.extend([2. / 51., 2. / 51., 2. / 51., 2. / 51., 11. / 51., 1.]);
+    let mut b = vec![0.; 10];
+    b.extend([1. / 51.; 6]);
+    for (actual, expected) in [(fractions(&rows, &["a"]), a), (fractions(&rows, &["b"]), b)] {
+      assert_eq!(actual.len(), expected.len());
+      assert!(actual.iter().zip(&expected).all(|(x, y)| close(*x, *y)), "{:?}", actual);
+    }
+  }
+
+  #[test]
+  fn profiles_compare_the_runtimes_to_the_fastest() {
+    // f_L = 0 and f_0 = 100. With tau = 0.1 the target is 10, reached by `a` after 30
+    // evaluations and by `b` after 40. Smaller tolerances are only reached by `a`.
+    let runs = by_problem(vec![
+      ("a", run(&[10., 20., 30., 40.], &[100., 50., 10., 0.])),
+      ("b", run(&[10., 20., 30., 40.], &[80., 40., 20., 5.])),
+    ]);
+    let (performance, data) = performance_and_data_profiles(&runs, &["a".to_owned(), "b".to_owned()], 1);
+    let performance = rows(&performance);
+    let data = rows(&data);
+
+    // Ratios 1 and 4/3, on the grid 1, 2^0.25 and 2^0.5.
+    assert_eq!(fractions(&performance, &["0.1", "a"]), vec![1., 1., 1.]);
+    assert_eq!(fractions(&performance, &["0.1", "b"]), vec![0., 0., 1.]);
+    assert_eq!(fractions(&performance, &["0.001", "a"]), vec![1.]);
+    assert_eq!(fractions(&performance, &["0.001", "b"]), vec![0.]);
+
+    // 15 and 20 simplex gradients of 2 evaluations, on the grid 10^(j / 10) up to 10^1.4.
+    let a = fractions(&data, &["0.1", "a"]);
+    let b = fractions(&data, &["0.1", "b"]);
+    assert_eq!(a.len(), 15);
+    assert_eq!(a.iter().position(|&f| f == 1.), Some(12));
+    assert_eq!(b.iter().position(|&f| f == 1.), Some(14));
+    let unsolved = fractions(&data, &["0.0000001", "b"]);
+    assert!(!unsolved.is_empty() && unsolved.iter().all(|&f| f == 0.));
+  }
+}
@@ -0,0 +1,595 @@
+use nalgebra::DVector;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+// How the trajectory of a run is written, selected with the `trajectory_format`
+// parameter.
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+  // data.json, a list with one object per iteration.
+  Json,
+  // A `trajectory` directory with one NPY array per field, e.g. pos.npy of shape
+  // [iterations, particles, dim] and fitness.npy of shape [iterations, particles].
+  Npy,
+}
+
+impl Format {
+  // Where the trajectory of an attempt is written.
+  pub fn path(&self, directory: &Path) -> PathBuf {
+    match self {
+      Format::Json => directory.join("data.json"),
+      Format::Npy => directory.join("trajectory"),
+    }
+  }
+}
+
+// Recording state of a run, kept by the optimizer next to its data rather than by the
+// problem it evaluates.
+#[derive(Clone, Default)]
+pub struct Recorder {
+  // Where the particles of every iteration go when the run streams them.
+  trajectory: Option<TrajectoryStream>,
+  // Statistics of every iteration, if enabled.
+  metrics: Option<Metrics>,
+  // Fitness and position of the best particle found so far.
+  best: Option<(f64, DVector<f64>)>,
+}
+
+impl Recorder {
+  // Keeps the particle if it improves on the best found so far.
+  pub fn update_best(&mut self, fitness: f64, pos: &DVector<f64>) {
+    let improved = match &self.best {
+      Some((best, _)) => fitness < *best,
+      None => true,
+    };
+    if improved {
+      self.best = Some((fitness, pos.clone()));
+    }
+  }
+
+  pub fn best(&self) -> Option<&(f64, DVector<f64>)> {
+    self.best.as_ref()
+  }
+
+  pub fn set_trajectory(&mut self, trajectory: Option<TrajectoryStream>) {
+    self.trajectory = trajectory;
+  }
+
+  pub fn trajectory(&mut self) -> Option<&mut TrajectoryStream> {
+    self.trajectory.as_mut()
+  }
+
+  // Records per-iteration statistics, or not, which spares the pairwise distances of
+  // large swarms.
+  pub fn set_metrics(&mut self, enabled: bool) {
+    self.metrics = enabled.then(Metrics::default);
+  }
+
+  pub fn metrics(&mut self) -> Option<&mut Metrics> {
+    self.metrics.as_mut()
+  }
+
+  // Sets a metric of the current iteration, if they are recorded.
+  pub fn set_metric(&mut self, name: &str, value: f64) {
+    if let Some(metrics) = self.metrics.as_mut() {
+      metrics.set(name, value);
+    }
+  }
+
+  // Closes the current iteration.
+  pub fn next_iteration(&mut self) {
+    if let Some(metrics) = self.metrics.as_mut() {
+      metrics.next_iteration();
+    }
+  }
+}
+
+// Statistics of every iteration of a run, written to summary.json. Optimizers set
+// their own, e.g. G for the GSA family, during the iteration.
+#[derive(Clone)]
+pub struct Metrics {
+  started: Instant,
+  values: BTreeMap<String, Vec<f64>>,
+  // Iterations closed so far.
+  iterations: usize,
+}
+
+impl Default for Metrics {
+  fn default() -> Self {
+    Metrics {
+      started: Instant::now(),
+      values: BTreeMap::new(),
+      iterations: 0,
+    }
+  }
+}
+
+impl Metrics {
+  // Sets a metric of the current iteration. Iterations where it was not set are NaN.
+  pub fn set(&mut self, name: &str, value: f64) {
+    let values = self.values.entry(name.to_string()).or_default();
+    values.resize(self.iterations, f64::NAN);
+    values.push(value);
+  }
+
+  // Value of a metric in the current iteration, if it was set.
+  pub fn get(&self, name: &str) -> Option<f64> {
+    self.values.get(name).and_then(|values| values.get(self.iterations)).copied()
+  }
+
+  // Closes the current iteration, recording the seconds elapsed since the start of the
+  // run.
+  pub fn next_iteration(&mut self) {
+    self.set("wall_clock_time", self.started.elapsed().as_secs_f64());
+    self.iterations += 1;
+  }
+
+  pub fn to_json(&self) -> Map<String, Value> {
+    let mut map = Map::new();
+    for (name, values) in self.values.iter() {
+      let mut values = values.clone();
+      values.resize(self.iterations, f64::NAN);
+      map.insert(name.clone(), json!(values));
+    }
+    map
+  }
+}
+
+// Iterations of a run that are saved.
+#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Schedule {
+  All,
+  // Every k-th iteration, starting with the first one.
+  Every(usize),
+  // About the given number of iterations per decade, spaced logarithmically, so that
+  // the start of the run is saved in more detail than the end.
+  Log(usize),
+}
+
+impl Schedule {
+  pub fn keeps(&self, iter: usize) -> bool {
+    match *self {
+      Schedule::All => true,
+      Schedule::Every(k) => iter.is_multiple_of(k.max(1)),
+      Schedule::Log(per_decade) => {
+        let point = |iter: usize| (per_decade as f64 * (iter as f64).log10()).floor();
+        iter == 0 || point(iter + 1) > point(iter)
+      }
+    }
+  }
+}
+
+// What is saved of a run, given with the `recording` parameter. The default saves
+// everything.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Policy {
+  pub schedule: Schedule,
+  // Particle fields that are saved, e.g. fitness and mass but not vel. None saves all.
+  pub fields: Option<Vec<String>>,
+  // Indices of the saved particles. None saves all.
+  pub particles: Option<Vec<usize>>,
+  // Indices of the saved components of pos, vel and other vectors. None saves all.
+  pub dimensions: Option<Vec<usize>>,
+}
+
+impl Default for Policy {
+  fn default() -> Self {
+    Policy {
+      schedule: Schedule::All,
+      fields: None,
+      particles: None,
+      dimensions: None,
+    }
+  }
+}
+
+impl Policy {
+  // The part of a particle that is saved.
+  fn particle(&self, particle: &Map<String, Value>) -> Value {
+    let mut kept = Map::new();
+    for (key, value) in particle.iter() {
+      if let Some(fields) = &self.fields {
+        if !fields.contains(key) {
+          continue;
+        }
+      }
+      let value = match (value, &self.dimensions) {
+        (Value::Array(values), Some(dimensions)) => {
+          Value::Array(dimensions.iter().filter_map(|&d| values.get(d).cloned()).collect())
+        }
+        (value, _) => value.clone(),
+      };
+      kept.insert(key.clone(), value);
+    }
+    Value::Object(kept)
+  }
+
+  // The part of an iteration that is saved. When iterations are skipped, the index of
+  // the iteration is added to it.
+  fn iteration(&self, iter: usize, record: &Value) -> Value {
+    let mut kept = record.as_object().cloned().unwrap_or_default();
+    if let Some(Value::Array(particles)) = record.get("particles") {
+      let particles: Vec<&Value> = match &self.particles {
+        Some(indices) => indices.iter().filter_map(|&idx| particles.get(idx)).collect(),
+        None => particles.iter().collect(),
+      };
+      let particles = particles.iter().filter_map(|particle| particle.as_object()).map(|p| self.particle(p));
+      kept.insert("particles".to_string(), Value::Array(particles.collect()));
+    }
+    if self.schedule != Schedule::All {
+      kept.insert("iteration".to_string(), json!(iter));
+    }
+    Value::Object(kept)
+  }
+}
+
+// The trajectory written one iteration at a time, so that the particles of a run are
+// never all kept in memory. Clones write to the same file.
+#[derive(Clone)]
+pub struct TrajectoryStream {
+  sink: Arc<Mutex<Sink>>,
+  policy: Arc<Policy>,
+  // Iterations seen so far, saved or not.
+  iterations: Arc<Mutex<usize>>,
+}
+
+enum Sink {
+  Json { writer: BufWriter<File>, records: usize },
+  Npy(NpyWriter),
+}
+
+impl TrajectoryStream {
+  pub fn create(path: &Path, format: Format, policy: Policy) -> std::io::Result<TrajectoryStream> {
+    let sink = match format {
+      Format::Json => {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(b"[")?;
+        Sink::Json { writer, records: 0 }
+      }
+      Format::Npy => Sink::Npy(NpyWriter::create(path)?),
+    };
+    Ok(TrajectoryStream {
+      sink: Arc::new(Mutex::new(sink)),
+      policy: Arc::new(policy),
+      iterations: Arc::new(Mutex::new(0)),
+    })
+  }
+
+  // Moves on to the next iteration, and tells if it is saved. Only then should it be
+  // appended.
+  pub fn advance(&mut self) -> bool {
+    let mut iterations = self.iterations.lock().unwrap();
+    *iterations += 1;
+    self.policy.schedule.keeps(*iterations - 1)
+  }
+
+  // Appends the current iteration, in the layout of `DataExporter::iteration_json`.
+  pub fn append(&mut self, record: &Value) -> std::io::Result<()> {
+    let iter = self.iterations.lock().unwrap().saturating_sub(1);
+    let record = &self.policy.iteration(iter, record);
+    match &mut *self.sink.lock().unwrap() {
+      Sink::Json { writer, records } => {
+        if *records > 0 {
+          writer.write_all(b",")?;
+        }
+        serde_json::to_writer(&mut *writer, record)?;
+        *records += 1;
+        Ok(())
+      }
+      Sink::Npy(writer) => writer.append(record),
+    }
+  }
+
+  // Closes the trajectory. Nothing should be appended afterwards.
+  pub fn finish(&mut self) -> std::io::Result<()> {
+    match &mut *self.sink.lock().unwrap() {
+      Sink::Json { writer, .. } => {
+        writer.write_all(b"]")?;
+        writer.flush()
+      }
+      Sink::Npy(writer) => writer.finish(),
+    }
+  }
+}
+
+// Size of the NPY headers. They are written before the shape is known and rewritten
+// by `finish`, so they all have the same size, a multiple of 64 as the format requires.
+const NPY_HEADER_LEN: usize = 128;
+
+fn npy_header(descr: &str, shape: &[usize]) -> Vec<u8> {
+  let shape = match shape.len() {
+    1 => format!("({},)", shape[0]),
+    _ => format!(
+      "({})",
+      shape.iter().map(|n| n.to_string()).collect::<Vec<_>>().join(", ")
+    ),
+  };
+  let mut dict = format!("{{'descr': '{}', 'fortran_order': False, 'shape': {}, }}", descr, shape);
+  dict.push_str(&" ".repeat(NPY_HEADER_LEN - 10 - dict.len() - 1));
+  dict.push('\n');
+
+  let mut header = b"\x93NUMPY\x01\x00".to_vec();
+  header.extend_from_slice(&(dict.len() as u16).to_le_bytes());
+  header.extend_from_slice(dict.as_bytes());
+  header
+}
+
+// Numbers of a field, or None if it isn't numeric. Null, i.e. a non-finite float in
+// the JSON layout, becomes NaN.
+fn numbers(value: &Value) -> Option<(Vec<f64>, Vec<usize>)> {
+  let number = |value: &Value| match value {
+    Value::Number(n) => n.as_f64(),
+    Value::Null => Some(f64::NAN),
+    _ => None,
+  };
+  match value {
+    Value::Array(values) => {
+      let values: Option<Vec<f64>> = values.iter().map(number).collect();
+      values.map(|values| {
+        let len = values.len();
+        (values, vec![len])
+      })
+    }
+    value => number(value).map(|n| (vec![n], vec![])),
+  }
+}
+
+fn invalid(message: String) -> std::io::Error {
+  std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
+
+struct NpyArray {
+  name: String,
+  writer: BufWriter<File>,
+  // Shape of a single value, [] for scalars and [dim] for vectors.
+  inner_shape: Vec<usize>,
+  // One value per particle instead of one per iteration.
+  per_particle: bool,
+}
+
+impl NpyArray {
+  fn create(directory: &Path, name: &str, inner_shape: Vec<usize>, per_particle: bool) -> std::io::Result<NpyArray> {
+    let mut writer = BufWriter::new(File::create(directory.join(format!("{}.npy", name)))?);
+    writer.write_all(&npy_header("<f8", &[0]))?;
+    Ok(NpyArray {
+      name: name.to_string(),
+      writer,
+      inner_shape,
+      per_particle,
+    })
+  }
+
+  fn write(&mut self, value: Option<&Value>) -> std::io::Result<()> {
+    let len: usize = self.inner_shape.iter().product();
+    let values = match value {
+      Some(value) => match numbers(value) {
+        Some((values, _)) if values.len() == len => values,
+        _ => return Err(invalid(format!("'{}' changed type or length.", self.name))),
+      },
+      // A field missing from a particle.
+      None => vec![f64::NAN; len],
+    };
+    for v in values {
+      self.writer.write_all(&v.to_le_bytes())?;
+    }
+    Ok(())
+  }
+
+  fn finish(&mut self, outer_shape: &[usize]) -> std::io::Result<()> {
+    let shape: Vec<usize> = outer_shape.iter().chain(self.inner_shape.iter()).copied().collect();
+    self.writer.seek(SeekFrom::Start(0))?;
+    self.writer.write_all(&npy_header("<f8", &shape))?;
+    self.writer.flush()
+  }
+}
+
+// Numeric fields of the iterations, as little-endian f64 arrays. The arrays are
+// taken from the first iteration, fields that appear later are an error.
+struct NpyWriter {
+  directory: PathBuf,
+  arrays: Vec<NpyArray>,
+  // Number of particles at every iteration.
+  particle_counts: Vec<usize>,
+}
+
+impl NpyWriter {
+  fn create(directory: &Path) -> std::io::Result<NpyWriter> {
+    fs::create_dir_all(directory)?;
+    Ok(NpyWriter {
+      directory: directory.to_path_buf(),
+      arrays: Vec::new(),
+      particle_counts: Vec::new(),
+    })
+  }
+
+  fn append(&mut self, record: &Value) -> std::io::Result<()> {
+    let empty = Map::new();
+    let iteration = record.as_object().unwrap_or(&empty);
+    let particles: Vec<&Map<String, Value>> = match iteration.get("particles") {
+      Some(Value::Array(particles)) => particles.iter().filter_map(|particle| particle.as_object()).collect(),
+      _ => Vec::new(),
+    };
+
+    if self.particle_counts.is_empty() {
+      self.create_arrays(iteration, &particles)?;
+    } else {
+      let known = |key: &String| self.arrays.iter().any(|array| &array.name == key);
+      if let Some(key) = particles.iter().flat_map(|p| p.keys()).find(|key| !known(key)) {
+        return Err(invalid(format!("'{}' is not in the first iteration.", key)));
+      }
+    }
+
+    for array in self.arrays.iter_mut() {
+      match array.per_particle {
+        true => {
+          for particle in particles.iter() {
+            array.write(particle.get(&array.name))?;
+          }
+        }
+        false => array.write(iteration.get(&array.name))?,
+      }
+    }
+    self.particle_counts.push(particles.len());
+    Ok(())
+  }
+
+  fn create_arrays(
+    &mut self,
+    iteration: &Map<String, Value>,
+    particles: &[&Map<String, Value>],
+  ) -> std::io::Result<()> {
+    for (key, value) in iteration.iter() {
+      if let Some((_, inner_shape)) = numbers(value) {
+        self.arrays.push(NpyArray::create(&self.directory, key, inner_shape, false)?);
+      }
+    }
+    for particle in particles.iter() {
+      for (key, value) in particle.iter() {
+        if self.arrays.iter().any(|array| &array.name == key) {
+          continue;
+        }
+        if let Some((_, inner_shape)) = numbers(value) {
+          self.arrays.push(NpyArray::create(&self.directory, key, inner_shape, true)?);
+        }
+      }
+    }
+    Ok(())
+  }
+
+  // Writes the shapes into the headers. If the number of particles changed during the
+  // run, the particles of all iterations are concatenated, and particle_count.npy
+  // tells how many belong to every iteration. manifest.json lists the arrays, so that
+  // loaders know which ones hold a value per particle.
+  fn finish(&mut self) -> std::io::Result<()> {
+    let iterations = self.particle_counts.len();
+    let particle_count = self.particle_counts.first().copied().unwrap_or(0);
+    let fixed = self.particle_counts.iter().all(|&count| count == particle_count);
+    for array in self.arrays.iter_mut() {
+      match (array.per_particle, fixed) {
+        (false, _) => array.finish(&[iterations])?,
+        (true, true) => array.finish(&[iterations, particle_count])?,
+        (true, false) => array.finish(&[self.particle_counts.iter().sum()])?,
+      }
+    }
+
+    if !fixed {
+      let mut writer = BufWriter::new(File::create(self.directory.join("particle_count.npy"))?);
+      writer.write_all(&npy_header("<i8", &[iterations]))?;
+      for &count in self.particle_counts.iter() {
+        writer.write_all(&(count as i64).to_le_bytes())?;
+      }
+      writer.flush()?;
+    }
+
+    let names = |per_particle: bool| -> Vec<&str> {
+      self.arrays.iter().filter(|array| array.per_particle == per_particle).map(|array| array.name.as_str()).collect()
+    };
+    let manifest = json!({
+      "iterations": iterations,
+      "iteration_fields": names(false),
+      "particle_fields": names(true),
+      "concatenated": !fixed,
+    });
+    fs::write(self.directory.join("manifest.json"), serde_json::to_string(&manifest)?)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn kept(schedule: Schedule, iterations: usize) -> Vec<usize> {
+    (0..iterations).filter(|&iter| schedule.keeps(iter)).collect()
+  }
+
+  fn record(fitness: f64) -> Value {
+    json!({
+      "global_best_fitness": fitness,
+      "particles": [
+        {"pos": [1., 2., 3.], "fitness": fitness},
+        {"pos": [4., 5., 6.], "fitness": fitness + 1.},
+      ],
+    })
+  }
+
+  #[test]
+  fn schedules_keep_their_iterations() {
+    assert_eq!(kept(Schedule::All, 5), vec![0, 1, 2, 3, 4]);
+    assert_eq!(kept(Schedule::Every(3), 10), vec![0, 3, 6, 9]);
+    assert_eq!(kept(Schedule::Every(0), 3), vec![0, 1, 2]);
+    assert_eq!(kept(Schedule::Log(1), 1000), vec![0, 9, 99, 999]);
+    let log = kept(Schedule::Log(10), 10000);
+    assert_eq!(&log[..3], &[0, 1, 2]);
+    // About ten per decade.
+    assert!((38..=42).contains(&log.len()), "{} iterations", log.len());
+  }
+
+  #[test]
+  fn policy_keeps_the_selected_parts() {
+    let policy = Policy {
+      schedule: Schedule::Every(2),
+      fields: Some(vec!["pos".to_owned()]),
+      particles: Some(vec![1]),
+      dimensions: Some(vec![0, 2]),
+    };
+    let expected = json!({
+      "global_best_fitness": 1.,
+      "iteration": 4,
+      "particles": [{"pos": [4., 6.]}],
+    });
+    assert_eq!(policy.iteration(4, &record(1.)), expected);
+    // The index is only added when iterations are skipped.
+    assert_eq!(Policy::default().iteration(4, &record(1.)), record(1.));
+  }
+
+  #[test]
+  fn metrics_are_read_back_within_their_iteration() {
+    let mut metrics = Metrics::default();
+    metrics.set("spread", 2.);
+    assert_eq!(metrics.get("spread"), Some(2.));
+    metrics.next_iteration();
+    assert_eq!(metrics.get("spread"), None);
+    metrics.next_iteration();
+    assert_eq!(metrics.to_json()["spread"], json!([2., null]));
+  }
+
+  #[test]
+  fn npy_trajectory_lists_its_arrays() {
+    let directory = std::env::temp_dir().join(format!("psobench-npy-{}", std::process::id()));
+    let policy = Policy {
+      schedule: Schedule::Every(2),
+      ..Policy::default()
+    };
+    let mut stream = TrajectoryStream::create(&directory, Format::Npy, policy).unwrap();
+    for iter in 0..5 {
+      if stream.advance() {
+        stream.append(&record(iter as f64)).unwrap();
+      }
+    }
+    stream.finish().unwrap();
+
+    let manifest: Value = serde_json::from_str(&fs::read_to_string(directory.join("manifest.json")).unwrap()).unwrap();
+    let expected = json!({
+      "iterations": 3,
+      "iteration_fields": ["global_best_fitness", "iteration"],
+      "particle_fields": ["fitness", "pos"],
+      "concatenated": false,
+    });
+    assert_eq!(manifest, expected);
+    let pos = fs::read(directory.join("pos.npy")).unwrap();
+    assert_eq!(pos.len(), NPY_HEADER_LEN + 3 * 2 * 3 * 8);
+    assert!(String::from_utf8_lossy(&pos[..NPY_HEADER_LEN]).contains("'shape': (3, 2, 3)"));
+    let iteration = fs::read(directory.join("iteration.npy")).unwrap();
+    let last = &iteration[iteration.len() - 8..];
+    assert_eq!(f64::from_le_bytes(last.try_into().unwrap()), 4.);
+    fs::remove_dir_all(directory).unwrap();
+  }
+}
@@ -1,14 +1,22 @@
 use crate::grid_search;
+use crate::iohprofiler;
+use crate::optimizers::diversity::Diversity;
+use crate::optimizers::observer::ObserverFactory;
+use crate::optimizers::spectrum::Spectrum;
 use crate::optimizers::traits::{DataExporter, Optimizer, ParamValue};
+use crate::particles::initializer::Init;
 use crate::particles::traits::{Behavior, Edge, Position, Velocity};
 use crate::problems;
+use crate::recording::{Format, Policy, TrajectoryStream};
 use crate::Normalizer;
 use indicatif::{ProgressBar, ProgressStyle};
 use nalgebra::DVector;
 use problems::Problem;
-use std::sync::{Arc, Mutex};
+use serde_json::Map;
+use std::sync::Arc;
 extern crate chrono;
 use rand::distributions::{Distribution, Uniform};
+use rand::{rngs::StdRng, Rng, SeedableRng};
 use rand_distr::Normal;
 use rayon::prelude::*;
 use std::{collections::HashMap, fs, io, path::PathBuf};
@@ -97,18 +105,6 @@ pub fn create_directory(path: PathBuf, addable: bool, ask_clear: bool) {
   }
 }
 
-fn flush_to_separate_files(
-  batch: &[(usize, String)],
-  out_directory: PathBuf,
-) -> Result<(), Box<dyn std::error::Error>> {
-  fs::create_dir_all(out_directory.clone())?;
-  for (attempt, json_str) in batch {
-    let file_path = out_directory.join(format!("{}", attempt)).join("data.json");
-    fs::write(&file_path, json_str)?;
-  }
-  Ok(())
-}
-
 #[allow(clippy::too_many_arguments)]
 pub fn run_attempts<U: Position + Velocity + Clone, T: Optimizer<U> + DataExporter<U>>(
   params: HashMap<String, ParamValue>,
@@ -119,48 +115,169 @@ pub fn run_attempts<U: Position + Velocity + Clone, T: Optimizer<U> + DataExport
   attempts: usize,
   save_data: bool,
   bar: &indicatif::ProgressBar,
+  observers: Option<ObserverFactory<U>>,
 ) -> Result<(), Box<dyn std::error::Error>> {
-  let batch_data = Arc::new(Mutex::new(Vec::new()));
-  let flush_size = 10;
+  let format = match params.contains_key("trajectory_format") {
+    true => match params["trajectory_format"] {
+      ParamValue::Format(val) => val,
+      _ => {
+        eprintln!("Error: parameter 'trajectory_format' should be of type Param::Format.");
+        std::process::exit(1);
+      }
+    },
+    false => Format::Json,
+  };
+  let policy = match params.contains_key("recording") {
+    true => match &params["recording"] {
+      ParamValue::Recording(val) => val.clone(),
+      _ => {
+        eprintln!("Error: parameter 'recording' should be of type Param::Recording.");
+        std::process::exit(1);
+      }
+    },
+    false => Policy::default(),
+  };
+  let diversity = match params.contains_key("diversity") {
+    true => match params["diversity"] {
+      ParamValue::Bool(val) => val,
+      _ => {
+        eprintln!("Error: parameter 'diversity' should be of type Param::Bool.");
+        std::process::exit(1);
+      }
+    },
+    false => false,
+  };
+  let spectrum = match params.contains_key("spectrum") {
+    true => match params["spectrum"] {
+      ParamValue::Bool(val) => val,
+      _ => {
+        eprintln!("Error: parameter 'spectrum' should be of type Param::Bool.");
+        std::process::exit(1);
+      }
+    },
+    false => false,
+  };
+  // Per-iteration swarm statistics in summary.json, with `metrics: true`. They are off by
+  // default since the spread and diameter cost a pairwise pass over the swarm per iteration.
+  let metrics = match params.contains_key("metrics") {
+    true => match params["metrics"] {
+      ParamValue::Bool(val) => val,
+      _ => {
+        eprintln!("Error: parameter 'metrics' should be of type Param::Bool.");
+        std::process::exit(1);
+      }
+    },
+    false => false,
+  };
+  let iohprofiler = match params.contains_key("iohprofiler") {
+    true => match &params["iohprofiler"] {
+      ParamValue::IohProfiler(val) => Some(val.clone()),
+      _ => {
+        eprintln!("Error: parameter 'iohprofiler' should be of type Param::IohProfiler.");
+        std::process::exit(1);
+      }
+    },
+    false => None,
+  };
+  // Vectors of the particles initialized with `Init::File`, one per line, shared by the
+  // attempts.
+  let initial_positions = match params.contains_key("initial_positions") {
+    true => match &params["initial_positions"] {
+      ParamValue::Path(val) => Some(load_vectors(val.clone())?),
+      _ => {
+        eprintln!("Error: parameter 'initial_positions' should be of type Param::Path.");
+        std::process::exit(1);
+      }
+    },
+    false => None,
+  };
+  let initial_velocities = match params.contains_key("initial_velocities") {
+    true => match &params["initial_velocities"] {
+      ParamValue::Path(val) => Some(load_vectors(val.clone())?),
+      _ => {
+        eprintln!("Error: parameter 'initial_velocities' should be of type Param::Path.");
+        std::process::exit(1);
+      }
+    },
+    false => None,
+  };
+  // data/<test>/<dim>/<optimizer>/<problem> is logged to data/<test>/iohprofiler/<optimizer>.
+  let logger = iohprofiler.as_ref().map(|tracked| {
+    let test = out_directory.ancestors().nth(3).unwrap_or(&out_directory);
+    iohprofiler::Logger::new(test.join("iohprofiler").join(&name), name.clone(), tracked.clone())
+  });
   (0..attempts).into_par_iter().for_each(|attempt| {
     let save = save_data;
+    // Every attempt evaluates the shared problem definition with its own counters.
+    let mut attempt_problem = problem.fork();
+    if let Some(positions) = &initial_positions {
+      attempt_problem.sampler().set_initial_positions(positions.clone());
+    }
+    if let Some(velocities) = &initial_velocities {
+      attempt_problem.sampler().set_initial_velocities(velocities.clone());
+    }
+    let mut attempt_params = params.clone();
+    // The initializers of the attempt draw from its seed too.
+    let sampler_seed = match params.get("seed") {
+      Some(ParamValue::Int(seed)) => {
+        let seed = attempt_seed(*seed, attempt);
+        attempt_params.insert("seed".to_owned(), ParamValue::Int(seed));
+        seed as u64
+      }
+      _ => rand::thread_rng().gen(),
+    };
+    attempt_problem.sampler().set_seed(sampler_seed);
     let mut pso: T = T::new(
       name.clone(),
-      problem.clone(),
-      params.clone(),
+      attempt_problem,
+      attempt_params,
       out_directory.join(format!("{}", attempt)),
       save,
     );
-    pso.run(iterations);
-    let _ = pso.save_summary();
-    let _ = pso.save_config(&params);
+    // Tracked IOHprofiler columns may be metrics, e.g. g.
+    pso.recorder().set_metrics(metrics || iohprofiler.as_ref().is_some_and(|tracked| !tracked.is_empty()));
+    // The trajectory is written while the optimizer runs.
     if save_data {
-      if let Ok(json_str) = pso.generate_data_json() {
-        let mut batch = batch_data.lock().unwrap();
-        batch.push((attempt, json_str));
-
-        if batch.len() >= flush_size {
-          let to_write = batch.drain(..).collect::<Vec<_>>();
-          drop(batch);
-
-          if let Err(e) = flush_to_separate_files(&to_write, out_directory.clone()) {
-            eprintln!("Failed to write batch to disk: {}", e);
-          }
-        }
+      match TrajectoryStream::create(&format.path(pso.out_directory()), format, policy.clone()) {
+        Ok(trajectory) => pso.recorder().set_trajectory(Some(trajectory)),
+        Err(e) => eprintln!("Failed to create the trajectory file: {}", e),
       }
     }
-    bar.inc(1);
-  });
-  {
-    let mut batch = batch_data.lock().unwrap();
-    if !batch.is_empty() {
-      let to_write = batch.drain(..).collect::<Vec<_>>();
-      drop(batch);
-
-      if let Err(e) = flush_to_separate_files(&to_write, out_directory.clone()) {
-        eprintln!("Failed to write final batch to disk: {}", e);
+    let mut attempt_observers = match &observers {
+      Some(observers) => observers(attempt),
+      None => Vec::new(),
+    };
+    if diversity {
+      attempt_observers.push(Box::new(Diversity::default()));
+    }
+    if spectrum {
+      attempt_observers.push(Box::new(Spectrum::default()));
+    }
+    match attempt_observers.is_empty() {
+      true => pso.run(iterations),
+      false => pso.run_observed(iterations, &mut attempt_observers),
+    }
+    if let Some(trajectory) = pso.recorder().trajectory() {
+      if let Err(e) = trajectory.finish() {
+        eprintln!("Failed to write the trajectory: {}", e);
       }
     }
+    let mut observed = Map::new();
+    for observer in attempt_observers.iter() {
+      observed.extend(observer.summary());
+    }
+    let _ = pso.save_summary(observed);
+    let _ = pso.save_config(&params);
+    if let (Some(logger), Some(tracked)) = (&logger, &iohprofiler) {
+      logger.add(attempt, pso.iohprofiler_run(tracked, &params));
+    }
+    if save_data && !pso.additional_data().is_empty() {
+      let _ = pso.save_additional_data();
+    }
+    bar.inc(1);
+  });
+  if let Some(logger) = logger {
+    logger.finish(problem.name(), problem.dim())?;
   }
 
   Ok(())
@@ -177,6 +294,7 @@ pub fn check_problem<T: Velocity + Clone, U: Optimizer<T>>(
   params_in_vec: Vec<(&str, ParamValue)>,
   problem: Problem,
   save: bool,
+  observers: Option<ObserverFactory<T>>,
 ) -> Result<(), Box<dyn std::error::Error>> {
   let params = param_hashmap_generator(params_in_vec);
   // Progress Bar.
@@ -195,11 +313,12 @@ pub fn check_problem<T: Velocity + Clone, U: Optimizer<T>>(
     params.clone(),
     optimizer_name.to_owned().clone(),
     problem.clone(),
-    out_directory.join(problem.clone().name()),
+    out_directory.join(problem.name()),
     iterations,
     attempts,
     save,
     &bar,
+    observers,
   );
 
   Ok(())
@@ -243,11 +362,64 @@ pub fn check_cec17<T: Velocity + Clone, U: Optimizer<T>>(
       params.clone(),
       optimizer_name.to_owned().clone(),
       problem.clone(),
-      out_directory.join(problem.clone().name()),
+      out_directory.join(problem.name()),
+      iterations,
+      attempts,
+      save,
+      &bar,
+      None,
+    );
+  });
+
+  Ok(())
+}
+
+// Runs the 24 BBOB functions on every instance, `attempts` times each. COCO expects
+// one attempt on each of 15 instances.
+#[allow(clippy::too_many_arguments)]
+#[allow(dead_code)]
+pub fn check_bbob<T: Velocity + Clone, U: Optimizer<T>>(
+  test_name: &str,
+  optimizer_name: &str,
+  iterations: usize,
+  dim: usize,
+  instances: Vec<usize>,
+  attempts: usize,
+  params_in_vec: Vec<(&str, ParamValue)>,
+  save: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+  let params = param_hashmap_generator(params_in_vec);
+  // Progress Bar.
+  let bar = ProgressBar::new((24 * instances.len() * attempts) as u64);
+  bar.set_style(
+    ProgressStyle::default_bar()
+      .template("{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {msg}")
+      .unwrap()
+      .progress_chars("#>-"),
+  );
+  bar.set_message(format!("{}...   ", optimizer_name));
+
+  let mut problems = Vec::new();
+  for func_num in 1..=24 {
+    for instance in instances.iter() {
+      problems.push((func_num, *instance));
+    }
+  }
+
+  let out_directory = generate_out_directory(test_name, dim, optimizer_name);
+
+  problems.into_par_iter().for_each(|(func_num, instance)| {
+    let problem = problems::bbob(func_num, instance, dim);
+    let _ = run_attempts::<T, U>(
+      params.clone(),
+      optimizer_name.to_owned().clone(),
+      problem.clone(),
+      out_directory.join(problem.name()),
       iterations,
       attempts,
       save,
       &bar,
+      None,
     );
   });
 
@@ -345,10 +517,27 @@ pub fn rank_normalize(input: Vec<f64>) -> Vec<f64> {
 }
 
 fn sigmoid_normalize(input: Vec<f64>, scale: f64) -> Vec<f64> {
-  z_score_normalize(input).into_iter().map(|x| 1.0 / (1.0 + (-1. * scale * x).exp())).collect()
+  z_score_normalize(input).into_iter().map(|x| 1.0 / (1.0 + (-scale * x).exp())).collect()
 }
 
 // mass
+// Applies a mass function to the feasible particles only. Infeasible ones, whose
+// fitness is infinite, have no mass and do not skew the normalization of the others.
+pub fn feasible_mass(input: Vec<f64>, mass: fn(Vec<f64>) -> Vec<f64>) -> Vec<f64> {
+  let feasible: Vec<f64> = input.iter().copied().filter(|f| f.is_finite()).collect();
+  if feasible.is_empty() {
+    return vec![0.0; input.len()];
+  }
+  let mut masses = mass(feasible).into_iter();
+  input
+    .iter()
+    .map(|f| match f.is_finite() {
+      true => masses.next().unwrap(),
+      false => 0.,
+    })
+    .collect()
+}
+
 pub fn original_gsa_mass(input: Vec<f64>) -> Vec<f64> {
   let input: Vec<f64> = min_max_normalize(input).iter().map(|x| 1. - x).collect();
   let sum: f64 = input.iter().sum();
@@ -363,7 +552,7 @@ pub fn original_gsa_mass_with_record(input: Vec<Vec<f64>>, _k: usize) -> Vec<Vec
   // result.push(original_gsa_mass(input.last().unwrap().clone()));
   // println!("{:?}", result);
   // result
-  vec![original_gsa_mass(input.last().unwrap().clone())]
+  vec![feasible_mass(input.last().unwrap().clone(), original_gsa_mass)]
 
   // let flattened: Vec<f64> = input.iter().flat_map(|inner| inner.iter().cloned()).collect();
   // let min = flattened.iter().fold(f64::INFINITY, |a, &b| a.min(b));
@@ -440,7 +629,9 @@ pub fn behavior_from_tiled(tiled: bool) -> ParamValue {
       true => Edge::Cycle,
       false => Edge::Pass,
     },
-    vmax: false,
+    vmax: None,
+    pos_init: Init::Uniform,
+    vel_init: None,
   })
 }
 
@@ -466,6 +657,7 @@ pub fn name_from_normalizer_and_tiled(normalizer: Normalizer, tiled: bool) -> St
   }
 }
 
+#[allow(dead_code)]
 pub fn calculate_std(data: &[f64]) -> f64 {
   let mean = data.iter().sum::<f64>() / data.len() as f64;
   let variance = data
@@ -478,3 +670,132 @@ pub fn calculate_std(data: &[f64]) -> f64 {
     / data.len() as f64;
   variance.sqrt()
 }
+
+// Mean Euclidean distance over all pairs, as in the MGSA/RGSA spread.
+pub fn average_pairwise_distance(points: &[DVector<f64>]) -> f64 {
+  pairwise_distances(points).1
+}
+
+// Largest and mean Euclidean distance between two points, in a single pass over the
+// pairs.
+pub fn pairwise_distances(points: &[DVector<f64>]) -> (f64, f64) {
+  let n = points.len();
+  if n < 2 {
+    return (0., 0.);
+  }
+  let (mut diameter, mut sum): (f64, f64) = (0., 0.);
+  for i in 0..n {
+    for j in i + 1..n {
+      let distance = (&points[i] - &points[j]).norm();
+      diameter = diameter.max(distance);
+      sum += distance;
+    }
+  }
+  (diameter, sum / (n * (n - 1) / 2) as f64)
+}
+
+// Reads one vector per line, with elements separated by commas or whitespace.
+pub fn load_vectors(path: PathBuf) -> Result<Vec<DVector<f64>>, Box<dyn std::error::Error>> {
+  let mut vectors = Vec::new();
+  for line in fs::read_to_string(path)?.lines() {
+    let elements = line
+      .split(|c: char| c == ',' || c.is_whitespace())
+      .filter(|e| !e.is_empty())
+      .map(|e| e.parse::<f64>())
+      .collect::<Result<Vec<f64>, _>>()?;
+    if !elements.is_empty() {
+      vectors.push(DVector::from_vec(elements));
+    }
+  }
+  Ok(vectors)
+}
+
+// Seed of one attempt, so that the attempts of a run with a fixed `seed` parameter
+// differ while staying reproducible. The first attempt keeps the seed.
+pub fn attempt_seed(seed: isize, attempt: usize) -> isize {
+  (seed as u64 ^ (attempt as u64).wrapping_mul(0x94d0_49bb_1331_11eb)) as isize
+}
+
+// Random number generator of one particle at one iteration. It only depends on its
+// arguments, so results do not change with the order in which particles are processed.
+pub fn particle_rng(seed: u64, iter: usize, idx: usize) -> StdRng {
+  let mixed =
+    seed ^ (iter as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15) ^ (idx as u64).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+  StdRng::seed_from_u64(mixed)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::optimizers::observer::Observer;
+  use crate::optimizers::pso::Pso;
+  use crate::particles::pso::PsoParticle;
+  use std::sync::Mutex;
+
+  type Vectors = Vec<(DVector<f64>, DVector<f64>)>;
+
+  // Positions and velocities of the particles of every attempt before the first step.
+  struct Initial(Arc<Mutex<Vec<Vectors>>>);
+
+  impl Observer<PsoParticle> for Initial {
+    fn on_init(&mut self, particles: &[PsoParticle]) {
+      let vectors = particles.iter().map(|p| (p.pos().clone(), p.vel().clone())).collect();
+      self.0.lock().unwrap().push(vectors);
+    }
+  }
+
+  #[test]
+  fn attempts_start_from_the_loaded_vectors() {
+    let directory = std::env::temp_dir().join(format!("psobench-initial-{}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    let positions: Vec<DVector<f64>> = (0..4).map(|i| DVector::from_fn(3, |d, _| 10. * i as f64 - d as f64)).collect();
+    let velocities: Vec<DVector<f64>> = (0..4).map(|i| DVector::from_element(3, 0.5 * i as f64)).collect();
+    let lines = |vectors: &[DVector<f64>], separator: &str| {
+      let lines: Vec<String> =
+        vectors.iter().map(|v| v.iter().map(|x| x.to_string()).collect::<Vec<String>>().join(separator)).collect();
+      lines.join("\n")
+    };
+    fs::write(directory.join("positions.csv"), lines(&positions, ",")).unwrap();
+    fs::write(directory.join("velocities.txt"), lines(&velocities, " ")).unwrap();
+
+    let params = param_hashmap_generator(vec![
+      ("particle_count", ParamValue::Int(4)),
+      ("w", ParamValue::Float(0.7)),
+      ("phi_p", ParamValue::Float(1.5)),
+      ("phi_g", ParamValue::Float(1.5)),
+      (
+        "behavior",
+        ParamValue::Behavior(Behavior {
+          edge: Edge::Pass,
+          vmax: None,
+          pos_init: Init::File,
+          vel_init: Some(Init::File),
+        }),
+      ),
+      ("initial_positions", ParamValue::Path(directory.join("positions.csv"))),
+      ("initial_velocities", ParamValue::Path(directory.join("velocities.txt"))),
+    ]);
+    let initial = Arc::new(Mutex::new(Vec::new()));
+    let shared = initial.clone();
+    let observers: ObserverFactory<PsoParticle> =
+      Arc::new(move |_| vec![Box::new(Initial(shared.clone())) as Box<dyn Observer<PsoParticle>>]);
+    run_attempts::<PsoParticle, Pso<PsoParticle>>(
+      params,
+      "pso".to_owned(),
+      problems::sphere_100(3),
+      directory.join("pso"),
+      1,
+      2,
+      false,
+      &ProgressBar::hidden(),
+      Some(observers),
+    )
+    .unwrap();
+
+    let expected: Vectors = positions.into_iter().zip(velocities).collect();
+    let initial = initial.lock().unwrap();
+    assert_eq!(initial.len(), 2);
+    assert!(initial.iter().all(|attempt| *attempt == expected));
+    let _ = fs::remove_dir_all(directory);
+  }
+}
@@ -8,7 +8,7 @@ from constants import CACHE, DATA
 def compute_attempt_checksum(attempt_path: pathlib.Path) -> str:
     """Compute a lightweight checksum based on file metadata for an attempt."""
     hash_sha256 = hashlib.sha256()
-    for file_path in sorted(attempt_path.iterdir()):
+    for file_path in sorted(attempt_path.rglob("*")):
         if file_path.is_file():
             stat = file_path.stat()
             # Incorporate file name, size, and modification time
@@ -18,9 +18,9 @@ def scatter_progress(pso: PSO, ax: Axes, label: str) -> Any:
     pso.load_full()
     x = []
     y = []
-    for i, iteration in enumerate(pso.iterations):
+    for iteration in pso.iterations:
         for particle in iteration.particles:
-            x.append(i)
+            x.append(iteration.index)
             y.append(particle.fitness)
 
     scatter = ax.scatter(x, y, s=0.01, rasterized=True)
@@ -54,7 +54,9 @@ def plot_progress(
     """
     pso.load_full()
     y = []
-    for i, iteration in enumerate(pso.iterations):
+    indices = []
+    for iteration in pso.iterations:
+        indices.append(iteration.index)
         min_fitness = 1e20
         for particle in iteration.particles:
             min_fitness = min(min_fitness, particle.fitness)
@@ -74,8 +76,9 @@ def plot_progress(
         original_x, smoothed_x, smoothed_percentages
     )
 
+    # Plotted against the recorded iterations, as only some may be saved
     ax.plot(
-        original_x,
+        indices,
         interpolated_y,
         label=label,
         linewidth=width,
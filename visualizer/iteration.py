@@ -4,6 +4,8 @@ from typing import List
 
 class Iteration:
     def __init__(self, global_best_fitness: float,
-                 particles: List[Particle]) -> None:
+                 particles: List[Particle], index: int) -> None:
         self.particles = particles
         self.global_best_fitness = global_best_fitness
+        # Index of the iteration in the run, as only some may be recorded
+        self.index = index
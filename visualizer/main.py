@@ -39,7 +39,7 @@ def generate_single_graph() -> None:
         name_dict = {"pso": "PSO", "ogsa": "GSA"}
         name = attempt.parent.parent.name.split("_")[0]
         scatter_progress(pso, ax=ax, label=name_dict[name])
-        iterations.append(len(pso.iterations))
+        iterations.append(pso.iterations[-1].index + 1)
 
     legend = plt.legend(
         prop={"size": "small"}, edgecolor="black", fancybox=False
@@ -116,7 +116,7 @@ def generate_multiple_graphs() -> None:
             color=color_dict.get(name, "#1f77b4"),
             width=1.4 if name == "Default" else 0.8,
         )
-        iterations.append(len(pso.iterations))
+        iterations.append(pso.iterations[-1].index + 1)
 
     utils.style_legend(plt)
     plt.xlabel("Iteration")
@@ -29,18 +29,63 @@ class PSO:
 
     def load_full(self) -> None:
         # Data
+        if (self.experiment_path / "trajectory").exists():
+            self.load_npy(self.experiment_path / "trajectory")
+            return
         if not (self.experiment_path / "data.json").exists():
             raise ValueError("Full data not exported.")
         self.fully_loaded = True
         with open(self.experiment_path / "data.json", "r") as file:
             data: List[Dict[str, Any]] = json.load(file)
         iterations = []
-        for iteration_data in data:
+        for t, iteration_data in enumerate(data):
             global_best_fitness = iteration_data["global_best_fitness"]
             particles = []
             for particle in iteration_data["particles"]:
                 particles.append(Particle(particle))
-            iterations.append(Iteration(global_best_fitness, particles))
+            # Only written when some iterations are not recorded
+            index = iteration_data.get("iteration", t)
+            iterations.append(
+                Iteration(global_best_fitness, particles, index))
+        self.iterations = iterations
+
+    def load_npy(self, path: pathlib.Path) -> None:
+        # Trajectory exported with the Npy format, one array per field, and
+        # the manifest telling which arrays hold a value per particle
+        self.fully_loaded = True
+        with open(path / "manifest.json", "r") as file:
+            manifest: Dict[str, Any] = json.load(file)
+        global_best_fitness = np.load(path / "global_best_fitness.npy")
+        if "iteration" in manifest["iteration_fields"]:
+            indices = [int(t) for t in np.load(path / "iteration.npy")]
+        else:
+            indices = list(range(manifest["iterations"]))
+        fields = {
+            name: np.load(path / f"{name}.npy")
+            for name in manifest["particle_fields"]
+        }
+        if manifest["concatenated"]:
+            # The particle count changed, so rows of all iterations are
+            # concatenated
+            counts = np.load(path / "particle_count.npy")
+            offsets = np.concatenate(([0], np.cumsum(counts)))
+            rows = [slice(offsets[t], offsets[t + 1])
+                    for t in range(len(counts))]
+        else:
+            rows = [t for t in range(manifest["iterations"])]
+        iterations = []
+        for t, row in enumerate(rows):
+            values = {key: value[row].tolist()
+                      for key, value in fields.items()}
+            # Any of the recorded fields gives the number of particles
+            count = len(next(iter(values.values()))) if values else 0
+            particles = [
+                Particle({key: values[key][n] for key in values})
+                for n in range(count)
+            ]
+            iterations.append(
+                Iteration(float(global_best_fitness[t]), particles,
+                          indices[t]))
         self.iterations = iterations
 
     def load_additional(self) -> None:
@@ -104,6 +149,30 @@ class PSO:
             return result
         raise ValueError("Incorrect dictionary type.")
 
+    def particle_count_progress(self) -> List[int]:
+        # Only written when the swarm size changes during the run.
+        if "particle_count" in self.summary:
+            particle_count = self.summary["particle_count"]
+            if not (
+                isinstance(particle_count, list)
+                and all(isinstance(i, int) for i in particle_count)
+            ):
+                raise ValueError("Incorrect dictionary type.")
+            return particle_count
+        if self.fully_loaded:
+            return [len(iteration.particles) for iteration in self.iterations]
+        count = self.config["method"]["parameters"]["particle_count"]
+        return [count] * len(self.summary["global_best_fitness"])
+
+    def out_of_bounds_progress(self) -> List[int]:
+        result = self.summary["out_of_bounds"]
+        if not (
+            isinstance(result, list)
+            and all(isinstance(i, int) for i in result)
+        ):
+            raise ValueError("Incorrect dictionary type.")
+        return result
+
     def global_best_fitness(self, idx: int = -1) -> float:
         progress = self.summary["global_best_fitness"]
         if idx != -1 and not (idx >= 0 and idx < len(progress)):